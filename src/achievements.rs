@@ -0,0 +1,93 @@
+use sdl2::pixels::Color;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+
+use crate::display::draw_text_centered;
+use crate::events::{EventListener, GameEvent};
+use crate::game::TICKS_PER_SECOND;
+
+const BANNER_TICKS: u64 = 3 * TICKS_PER_SECOND as u64;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Achievement {
+    GhostHunter, // Eat a ghost
+    FullHouse,   // Eat all four ghosts on one energizer
+    FruitLover,  // Eat a fruit
+    Flawless,    // Clear a level without dying
+}
+
+impl Achievement {
+    pub fn label(self) -> &'static str {
+        match self {
+            Achievement::GhostHunter => "Ghost Hunter",
+            Achievement::FullHouse => "Full House",
+            Achievement::FruitLover => "Fruit Lover",
+            Achievement::Flawless => "Flawless",
+        }
+    }
+}
+
+// Worked out from the game's events alone. Unlocked achievements last for
+// the session and are announced with a banner.
+#[derive(Default)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+    ghosts_this_energizer: u32,
+    died_this_level: bool,
+    banner: Option<(Achievement, u64)>, // Shown until the tick
+}
+
+impl Achievements {
+    fn unlock(&mut self, achievement: Achievement, tick: u64) {
+        if !self.unlocked.contains(&achievement) {
+            self.unlocked.push(achievement);
+            self.banner = Some((achievement, tick + BANNER_TICKS));
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        center: (i32, i32),
+        tick: u64,
+    ) -> Result<(), String> {
+        match self.banner {
+            Some((achievement, until)) if tick < until => draw_text_centered(
+                canvas,
+                font,
+                &format!("ACHIEVEMENT: {}", achievement.label()),
+                center,
+                Color::RGB(255, 255, 0),
+            ),
+            _ => {
+                self.banner = None;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl EventListener for Achievements {
+    fn on_event(&mut self, tick: u64, event: &GameEvent) {
+        match event {
+            GameEvent::EnergizerEaten { .. } => self.ghosts_this_energizer = 0,
+            GameEvent::GhostEaten { .. } => {
+                self.ghosts_this_energizer += 1;
+                self.unlock(Achievement::GhostHunter, tick);
+                if self.ghosts_this_energizer == 4 {
+                    self.unlock(Achievement::FullHouse, tick);
+                }
+            }
+            GameEvent::FruitEaten { .. } => self.unlock(Achievement::FruitLover, tick),
+            GameEvent::PacmanDied { .. } => self.died_this_level = true,
+            GameEvent::LevelCleared { .. } => {
+                if !self.died_this_level {
+                    self.unlock(Achievement::Flawless, tick);
+                }
+                self.died_this_level = false;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+// Settings that make the game easier, for newer players or anyone who needs
+// more time. A game played with any of them on is marked in the high
+// scores. Read from config.toml's [assists] table.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct Assists {
+    pub game_speed: f32,               // From 0.5 to 1.0 of the arcade's speed
+    pub extra_lives: u32,              // On top of the usual three
+    pub extra_frightened_seconds: u32, // Added to every energizer
+    pub tourist_mode: bool,            // Ghosts pass through Pac-Man harmlessly
+    pub turn_assist: bool,             // Pac-Man takes the next turn rather than stopping at a wall
+}
+
+impl Default for Assists {
+    fn default() -> Self {
+        Assists {
+            game_speed: 1.0,
+            extra_lives: 0,
+            extra_frightened_seconds: 0,
+            tourist_mode: false,
+            turn_assist: false,
+        }
+    }
+}
+
+impl Assists {
+    pub const MIN_GAME_SPEED: f32 = 0.5;
+
+    // Game ticks per frame, kept within what the setting allows
+    pub fn game_speed(&self) -> f32 {
+        if self.game_speed.is_nan() {
+            return 1.0;
+        }
+        self.game_speed.clamp(Assists::MIN_GAME_SPEED, 1.0)
+    }
+
+    pub fn any(&self) -> bool {
+        self.game_speed() < 1.0
+            || self.extra_lives > 0
+            || self.extra_frightened_seconds > 0
+            || self.tourist_mode
+            || self.turn_assist
+    }
+
+    // Short descriptions of the assists that are on, for the HUD and the
+    // high score table
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels = Vec::new();
+        if self.game_speed() < 1.0 {
+            labels.push(format!("SPEED {:.0}%", self.game_speed() * 100.0));
+        }
+        if self.extra_lives > 0 {
+            labels.push(format!("+{} LIVES", self.extra_lives));
+        }
+        if self.extra_frightened_seconds > 0 {
+            labels.push(format!("+{}S FRIGHT", self.extra_frightened_seconds));
+        }
+        if self.tourist_mode {
+            labels.push("TOURIST".to_string());
+        }
+        if self.turn_assist {
+            labels.push("TURNS".to_string());
+        }
+        labels
+    }
+}
@@ -0,0 +1,292 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+use std::time::Duration;
+
+use crate::display::{draw_text, draw_text_centered};
+use crate::game::{Game, Ghost, GhostName, Phase, TICKS_PER_SECOND};
+use crate::high_scores::HighScores;
+use crate::render::{draw_ghost, draw_pacman};
+use crate::sprites::SpriteAtlas;
+use crate::theme::Theme;
+
+const TITLE_IDLE_TICKS: u32 = 5 * TICKS_PER_SECOND; // Before the attract sequence starts
+const INTRO_STEP_TICKS: u32 = TICKS_PER_SECOND; // Between one line of the introduction and the next
+const INTRO_TICKS: u32 = 8 * INTRO_STEP_TICKS; // Four ghosts, the points and a pause
+const DEMO_TICKS: u32 = 45 * TICKS_PER_SECOND; // Longest a demo is shown
+const DEMO_GAME_OVER_TICKS: u32 = 3 * TICKS_PER_SECOND;
+const INTERMISSION_TICKS: u32 = 8 * TICKS_PER_SECOND; // Both halves of the chase
+
+// What the window shows. Idling on the title runs through the character
+// introduction and a demo game played by the bot, then back to the title,
+// like an arcade cabinet waiting for a coin.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Screen {
+    Title { idle_ticks: u32 },
+    CharacterIntro { ticks: u32 },
+    Demo { ticks_left: u32 },
+    Playing,
+    Editor,
+    Intermission { ticks: u32 }, // A cutscene between a pack's levels, then back to playing
+}
+
+impl Screen {
+    pub fn title() -> Screen {
+        Screen::Title { idle_ticks: 0 }
+    }
+
+    pub fn demo() -> Screen {
+        Screen::Demo {
+            ticks_left: DEMO_TICKS,
+        }
+    }
+
+    // The screen after this one has been shown for a tick. `demo_game` is
+    // the game being played in the demo, if it is on.
+    pub fn advance(self, demo_game: &Game) -> Screen {
+        match self {
+            Screen::Title { idle_ticks } if idle_ticks + 1 >= TITLE_IDLE_TICKS => {
+                Screen::CharacterIntro { ticks: 0 }
+            }
+            Screen::Title { idle_ticks } => Screen::Title {
+                idle_ticks: idle_ticks + 1,
+            },
+            Screen::CharacterIntro { ticks } if ticks + 1 >= INTRO_TICKS => Screen::demo(),
+            Screen::CharacterIntro { ticks } => Screen::CharacterIntro { ticks: ticks + 1 },
+            Screen::Demo { ticks_left } => {
+                // The demo ends a moment after Pac-Man's only life is lost
+                let ticks_left = if demo_game.phase == Phase::GameOver {
+                    ticks_left.min(DEMO_GAME_OVER_TICKS)
+                } else {
+                    ticks_left
+                };
+                if ticks_left <= 1 {
+                    Screen::title()
+                } else {
+                    Screen::Demo {
+                        ticks_left: ticks_left - 1,
+                    }
+                }
+            }
+            Screen::Playing => Screen::Playing,
+            Screen::Editor => Screen::Editor,
+            Screen::Intermission { ticks } if ticks + 1 >= INTERMISSION_TICKS => Screen::Playing,
+            Screen::Intermission { ticks } => Screen::Intermission { ticks: ticks + 1 },
+        }
+    }
+}
+
+// The title, and the high scores under it with games played with assists
+// marked
+pub fn draw_title(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    idle_ticks: u32,
+    high_scores: &HighScores,
+) -> Result<(), String> {
+    let viewport = canvas.viewport();
+    let center_x = viewport.width() as i32 / 2;
+    let height = viewport.height() as i32;
+    draw_text_centered(
+        canvas,
+        font,
+        "PAC-MAN",
+        (center_x, height / 3),
+        Color::RGB(255, 255, 0),
+    )?;
+    if (idle_ticks / (TICKS_PER_SECOND / 2)).is_multiple_of(2) {
+        draw_text_centered(
+            canvas,
+            font,
+            "PRESS START",
+            (center_x, height / 2),
+            Color::RGB(255, 255, 255),
+        )?;
+    }
+
+    let entries = high_scores.entries();
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let line_height = font.height();
+    let top = height * 3 / 5;
+    draw_text_centered(
+        canvas,
+        font,
+        "HIGH SCORES",
+        (center_x, top),
+        Color::RGB(255, 184, 82),
+    )?;
+    for (i, entry) in entries.iter().enumerate() {
+        let (marker, color) = if entry.assisted() {
+            ("*", Color::RGB(0, 255, 255))
+        } else {
+            (" ", Color::RGB(255, 255, 255))
+        };
+        draw_text_centered(
+            canvas,
+            font,
+            &format!(
+                "{:>2}  {:>7}{}  LEVEL {}",
+                i + 1,
+                entry.score,
+                marker,
+                entry.level
+            ),
+            (center_x, top + line_height * (i as i32 + 2)),
+            color,
+        )?;
+    }
+    if entries.iter().any(|entry| entry.assisted()) {
+        draw_text_centered(
+            canvas,
+            font,
+            "* PLAYED WITH ASSISTS",
+            (center_x, top + line_height * (entries.len() as i32 + 3)),
+            Color::RGB(0, 255, 255),
+        )?;
+    }
+    Ok(())
+}
+
+// The ghosts come on one at a time with their nicknames, then what the
+// pellets are worth
+pub fn draw_character_intro(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    ticks: u32,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let white = Color::RGB(255, 255, 255);
+    let row_y = |row: f32| (row * tile_size) as i32;
+    let ghost_x = tile_size * 4.0;
+    let nickname_x = (tile_size * 6.0) as i32;
+    let name_x = (tile_size * 16.0) as i32;
+    draw_text(
+        canvas,
+        font,
+        "CHARACTER / NICKNAME",
+        (nickname_x, row_y(4.0)),
+        white,
+    )?;
+
+    let step = ticks / INTRO_STEP_TICKS;
+    let half_step = ticks % INTRO_STEP_TICKS >= INTRO_STEP_TICKS / 2;
+    for (i, name) in GhostName::ALL.into_iter().enumerate() {
+        if step < i as u32 {
+            break;
+        }
+        let row = 7.0 + i as f32 * 3.0;
+        let mut ghost = Ghost::new(name);
+        ghost.pos = (ghost_x, (row + 0.5) * tile_size);
+        ghost.direction = (1.0, 0.0);
+        draw_ghost(
+            canvas,
+            &ghost,
+            false,
+            ticks as u64,
+            tile_size * 1.5,
+            sprite_atlas,
+            elapsed,
+            theme,
+        )?;
+        // The nickname first, the name half a step later
+        if step > i as u32 || half_step {
+            let color = theme.ghost(name);
+            let nickname = format!("-{}", name.nickname().to_uppercase());
+            draw_text(canvas, font, &nickname, (nickname_x, row_y(row)), color)?;
+            let name = format!("\"{}\"", name.name().to_uppercase());
+            draw_text(canvas, font, &name, (name_x, row_y(row)), color)?;
+        }
+    }
+
+    if step >= GhostName::ALL.len() as u32 {
+        for (row, size, points) in [(20.0, 0.25, "10 PTS"), (22.0, 0.75, "50 PTS")] {
+            let centre = (ghost_x, (row + 0.5) * tile_size);
+            let size = tile_size * size;
+            canvas.set_draw_color(theme.pellet);
+            canvas.fill_rect(Rect::new(
+                (centre.0 - size / 2.0) as i32,
+                (centre.1 - size / 2.0) as i32,
+                size as u32,
+                size as u32,
+            ))?;
+            draw_text(canvas, font, points, (nickname_x, row_y(row)), white)?;
+        }
+    }
+    Ok(())
+}
+
+// The arcade's first cutscene: Blinky chases Pac-Man off one side, then a
+// giant Pac-Man chases a frightened Blinky back the other way. The title
+// comes from the level pack.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_intermission(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    ticks: u32,
+    title: &str,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let viewport = canvas.viewport();
+    let width = viewport.width() as f32;
+    let centre_y = viewport.height() as f32 / 2.0;
+    draw_text_centered(
+        canvas,
+        font,
+        title,
+        (width as i32 / 2, (centre_y - tile_size * 6.0) as i32),
+        Color::RGB(255, 255, 255),
+    )?;
+
+    // Each half crosses the screen with room either side to come on and go off
+    let half = INTERMISSION_TICKS / 2;
+    let returning = ticks >= half;
+    let progress = (ticks % half) as f32 / half as f32;
+    let run = width + tile_size * 12.0;
+    let mouth_angle = ((ticks % 8) as f32 - 4.0).abs() * 11.0;
+    let mut blinky = Ghost::new(GhostName::Blinky);
+    if !returning {
+        let pacman_x = width + tile_size * 2.0 - run * progress;
+        blinky.pos = (pacman_x + tile_size * 3.0, centre_y);
+        blinky.direction = (-1.0, 0.0);
+        draw_pacman(
+            canvas,
+            (pacman_x, centre_y),
+            tile_size * 0.75,
+            mouth_angle,
+            (-1.0, 0.0),
+            theme.pacman,
+        )?;
+    } else {
+        let pacman_x = -tile_size * 10.0 + run * progress;
+        blinky.pos = (pacman_x + tile_size * 6.0, centre_y);
+        blinky.direction = (1.0, 0.0);
+        blinky.frightened = true;
+        draw_pacman(
+            canvas,
+            (pacman_x, centre_y),
+            tile_size * 3.0,
+            mouth_angle,
+            (1.0, 0.0),
+            theme.pacman,
+        )?;
+    }
+    draw_ghost(
+        canvas,
+        &blinky,
+        false,
+        ticks as u64,
+        tile_size * 1.5,
+        sprite_atlas,
+        elapsed,
+        theme,
+    )
+}
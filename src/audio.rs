@@ -0,0 +1,405 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::AudioConfig;
+use crate::events::{EventListener, GameEvent};
+
+// Every sound is made up at runtime from oscillators and envelopes, so the
+// game needs no audio files. The one-shot sounds are rendered into buffers
+// when the device opens, the sirens are generated as they play because they
+// loop and follow the game.
+
+const SAMPLE_RATE: i32 = 44_100;
+const INTRO_SIXTEENTH: f32 = 0.13; // Seconds per sixteenth note of the intro tune
+
+// Sounds that play once
+#[derive(Clone, Copy)]
+pub enum Sound {
+    Chomp, // Alternates between the two halves of the waka-waka
+    GhostEaten,
+    Death,
+    Intro,
+}
+
+// The looping background sound
+#[derive(Clone, Copy, PartialEq)]
+pub enum Siren {
+    Off,
+    Normal(f32), // Level progress from 0.0 to 1.0, the siren rises with it
+    Frightened,
+}
+
+// Each channel has its own volume, the siren being the third one
+#[derive(Clone, Copy, PartialEq)]
+enum Channel {
+    Effects,
+    Music,
+}
+
+impl Sound {
+    fn channel(self) -> Channel {
+        match self {
+            Sound::Chomp | Sound::GhostEaten | Sound::Death => Channel::Effects,
+            Sound::Intro => Channel::Music,
+        }
+    }
+}
+
+pub struct Audio {
+    device: AudioDevice<Mixer>,
+}
+
+impl Audio {
+    pub fn open(sdl_context: &sdl2::Sdl, config: &AudioConfig) -> Result<Audio, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1), // Mono
+            samples: None,
+        };
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            Mixer::new(spec.freq as f32, config)
+        })?;
+        device.resume();
+        Ok(Audio { device })
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        self.device.lock().play(sound);
+    }
+
+    pub fn set_siren(&mut self, siren: Siren) {
+        self.device.lock().siren = siren;
+    }
+}
+
+impl EventListener for Audio {
+    fn on_event(&mut self, _tick: u64, event: &GameEvent) {
+        match event {
+            GameEvent::DotEaten { .. } | GameEvent::EnergizerEaten { .. } => {
+                self.play(Sound::Chomp)
+            }
+            GameEvent::GhostEaten { .. } => self.play(Sound::GhostEaten),
+            GameEvent::PacmanDied { .. } => self.play(Sound::Death),
+            _ => {}
+        }
+    }
+}
+
+// How long the intro tune plays, also without audio so the game waits the
+// same time either way
+pub fn intro_duration() -> Duration {
+    let sixteenths: f32 = INTRO_MELODY.iter().map(|(_, length)| length).sum();
+    Duration::from_secs_f32(sixteenths * INTRO_SIXTEENTH)
+}
+
+struct Voice {
+    samples: Arc<[f32]>,
+    position: usize,
+    channel: Channel,
+}
+
+// Runs on SDL's audio thread, the game talks to it through the device lock
+struct Mixer {
+    sample_rate: f32,
+    effects_volume: f32,
+    siren_volume: f32,
+    music_volume: f32,
+    voices: Vec<Voice>,
+    chomps: [Arc<[f32]>; 2],
+    next_chomp: usize,
+    ghost_eaten: Arc<[f32]>,
+    death: Arc<[f32]>,
+    intro: Arc<[f32]>,
+    siren: Siren,
+    siren_phase: f32, // Position in the current cycle of the tone, 0.0..1.0
+    siren_sweep: f32, // Position in the current rise and fall of the pitch, 0.0..1.0
+}
+
+impl Mixer {
+    fn new(sample_rate: f32, config: &AudioConfig) -> Mixer {
+        let volume = |volume: f32| volume.clamp(0.0, 1.0) * config.master_volume.clamp(0.0, 1.0);
+        Mixer {
+            sample_rate,
+            effects_volume: volume(config.effects_volume),
+            siren_volume: volume(config.siren_volume),
+            music_volume: volume(config.music_volume),
+            voices: Vec::new(),
+            chomps: [
+                render_chomp(sample_rate, 480.0, 240.0).into(),
+                render_chomp(sample_rate, 240.0, 480.0).into(),
+            ],
+            next_chomp: 0,
+            ghost_eaten: render_ghost_eaten(sample_rate).into(),
+            death: render_death(sample_rate).into(),
+            intro: render_intro(sample_rate).into(),
+            siren: Siren::Off,
+            siren_phase: 0.0,
+            siren_sweep: 0.0,
+        }
+    }
+
+    fn play(&mut self, sound: Sound) {
+        let samples = match sound {
+            Sound::Chomp => {
+                self.next_chomp = 1 - self.next_chomp;
+                // A chomp cuts off the previous one instead of piling up
+                let chomps = &self.chomps;
+                self.voices.retain(|voice| {
+                    !chomps
+                        .iter()
+                        .any(|chomp| Arc::ptr_eq(chomp, &voice.samples))
+                });
+                self.chomps[self.next_chomp].clone()
+            }
+            Sound::GhostEaten => self.ghost_eaten.clone(),
+            Sound::Death => self.death.clone(),
+            Sound::Intro => self.intro.clone(),
+        };
+        self.voices.push(Voice {
+            samples,
+            position: 0,
+            channel: sound.channel(),
+        });
+    }
+
+    fn next_siren_sample(&mut self) -> f32 {
+        let (frequency, sweeps_per_second, sample) = match self.siren {
+            Siren::Off => return 0.0,
+            Siren::Normal(progress) => {
+                // Rise and fall between a low and a high pitch, all of it
+                // higher and faster as the level goes on
+                let progress = progress.clamp(0.0, 1.0);
+                let low = 380.0 + 320.0 * progress;
+                let rise = 1.0 - (self.siren_sweep * 2.0 - 1.0).abs();
+                let frequency = low * (1.0 + 0.5 * rise);
+                (frequency, 2.5 + 2.0 * progress, sine(self.siren_phase))
+            }
+            Siren::Frightened => {
+                // Quick upward sweeps that start over
+                let frequency = 250.0 + 750.0 * self.siren_sweep;
+                (frequency, 7.5, square(self.siren_phase) * 0.5)
+            }
+        };
+        self.siren_phase = (self.siren_phase + frequency / self.sample_rate) % 1.0;
+        self.siren_sweep = (self.siren_sweep + sweeps_per_second / self.sample_rate) % 1.0;
+        sample * 0.3
+    }
+}
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let mut mixed = self.next_siren_sample() * self.siren_volume;
+            for voice in &mut self.voices {
+                if let Some(value) = voice.samples.get(voice.position) {
+                    let volume = match voice.channel {
+                        Channel::Effects => self.effects_volume,
+                        Channel::Music => self.music_volume,
+                    };
+                    mixed += value * volume;
+                    voice.position += 1;
+                }
+            }
+            *sample = mixed.clamp(-1.0, 1.0);
+        }
+        self.voices
+            .retain(|voice| voice.position < voice.samples.len());
+    }
+}
+
+// Oscillators, all taking the position in the cycle from 0.0 to 1.0
+
+fn sine(phase: f32) -> f32 {
+    (phase * TAU).sin()
+}
+
+fn square(phase: f32) -> f32 {
+    if phase < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+fn triangle(phase: f32) -> f32 {
+    1.0 - 4.0 * (phase - 0.5).abs()
+}
+
+// Linear attack, then an exponential decay for the rest of the note
+fn envelope(time: f32, attack: f32, decay: f32) -> f32 {
+    if time < attack {
+        time / attack
+    } else {
+        (-(time - attack) / decay).exp()
+    }
+}
+
+// Renders a tone whose frequency is given as a function of the time
+fn render_sweep(
+    sample_rate: f32,
+    seconds: f32,
+    wave: fn(f32) -> f32,
+    frequency: impl Fn(f32) -> f32,
+    volume: impl Fn(f32) -> f32,
+) -> Vec<f32> {
+    let mut phase = 0.0;
+    (0..(seconds * sample_rate) as usize)
+        .map(|i| {
+            let time = i as f32 / sample_rate;
+            let sample = wave(phase) * volume(time);
+            phase = (phase + frequency(time) / sample_rate) % 1.0;
+            sample
+        })
+        .collect()
+}
+
+// One half of the waka-waka: a short triangle sweep, down for "wa" and up for "ka"
+fn render_chomp(sample_rate: f32, from: f32, to: f32) -> Vec<f32> {
+    let seconds = 0.11;
+    render_sweep(
+        sample_rate,
+        seconds,
+        triangle,
+        |time| from + (to - from) * time / seconds,
+        |time| 0.4 * envelope(time, 0.005, 0.06),
+    )
+}
+
+// A fast rising zip
+fn render_ghost_eaten(sample_rate: f32) -> Vec<f32> {
+    let seconds = 0.5;
+    render_sweep(
+        sample_rate,
+        seconds,
+        square,
+        |time| 200.0 * 8.0f32.powf(time / seconds),
+        |time| 0.2 * (1.0 - time / seconds),
+    )
+}
+
+// Warbling sweeps that fall lower and lower, then two blips
+fn render_death(sample_rate: f32) -> Vec<f32> {
+    let warble_seconds = 1.4;
+    let warbles = 10.0;
+    let mut samples = render_sweep(
+        sample_rate,
+        warble_seconds,
+        triangle,
+        |time| {
+            let progress = time / warble_seconds;
+            let within_warble = (progress * warbles) % 1.0;
+            let top = 900.0 - 650.0 * progress;
+            top * (1.0 - 0.4 * within_warble)
+        },
+        |time| 0.4 * (1.0 - 0.5 * time / warble_seconds),
+    );
+    for _ in 0..2 {
+        samples.extend(render_sweep(
+            sample_rate,
+            0.12,
+            triangle,
+            |time| 150.0 + 1200.0 * time,
+            |time| 0.4 * envelope(time, 0.005, 0.04),
+        ));
+        samples.extend(std::iter::repeat_n(0.0, (0.05 * sample_rate) as usize));
+    }
+    samples
+}
+
+// Notes as MIDI numbers, None for a rest, with their lengths in sixteenths
+const INTRO_MELODY: [(Option<u8>, f32); 35] = [
+    (Some(71), 1.0), // B4
+    (Some(83), 1.0), // B5
+    (Some(78), 1.0), // F#5
+    (Some(75), 1.0), // D#5
+    (Some(83), 0.5),
+    (Some(78), 1.5),
+    (Some(75), 2.0),
+    (Some(72), 1.0), // C5
+    (Some(84), 1.0), // C6
+    (Some(79), 1.0), // G5
+    (Some(76), 1.0), // E5
+    (Some(84), 0.5),
+    (Some(79), 1.5),
+    (Some(76), 2.0),
+    (Some(71), 1.0),
+    (Some(83), 1.0),
+    (Some(78), 1.0),
+    (Some(75), 1.0),
+    (Some(83), 0.5),
+    (Some(78), 1.5),
+    (Some(75), 2.0),
+    (Some(75), 0.5), // The run up: D#5 E5 F5, F5 F#5 G5, G5 G#5 A5
+    (Some(76), 0.5),
+    (Some(77), 0.5),
+    (Some(77), 0.5),
+    (Some(78), 0.5),
+    (Some(79), 0.5),
+    (Some(79), 0.5),
+    (Some(80), 0.5),
+    (Some(81), 0.5),
+    (Some(83), 2.0), // B5
+    (None, 0.5),
+    (None, 0.5),
+    (None, 0.5),
+    (None, 0.5),
+];
+
+const INTRO_BASS: [(Option<u8>, f32); 16] = [
+    (Some(47), 3.0), // B2
+    (Some(59), 1.0), // B3
+    (Some(47), 3.0),
+    (Some(59), 1.0),
+    (Some(48), 3.0), // C3
+    (Some(60), 1.0), // C4
+    (Some(48), 3.0),
+    (Some(60), 1.0),
+    (Some(47), 3.0),
+    (Some(59), 1.0),
+    (Some(47), 3.0),
+    (Some(59), 1.0),
+    (Some(42), 2.0), // F#2, G#2, A#2, B2
+    (Some(44), 2.0),
+    (Some(46), 2.0),
+    (Some(47), 2.0),
+];
+
+fn render_notes(
+    sample_rate: f32,
+    notes: &[(Option<u8>, f32)],
+    wave: fn(f32) -> f32,
+    volume: f32,
+) -> Vec<f32> {
+    let mut samples = Vec::new();
+    for &(note, length) in notes {
+        let seconds = length * INTRO_SIXTEENTH;
+        match note {
+            Some(note) => {
+                let frequency = 440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0);
+                samples.extend(render_sweep(
+                    sample_rate,
+                    seconds,
+                    wave,
+                    |_| frequency,
+                    |time| volume * envelope(time, 0.005, seconds),
+                ));
+            }
+            None => samples.extend(std::iter::repeat_n(0.0, (seconds * sample_rate) as usize)),
+        }
+    }
+    samples
+}
+
+fn render_intro(sample_rate: f32) -> Vec<f32> {
+    let mut samples = render_notes(sample_rate, &INTRO_MELODY, square, 0.15);
+    let bass = render_notes(sample_rate, &INTRO_BASS, triangle, 0.35);
+    for (sample, bass) in samples.iter_mut().zip(bass) {
+        *sample += bass;
+    }
+    samples
+}
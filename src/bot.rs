@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::events::{EventListener, GameEvent};
+use crate::game::{tile_of, Game, GhostState, Phase};
+use crate::pathfinding::Mover;
+use crate::scent::ScentMap;
+
+// Tiles this close to a ghost that can kill Pac-Man are avoided
+const DANGER_DISTANCE: u32 = 3;
+
+// How far ahead the search bot plays each choice out
+const ROLLOUT_TICKS: u32 = 90;
+const DEATH_PENALTY: i64 = 5000;
+
+pub const BOT_NAMES: [&str; 2] = ["greedy", "search"];
+
+// What a controller gets to look at each tick. It can see everything but
+// change nothing.
+pub struct GameView<'a> {
+    pub game: &'a Game,
+    pub scent: &'a ScentMap,
+}
+
+// Something that plays Pac-Man in place of the player. It is asked every
+// tick and returns the direction to go, or None to leave the buffered one.
+pub trait Controller {
+    fn decide(&mut self, view: &GameView) -> Option<(f32, f32)>;
+}
+
+pub fn bot_by_name(name: &str) -> Result<Box<dyn Controller>, String> {
+    match name {
+        "greedy" => Ok(Box::new(GreedyBot)),
+        "search" => Ok(Box::new(SearchBot::default())),
+        _ => Err(format!(
+            "Unknown bot '{}', the bots are: {}",
+            name,
+            BOT_NAMES.join(", ")
+        )),
+    }
+}
+
+// Heads for the nearest pellet, fruit or frightened ghost along the maze,
+// or straight for the fruit while it is out, keeping away from the ghosts
+// that can kill. Among equally good ways it prefers the tiles Pac-Man
+// hasn't been on lately.
+pub struct GreedyBot;
+
+impl Controller for GreedyBot {
+    fn decide(&mut self, view: &GameView) -> Option<(f32, f32)> {
+        let game = view.game;
+        if game.phase != Phase::Playing {
+            return None;
+        }
+        let start = maze_tile(game, tile_of(game.pacman.pos))?;
+        let danger = danger_distances(game);
+        let is_goal = |tile: (i32, i32)| {
+            let key = (tile.0 as usize, tile.1 as usize);
+            game.pellets.contains_key(&key)
+                || (game.fruit.is_some() && key == game.level.fruit_tile)
+                || game.ghosts.iter().any(|ghost| {
+                    ghost.frightened
+                        && ghost.state == GhostState::Active
+                        && tile_of(ghost.pos) == tile
+                })
+        };
+
+        // First steps with the faintest scent are searched first, so they
+        // win ties
+        let mut first_steps: Vec<((i32, i32), (i32, i32))> =
+            game.paths.neighbours(Mover::Pacman, start).collect();
+        first_steps.sort_by(|a, b| {
+            let scent_a = view
+                .scent
+                .strength((a.1 .0 as usize, a.1 .1 as usize), game.tick);
+            let scent_b = view
+                .scent
+                .strength((b.1 .0 as usize, b.1 .1 as usize), game.tick);
+            scent_a.total_cmp(&scent_b)
+        });
+
+        let safe = |tile: &(i32, i32)| danger.get(tile).is_none_or(|&d| d >= DANGER_DISTANCE);
+
+        // The fruit is worth more than the pellets on the way to it, so while
+        // it is out it is gone for directly, as long as the way is safe
+        if game.fruit.is_some() {
+            let fruit = (
+                game.level.fruit_tile.0 as i32,
+                game.level.fruit_tile.1 as i32,
+            );
+            let path = game
+                .paths
+                .astar(Mover::Pacman, start, fruit)
+                .filter(|path| path.iter().all(safe));
+            let first = path
+                .and_then(|path| path.first().copied())
+                .and_then(|next| first_steps.iter().find(|(_, tile)| *tile == next));
+            if let Some(&(direction, _)) = first {
+                return Some((direction.0 as f32, direction.1 as f32));
+            }
+        }
+
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::new();
+        for &(direction, tile) in first_steps.iter().filter(|(_, tile)| safe(tile)) {
+            if seen.insert(tile) {
+                queue.push_back((tile, direction));
+            }
+        }
+        while let Some((tile, first)) = queue.pop_front() {
+            if is_goal(tile) {
+                return Some((first.0 as f32, first.1 as f32));
+            }
+            for (_, next) in game.paths.neighbours(Mover::Pacman, tile) {
+                if safe(&next) && seen.insert(next) {
+                    queue.push_back((next, first));
+                }
+            }
+        }
+
+        // Nothing reachable safely, get as far from the ghosts as possible
+        first_steps
+            .iter()
+            .max_by_key(|(_, tile)| danger.get(tile).copied().unwrap_or(u32::MAX))
+            .map(|&(direction, _)| (direction.0 as f32, direction.1 as f32))
+    }
+}
+
+// Tries each way out of Pac-Man's tile on a copy of the game, plays it out
+// for a second and a half with the greedy bot and keeps the one that ends
+// best. The copy has the same random state, so what it sees is what will
+// happen if nothing else changes. Plans once per tile.
+#[derive(Default)]
+pub struct SearchBot {
+    plan: Option<((i32, i32), (f32, f32))>, // The tile planned on and the way chosen there
+}
+
+impl Controller for SearchBot {
+    fn decide(&mut self, view: &GameView) -> Option<(f32, f32)> {
+        let game = view.game;
+        if game.phase != Phase::Playing {
+            self.plan = None;
+            return None;
+        }
+        let tile = tile_of(game.pacman.pos);
+        if let Some((planned_at, direction)) = self.plan {
+            if planned_at == tile {
+                return Some(direction);
+            }
+        }
+        let start = maze_tile(game, tile)?;
+
+        let mut best: Option<(i64, (f32, f32))> = None;
+        for (direction, _) in game.paths.neighbours(Mover::Pacman, start) {
+            let direction = (direction.0 as f32, direction.1 as f32);
+            let value = rollout(game, direction);
+            if best.is_none_or(|(best_value, _)| value > best_value) {
+                best = Some((value, direction));
+            }
+        }
+        let (_, direction) = best?;
+        self.plan = Some((tile, direction));
+        Some(direction)
+    }
+}
+
+// How good it is to set off in `direction`: points scored, less a lot for
+// dying and a little for every tile left to the nearest pellet
+fn rollout(game: &Game, direction: (f32, f32)) -> i64 {
+    let mut copy = game.clone();
+    let start_tile = tile_of(copy.pacman.pos);
+    let start_lives = copy.lives;
+    let start_level = copy.level_number;
+    let scent = ScentMap::default();
+    let mut greedy = GreedyBot;
+    let mut turn = Some(direction);
+    let mut decided_at = start_tile;
+
+    // Past the first tile the greedy bot steers, asked once per tile to
+    // keep the search quick
+    for _ in 0..ROLLOUT_TICKS {
+        let tile = tile_of(copy.pacman.pos);
+        if tile != decided_at {
+            decided_at = tile;
+            turn = greedy.decide(&GameView {
+                game: &copy,
+                scent: &scent,
+            });
+        }
+        copy.tick(turn);
+        if copy.phase != Phase::Playing {
+            break;
+        }
+    }
+
+    let mut value = copy.score as i64 - game.score as i64;
+    if copy.lives < start_lives {
+        value -= DEATH_PENALTY;
+    }
+    if copy.level_number == start_level && copy.phase == Phase::Playing {
+        value -= nearest_pellet_distance(&copy).unwrap_or(0) as i64;
+    }
+    value
+}
+
+// The tile Pac-Man is on, None while he is off the edge in a tunnel
+fn maze_tile(game: &Game, tile: (i32, i32)) -> Option<(i32, i32)> {
+    let inside = tile.0 >= 0
+        && tile.1 >= 0
+        && (tile.0 as usize) < game.level.width()
+        && (tile.1 as usize) < game.level.height();
+    inside.then_some(tile)
+}
+
+// Distance along the maze from the nearest ghost that can kill Pac-Man, for
+// the tiles within DANGER_DISTANCE of one
+fn danger_distances(game: &Game) -> HashMap<(i32, i32), u32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for ghost in &game.ghosts {
+        let deadly = matches!(ghost.state, GhostState::Active | GhostState::LeavingHouse)
+            && !ghost.frightened;
+        if let Some(tile) = maze_tile(game, tile_of(ghost.pos)).filter(|_| deadly) {
+            distances.insert(tile, 0);
+            queue.push_back(tile);
+        }
+    }
+    while let Some(tile) = queue.pop_front() {
+        let distance = distances[&tile] + 1;
+        if distance > DANGER_DISTANCE {
+            continue;
+        }
+        for (_, next) in game.paths.neighbours(Mover::Ghost, tile) {
+            distances.entry(next).or_insert_with(|| {
+                queue.push_back(next);
+                distance
+            });
+        }
+    }
+    distances
+}
+
+fn nearest_pellet_distance(game: &Game) -> Option<u32> {
+    let start = maze_tile(game, tile_of(game.pacman.pos))?;
+    let path = game.paths.bfs(Mover::Pacman, start, |tile| {
+        game.pellets
+            .contains_key(&(tile.0 as usize, tile.1 as usize))
+    })?;
+    Some(path.len() as u32)
+}
+
+// Tallies what happened in a bot's game, for the headless report
+#[derive(Default)]
+pub struct BotStats {
+    pub deaths: u32,
+    pub levels_cleared: u32,
+    pub ghosts_eaten: u32,
+}
+
+impl EventListener for BotStats {
+    fn on_event(&mut self, _tick: u64, event: &GameEvent) {
+        match event {
+            GameEvent::PacmanDied { .. } => self.deaths += 1,
+            GameEvent::LevelCleared { .. } => self.levels_cleared += 1,
+            GameEvent::GhostEaten { .. } => self.ghosts_eaten += 1,
+            _ => {}
+        }
+    }
+}
+
+// Plays a whole game with `controller` as fast as possible, without a
+// window, until it is over or `max_ticks` have gone by
+pub fn play_headless(game: &mut Game, controller: &mut dyn Controller, max_ticks: u64) -> BotStats {
+    let mut scent = ScentMap::default();
+    let mut stats = BotStats::default();
+    while game.phase != Phase::GameOver && game.tick < max_ticks {
+        let turn = controller.decide(&GameView {
+            game,
+            scent: &scent,
+        });
+        game.tick(turn);
+        game.events.dispatch(&mut [&mut scent, &mut stats]);
+        scent.update(game.tick);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Fruit, TICKS_PER_SECOND};
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    // From the start the fruit is 14 tiles away, past plenty of pellets
+    #[test]
+    fn the_greedy_bot_goes_straight_for_the_fruit() {
+        let mut game = Game::new(parse_level(CLASSIC_LEVEL).unwrap(), 1);
+        game.phase = Phase::Playing;
+        game.fruit = Some((Fruit::Cherry, TICKS_PER_SECOND * 5));
+        let scent = ScentMap::default();
+        let mut bot = GreedyBot;
+        for _ in 0..TICKS_PER_SECOND * 2 {
+            if game.fruit.is_none() {
+                return;
+            }
+            let turn = bot.decide(&GameView {
+                game: &game,
+                scent: &scent,
+            });
+            game.tick(turn);
+        }
+        panic!("Pac-Man didn't go for the fruit");
+    }
+}
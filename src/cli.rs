@@ -0,0 +1,108 @@
+use crate::generate::DEFAULT_PELLETS;
+use crate::gym::ObservationKind;
+
+// Command line:
+//   pacman [level file] [--bot <name>] [--headless] [--seed <n>] [--games <n>]
+//   pacman [level file] [--endless | --pack <directory or zip>] --gym
+//          [--observation tiles|schematic] [--frame-skip <n>]
+//   pacman [level file] --edit
+//   pacman [level file] --endless
+//   pacman --pack <directory or zip>
+//   pacman validate [level file]
+//   pacman gen [--seed <n>] [--out <file>] [--pellets <n>]
+pub struct Options {
+    pub subcommand: Subcommand,
+    pub level_path: Option<String>,
+    pub bot: Option<String>, // A bot plays instead of the player
+    pub headless: bool,      // Play the bot's games without a window
+    pub seed: Option<u64>,   // Random if not given
+    pub games: u32,          // How many games to play headless
+    pub gym: bool,           // Serve the learning environment on stdin and stdout
+    pub observation: ObservationKind,
+    pub frame_skip: Option<u32>,
+    pub edit: bool,           // Open the level in the maze editor
+    pub endless: bool,        // Every level is a newly generated maze
+    pub pack: Option<String>, // Play through a level pack from its first level
+    pub out: Option<String>,  // Where gen writes the maze, printed if not given
+    pub pellets: usize,       // How many pellets gen aims for
+}
+
+// What to do, given as the first argument. Playing is the default.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Subcommand {
+    Play,
+    Validate, // Check the level for mistakes and print a report
+    Generate, // Make a new maze
+}
+
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        subcommand: Subcommand::Play,
+        level_path: None,
+        bot: None,
+        headless: false,
+        seed: None,
+        games: 1,
+        gym: false,
+        observation: ObservationKind::Tiles,
+        frame_skip: None,
+        edit: false,
+        endless: false,
+        pack: None,
+        out: None,
+        pellets: DEFAULT_PELLETS,
+    };
+    let mut args = args.peekable();
+    let subcommand = match args.peek().map(String::as_str) {
+        Some("validate") => Some(Subcommand::Validate),
+        Some("gen") => Some(Subcommand::Generate),
+        _ => None,
+    };
+    if let Some(subcommand) = subcommand {
+        options.subcommand = subcommand;
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--bot" => options.bot = Some(value("--bot")?),
+            "--headless" => options.headless = true,
+            "--seed" => options.seed = Some(parse_number(&value("--seed")?, "--seed")?),
+            "--games" => options.games = parse_number(&value("--games")?, "--games")? as u32,
+            "--gym" => options.gym = true,
+            "--edit" => options.edit = true,
+            "--endless" => options.endless = true,
+            "--pack" => options.pack = Some(value("--pack")?),
+            "--out" => options.out = Some(value("--out")?),
+            "--pellets" => {
+                options.pellets = parse_number(&value("--pellets")?, "--pellets")? as usize
+            }
+            "--observation" => {
+                options.observation = ObservationKind::from_name(&value("--observation")?)?
+            }
+            "--frame-skip" => {
+                options.frame_skip =
+                    Some(parse_number(&value("--frame-skip")?, "--frame-skip")? as u32)
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ if options.level_path.is_none() => options.level_path = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    if options.headless && options.bot.is_none() {
+        return Err("--headless needs a --bot to play".to_string());
+    }
+    if options.endless && options.pack.is_some() {
+        return Err("--endless and --pack can't be used together".to_string());
+    }
+    // The trainer gives each episode's seed when it resets
+    if options.gym && (options.seed.is_some() || options.bot.is_some() || options.edit) {
+        return Err("--gym can't be used with --seed, --bot or --edit".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_number(text: &str, name: &str) -> Result<u64, String> {
+    text.parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", name, text))
+}
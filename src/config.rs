@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::assists::Assists;
+use crate::theme::{GhostMarkers, Palette, ThemeSettings};
+
+// User settings read from config.toml. Every field has a default, so the
+// file only needs the settings that differ. See config.example.toml.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub post_processing: PostProcessingConfig,
+    pub controls: BTreeMap<String, Vec<String>>, // Action name to key names, see input.rs
+    pub controller: ControllerConfig,
+    pub audio: AudioConfig,
+    pub theme: ThemeSettings, // Over the arcade's colours, a level pack can change it further
+    pub accessibility: AccessibilityConfig,
+    pub assists: Assists,
+    pub debug: DebugConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DebugConfig {
+    pub log_events: bool,        // Write every game event to stderr
+    pub playback_controls: bool, // Freeze, step, slow motion, fast forward and rewind
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    pub palette: Option<Palette>, // Ghost colours for a kind of colour blindness
+    pub ghost_markers: GhostMarkers,
+    pub high_contrast: bool, // Thick outlines on the walls, Pac-Man and the ghosts
+    pub reduced_flashing: bool, // Fade rather than flash when ghosts recover and levels end
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    // Volumes from 0.0 to 1.0, each channel is also scaled by the master volume
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub siren_volume: f32,
+    pub music_volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            enabled: true,
+            master_volume: 0.8,
+            effects_volume: 1.0,
+            siren_volume: 0.6,
+            music_volume: 1.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ControllerConfig {
+    pub stick_deadzone: f32, // Fraction of the stick's travel that is ignored
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        ControllerConfig {
+            stick_deadzone: 0.25,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PostProcessingConfig {
+    pub scanlines: EffectConfig,
+    pub curvature: EffectConfig,
+    pub glow: EffectConfig,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+pub struct EffectConfig {
+    pub enabled: bool,
+    pub budget_ms: Option<f32>, // Most CPU time the effect may take per frame
+}
+
+impl Config {
+    // A missing file just means the defaults
+    pub fn load(path: &str) -> Result<Config, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path, e)),
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+
+use crate::display::draw_text;
+use crate::game::{tile_centre, Game, GhostMode, GhostName};
+
+const OUTPUT_LINES: usize = 100; // Kept for scrolling back through
+const HEIGHT_FRACTION: f32 = 0.4; // Of the playfield the console covers
+const MAX_SPEED: f32 = 2.0; // Any faster and Pac-Man could skip over a wall
+
+const HELP: &str = "\
+help                    this list
+teleport <col> <row>    move Pac-Man to a tile
+god                     ghosts can't catch Pac-Man, or can again
+level <n>               start level n
+spawn fruit             put the level's fruit out
+set speed <n>|auto      Pac-Man's speed as a fraction of full speed, up to 2
+set lives <n>           at least 1
+set score <n>
+ghost <name> scatter|chase|auto
+seed <n>                new game with this seed
+save state              remember the game as it is now
+load state              go back to the saved game
+clear                   empty the console";
+
+// Typed into the console, see HELP for what each does
+pub enum Command {
+    Help,
+    Clear,
+    Teleport((i32, i32)),
+    God,
+    Level(u32),
+    SpawnFruit,
+    SetSpeed(Option<f32>), // None for the level's own speed
+    SetLives(u32),
+    SetScore(u32),
+    Ghost(GhostName, Option<GhostMode>), // None to follow the schedule again
+    Seed(u64),
+    SaveState,
+    LoadState,
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.to_lowercase();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words[..] {
+        ["help"] => Command::Help,
+        ["clear"] => Command::Clear,
+        ["teleport", col, row] => Command::Teleport((parse(col)?, parse(row)?)),
+        ["god"] => Command::God,
+        ["level", level] => Command::Level(parse(level)?),
+        ["spawn", "fruit"] => Command::SpawnFruit,
+        ["set", "speed", "auto"] => Command::SetSpeed(None),
+        ["set", "speed", speed] => {
+            let speed: f32 = parse(speed)?;
+            if !(speed > 0.0 && speed <= MAX_SPEED) {
+                return Err(format!("Speed must be above 0 and at most {}", MAX_SPEED));
+            }
+            Command::SetSpeed(Some(speed))
+        }
+        ["set", "lives", lives] => match parse(lives)? {
+            0 => return Err("Pac-Man needs at least one life".to_string()),
+            lives => Command::SetLives(lives),
+        },
+        ["set", "score", score] => Command::SetScore(parse(score)?),
+        ["ghost", name, mode] => {
+            let name = GhostName::ALL
+                .into_iter()
+                .find(|ghost| ghost.name() == name)
+                .ok_or(format!("No ghost called '{}'", name))?;
+            let mode = match mode {
+                "scatter" => Some(GhostMode::Scatter),
+                "chase" => Some(GhostMode::Chase),
+                "auto" => None,
+                _ => return Err(format!("Ghosts can scatter, chase or auto, not '{}'", mode)),
+            };
+            Command::Ghost(name, mode)
+        }
+        ["seed", seed] => Command::Seed(parse(seed)?),
+        ["save", "state"] => Command::SaveState,
+        ["load", "state"] => Command::LoadState,
+        _ => return Err(format!("Unknown command '{}', try help", line.trim())),
+    };
+    Ok(command)
+}
+
+fn parse<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("'{}' isn't a valid number", word))
+}
+
+// Carries out the commands that only change the game, returning what to
+// print. The rest need more than the game and are up to the caller.
+pub fn apply_to_game(command: &Command, game: &mut Game) -> Result<String, String> {
+    if !matches!(command, Command::Help) {
+        game.dev_tools_used = true;
+    }
+    match *command {
+        Command::Help => Ok(HELP.to_string()),
+        Command::Teleport(tile) => {
+            let (width, height) = (game.level.width() as i32, game.level.height() as i32);
+            if tile.0 < 0 || tile.1 < 0 || tile.0 >= width || tile.1 >= height {
+                return Err(format!("({}, {}) is off the maze", tile.0, tile.1));
+            }
+            if game.blocks_pacman(tile) {
+                return Err(format!("({}, {}) is a wall", tile.0, tile.1));
+            }
+            game.pacman.pos = tile_centre(tile);
+            game.pacman.turn_buffer = None;
+            Ok(format!("Pac-Man is at ({}, {})", tile.0, tile.1))
+        }
+        Command::God => {
+            game.invincible = !game.invincible;
+            Ok(format!("God mode {}", on_off(game.invincible)))
+        }
+        Command::Level(level) => {
+            game.go_to_level(level);
+            Ok(format!("Level {}", game.level_number))
+        }
+        Command::SpawnFruit => {
+            game.spawn_fruit();
+            Ok("Fruit is out".to_string())
+        }
+        Command::SetSpeed(speed) => {
+            game.pacman_speed = speed;
+            Ok(match speed {
+                Some(speed) => format!("Pac-Man runs at {} of full speed", speed),
+                None => "Pac-Man runs at the level's speed".to_string(),
+            })
+        }
+        Command::SetLives(lives) => {
+            game.lives = lives;
+            Ok(format!("{} lives", lives))
+        }
+        Command::SetScore(score) => {
+            game.score = score;
+            Ok(format!("Score {}", score))
+        }
+        Command::Ghost(name, mode) => {
+            let ghost = game.ghosts.iter_mut().find(|ghost| ghost.name == name);
+            if let Some(ghost) = ghost {
+                ghost.forced_mode = mode;
+            }
+            Ok(match mode {
+                Some(mode) => format!("{} will {:?}", name.name(), mode).to_lowercase(),
+                None => format!("{} follows the schedule", name.name()),
+            })
+        }
+        Command::Clear | Command::Seed(_) | Command::SaveState | Command::LoadState => {
+            Err("Not a game command".to_string())
+        }
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+// The words that can come after `words`, for tab completion
+fn next_words(words: &[&str]) -> Vec<&'static str> {
+    match words {
+        [] => vec![
+            "help", "teleport", "god", "level", "spawn", "set", "ghost", "seed", "save", "load",
+            "clear",
+        ],
+        ["spawn"] => vec!["fruit"],
+        ["set"] => vec!["speed", "lives", "score"],
+        ["set", "speed"] => vec!["auto"],
+        ["ghost"] => GhostName::ALL.iter().map(|ghost| ghost.name()).collect(),
+        ["ghost", _] => vec!["scatter", "chase", "auto"],
+        ["save"] | ["load"] => vec!["state"],
+        _ => Vec::new(),
+    }
+}
+
+// A drop-down console for trying things out without changing the code.
+// While it is open it takes all the typing, and the game waits.
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    browsing: Option<usize>, // Position in the history while going through it
+    output: Vec<(String, bool)>, // Each line, and whether it is an error
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            open: false,
+            input: String::new(),
+            history: Vec::new(),
+            browsing: None,
+            output: vec![("Type help for the list of commands".to_string(), false)],
+        }
+    }
+
+    // Typed text. The backquote that opens the console is left out.
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.chars().filter(|&c| c != '`'));
+    }
+
+    // Takes a key press, returning the line entered when it is Return
+    pub fn handle_key(&mut self, key: Keycode) -> Option<String> {
+        match key {
+            Keycode::Return | Keycode::KpEnter => {
+                let line = std::mem::take(&mut self.input);
+                self.browsing = None;
+                if line.trim().is_empty() {
+                    return None;
+                }
+                self.print(&format!("> {}", line));
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return Some(line);
+            }
+            Keycode::Backspace => {
+                self.input.pop();
+            }
+            Keycode::Tab => self.complete(),
+            Keycode::Up => self.browse(-1),
+            Keycode::Down => self.browse(1),
+            Keycode::Escape | Keycode::Backquote => self.open = false,
+            _ => {}
+        }
+        None
+    }
+
+    fn browse(&mut self, step: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() as i32 - 1;
+        let position = match self.browsing {
+            Some(position) => position as i32 + step,
+            None if step < 0 => last,
+            None => return,
+        };
+        if position > last {
+            self.browsing = None;
+            self.input.clear();
+        } else {
+            let position = position.max(0) as usize;
+            self.browsing = Some(position);
+            self.input = self.history[position].clone();
+        }
+    }
+
+    // Finishes the word being typed as far as it is certain, listing the
+    // choices when there are several
+    fn complete(&mut self) {
+        let input = self.input.to_lowercase();
+        let mut words: Vec<&str> = input.split_whitespace().collect();
+        let partial = if input.ends_with(' ') || input.is_empty() {
+            ""
+        } else {
+            words.pop().unwrap_or_default()
+        };
+        let choices: Vec<&str> = next_words(&words)
+            .into_iter()
+            .filter(|word| word.starts_with(partial))
+            .collect();
+        let completed = match choices[..] {
+            [] => return,
+            [word] => format!("{} ", word),
+            _ => {
+                let common = choices.iter().fold(choices[0], |common, word| {
+                    let same = common
+                        .chars()
+                        .zip(word.chars())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    &common[..same]
+                });
+                self.print(&choices.join("  "));
+                common.to_string()
+            }
+        };
+        words.push(&completed);
+        self.input = words.join(" ");
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.add_lines(text, false);
+    }
+
+    // Prints what a command did, or what went wrong
+    pub fn reply(&mut self, reply: Result<String, String>) {
+        match reply {
+            Ok(text) => self.add_lines(&text, false),
+            Err(text) => self.add_lines(&text, true),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    fn add_lines(&mut self, text: &str, error: bool) {
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            self.output.push((line.to_string(), error));
+        }
+        let excess = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.output.drain(..excess);
+    }
+
+    // Over the top of the playfield, the latest output just above the line
+    // being typed
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        cursor_on: bool,
+    ) -> Result<(), String> {
+        let viewport = canvas.viewport();
+        let height = (viewport.height() as f32 * HEIGHT_FRACTION) as i32;
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 40, 220));
+        canvas.fill_rect(Rect::new(0, 0, viewport.width(), height as u32))?;
+        canvas.set_blend_mode(sdl2::render::BlendMode::None);
+        canvas.set_draw_color(Color::RGB(80, 80, 255));
+        canvas.fill_rect(Rect::new(0, height, viewport.width(), 2))?;
+
+        let line_height = font.recommended_line_spacing();
+        let mut y = height - line_height - 4;
+        let cursor = if cursor_on { "_" } else { "" };
+        draw_text(
+            canvas,
+            font,
+            &format!("> {}{}", self.input, cursor),
+            (10, y),
+            Color::RGB(255, 255, 0),
+        )?;
+        for (line, error) in self.output.iter().rev() {
+            y -= line_height;
+            if y < 0 {
+                break;
+            }
+            let color = if *error {
+                Color::RGB(255, 80, 80)
+            } else {
+                Color::RGB(255, 255, 255)
+            };
+            draw_text(canvas, font, line, (10, y), color)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Phase, DEATH_TICKS};
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    fn game() -> Game {
+        let mut game = Game::new(parse_level(CLASSIC_LEVEL).unwrap(), 1);
+        game.phase = Phase::Playing;
+        game
+    }
+
+    #[test]
+    fn commands_parse() {
+        assert!(matches!(
+            parse_command("teleport 1 2"),
+            Ok(Command::Teleport((1, 2)))
+        ));
+        assert!(matches!(parse_command("  GOD "), Ok(Command::God)));
+        assert!(matches!(parse_command("level 3"), Ok(Command::Level(3))));
+        assert!(matches!(
+            parse_command("set speed auto"),
+            Ok(Command::SetSpeed(None))
+        ));
+        assert!(matches!(
+            parse_command("set speed 0.5"),
+            Ok(Command::SetSpeed(Some(speed))) if speed == 0.5
+        ));
+        assert!(matches!(
+            parse_command("ghost pinky chase"),
+            Ok(Command::Ghost(GhostName::Pinky, Some(GhostMode::Chase)))
+        ));
+        assert!(matches!(parse_command("seed 42"), Ok(Command::Seed(42))));
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        for line in [
+            "",
+            "fly",
+            "level",
+            "level two",
+            "teleport 1",
+            "ghost sue chase",
+            "ghost blinky dance",
+            "set lives -1",
+        ] {
+            assert!(parse_command(line).is_err(), "'{}' was accepted", line);
+        }
+    }
+
+    #[test]
+    fn zero_lives_are_refused() {
+        assert!(parse_command("set lives 0").is_err());
+        assert!(matches!(
+            parse_command("set lives 1"),
+            Ok(Command::SetLives(1))
+        ));
+    }
+
+    #[test]
+    fn speeds_out_of_range_are_refused() {
+        for speed in ["0", "-1", "2.5", "nan", "inf", "-inf"] {
+            let line = format!("set speed {}", speed);
+            assert!(parse_command(&line).is_err(), "'{}' was accepted", line);
+        }
+        assert!(parse_command("set speed 2").is_ok());
+    }
+
+    #[test]
+    fn a_catch_with_no_lives_left_ends_the_game() {
+        let mut game = game();
+        game.lives = 0;
+        game.ghosts[0].pos = game.pacman.pos;
+        game.tick(None);
+        assert!(matches!(game.phase, Phase::Dying(_)));
+        assert_eq!(game.lives, 0);
+        for _ in 0..DEATH_TICKS {
+            game.tick(None);
+        }
+        assert_eq!(game.phase, Phase::GameOver);
+    }
+
+    #[test]
+    fn game_commands_mark_the_game() {
+        let mut game = game();
+        apply_to_game(&Command::Help, &mut game).unwrap();
+        assert!(!game.dev_tools_used);
+        let reply = apply_to_game(&Command::SetLives(5), &mut game).unwrap();
+        assert_eq!(reply, "5 lives");
+        assert_eq!(game.lives, 5);
+        assert!(game.dev_tools_used);
+    }
+
+    #[test]
+    fn teleporting_into_a_wall_is_refused() {
+        let mut game = game();
+        assert!(apply_to_game(&Command::Teleport((0, 0)), &mut game).is_err());
+        assert!(apply_to_game(&Command::Teleport((-1, 5)), &mut game).is_err());
+        assert!(apply_to_game(&Command::Teleport((1, 1)), &mut game).is_ok());
+        assert_eq!(game.pacman.pos, tile_centre((1, 1)));
+    }
+}
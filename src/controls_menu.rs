@@ -0,0 +1,196 @@
+use sdl2::controller::{Axis, Button};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+
+use crate::display::draw_text;
+use crate::input::{Action, Binding, Bindings};
+
+// Lists every action with its bindings. The menu's own keys are fixed
+// (arrows, Return, Delete, Escape, or D-pad or left stick, A, Back, B on a
+// controller) so a bad binding can't lock anyone out.
+pub struct ControlsMenu {
+    pub open: bool,
+    selected: usize,
+    capturing: bool, // Waiting for the key to bind to the selected action
+    changed: bool,
+}
+
+impl ControlsMenu {
+    pub fn new() -> ControlsMenu {
+        ControlsMenu {
+            open: false,
+            selected: 0,
+            capturing: false,
+            changed: false,
+        }
+    }
+
+    pub fn show(&mut self) {
+        self.open = true;
+        self.capturing = false;
+        self.changed = false;
+    }
+
+    // Takes a key, button or stick press. Returns true when the menu
+    // closes after the bindings were edited, so they can be saved.
+    pub fn handle_press(&mut self, pressed: Binding, bindings: &mut Bindings) -> bool {
+        let action = Action::ALL[self.selected];
+
+        if self.capturing {
+            match pressed {
+                Binding::Key { key, .. } if is_modifier(key) => {} // Wait for the key that goes with it
+                Binding::Key {
+                    key: Keycode::Escape,
+                    ..
+                } => self.capturing = false,
+                _ => {
+                    bindings.add(action, pressed);
+                    self.capturing = false;
+                    self.changed = true;
+                }
+            }
+            return false;
+        }
+
+        let count = Action::ALL.len();
+        match menu_command(pressed) {
+            Some(MenuCommand::Previous) => self.selected = (self.selected + count - 1) % count,
+            Some(MenuCommand::Next) => self.selected = (self.selected + 1) % count,
+            Some(MenuCommand::Capture) => self.capturing = true,
+            Some(MenuCommand::Clear) => {
+                bindings.clear(action);
+                self.changed = true;
+            }
+            Some(MenuCommand::Close) => {
+                self.open = false;
+                return self.changed;
+            }
+            None => {}
+        }
+        false
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        bindings: &Bindings,
+    ) -> Result<(), String> {
+        let viewport = canvas.viewport();
+
+        // Dim the game behind the menu
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 220));
+        canvas.fill_rect(Rect::new(0, 0, viewport.width(), viewport.height()))?;
+        canvas.set_blend_mode(sdl2::render::BlendMode::None);
+
+        let white = Color::RGB(255, 255, 255);
+        let yellow = Color::RGB(255, 255, 0);
+        let red = Color::RGB(255, 80, 80);
+        let line_height = font.recommended_line_spacing();
+        let mut y = 40;
+
+        draw_text(canvas, font, "CONTROLS", (40, y), yellow)?;
+        y += line_height * 2;
+
+        let conflicts = bindings.conflicts();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let keys: Vec<String> = bindings
+                .get(*action)
+                .iter()
+                .map(|b| b.to_string())
+                .collect();
+            let keys = if self.capturing && i == self.selected {
+                "press a key, button or stick...".to_string()
+            } else if keys.is_empty() {
+                "-".to_string()
+            } else {
+                keys.join(", ")
+            };
+            let conflicted = conflicts
+                .iter()
+                .any(|(_, actions)| actions.contains(action));
+            let color = if i == self.selected {
+                yellow
+            } else if conflicted {
+                red
+            } else {
+                white
+            };
+
+            if i == self.selected {
+                draw_text(canvas, font, ">", (40, y), color)?;
+            }
+            draw_text(canvas, font, action.label(), (70, y), color)?;
+            draw_text(canvas, font, &keys, (330, y), color)?;
+            y += line_height;
+        }
+
+        y += line_height;
+        for (binding, actions) in &conflicts {
+            let names: Vec<&str> = actions.iter().map(|a| a.label()).collect();
+            let text = format!("{} is bound to {}", binding, names.join(" and "));
+            draw_text(canvas, font, &text, (40, y), red)?;
+            y += line_height;
+        }
+
+        y += line_height;
+        draw_text(
+            canvas,
+            font,
+            "Return: add binding   Delete: clear   Esc: close",
+            (40, y),
+            white,
+        )?;
+        Ok(())
+    }
+}
+
+enum MenuCommand {
+    Previous,
+    Next,
+    Capture,
+    Clear,
+    Close,
+}
+
+fn menu_command(pressed: Binding) -> Option<MenuCommand> {
+    match pressed {
+        Binding::Key { key, .. } => match key {
+            Keycode::Up => Some(MenuCommand::Previous),
+            Keycode::Down => Some(MenuCommand::Next),
+            Keycode::Return => Some(MenuCommand::Capture),
+            Keycode::Delete | Keycode::Backspace => Some(MenuCommand::Clear),
+            Keycode::Escape => Some(MenuCommand::Close),
+            _ => None,
+        },
+        Binding::Button(button) => match button {
+            Button::DPadUp => Some(MenuCommand::Previous),
+            Button::DPadDown => Some(MenuCommand::Next),
+            Button::A => Some(MenuCommand::Capture),
+            Button::Back => Some(MenuCommand::Clear),
+            Button::B => Some(MenuCommand::Close),
+            _ => None,
+        },
+        Binding::Stick(Axis::LeftY, false) => Some(MenuCommand::Previous),
+        Binding::Stick(Axis::LeftY, true) => Some(MenuCommand::Next),
+        Binding::Stick(..) => None,
+    }
+}
+
+fn is_modifier(key: Keycode) -> bool {
+    matches!(
+        key,
+        Keycode::LCtrl
+            | Keycode::RCtrl
+            | Keycode::LAlt
+            | Keycode::RAlt
+            | Keycode::LShift
+            | Keycode::RShift
+            | Keycode::LGui
+            | Keycode::RGui
+    )
+}
@@ -0,0 +1,247 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+use std::time::Instant;
+
+use crate::display::draw_text;
+use crate::game::{tile_centre, tile_of, Game, GhostState, COLLISION_REACH, TILE_SIZE};
+use crate::scent::ScentMap;
+use crate::theme::Theme;
+
+// How much of each new frame time goes into the shown average
+const TIMING_SMOOTHING: f32 = 0.1;
+const PLANNED_TILES: usize = 40; // Longest ghost path the targets overlay shows
+
+// Developer overlays for tuning the AI and chasing movement bugs, each
+// switched on and off with its own key. All off to begin with.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub grid: bool,
+    pub scent: bool,
+    pub targets: bool, // Each ghost's target tile and the way it will take there
+    pub tile_centres: bool,
+    pub collisions: bool,
+    pub timings: bool,
+}
+
+impl DebugOverlay {
+    // The overlays that go under the maze, in maze coordinates
+    pub fn draw_under(
+        &self,
+        canvas: &mut WindowCanvas,
+        game: &Game,
+        scent: &ScentMap,
+        theme: &Theme,
+    ) -> Result<(), String> {
+        if self.grid {
+            draw_grid(canvas, game.level.width(), game.level.height(), theme.grid)?;
+        }
+        if self.scent {
+            scent.draw(canvas, TILE_SIZE, TILE_SIZE, game.tick)?;
+        }
+        Ok(())
+    }
+
+    // The overlays that go over the actors, in maze coordinates
+    pub fn draw_over(
+        &self,
+        canvas: &mut WindowCanvas,
+        game: &Game,
+        theme: &Theme,
+    ) -> Result<(), String> {
+        if self.tile_centres {
+            draw_tile_centres(canvas, game)?;
+        }
+        if self.targets {
+            draw_targets(canvas, game, theme)?;
+        }
+        if self.collisions {
+            draw_collisions(canvas, game)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn draw_grid(
+    canvas: &mut WindowCanvas,
+    width: usize,
+    height: usize,
+    color: Color,
+) -> Result<(), String> {
+    canvas.set_draw_color(color);
+    let (right, bottom) = (
+        (width as f32 * TILE_SIZE) as i32,
+        (height as f32 * TILE_SIZE) as i32,
+    );
+    for col in 0..=width {
+        let x = (col as f32 * TILE_SIZE) as i32;
+        canvas.fill_rect(Rect::new(x, 0, 1, bottom as u32))?;
+    }
+    for row in 0..=height {
+        let y = (row as f32 * TILE_SIZE) as i32;
+        canvas.fill_rect(Rect::new(0, y, right as u32, 1))?;
+    }
+    Ok(())
+}
+
+// A dot at the centre of every open tile, and a line from each actor to the
+// centre of the tile it is on, which is where it turns
+fn draw_tile_centres(canvas: &mut WindowCanvas, game: &Game) -> Result<(), String> {
+    let mut centres = Vec::new();
+    for (row, tiles) in game.level.tiles.iter().enumerate() {
+        for (col, tile) in tiles.iter().enumerate() {
+            if !tile.is_wall() {
+                let centre = tile_centre((col as i32, row as i32));
+                centres.push(Rect::new(centre.0 as i32 - 1, centre.1 as i32 - 1, 2, 2));
+            }
+        }
+    }
+    canvas.set_draw_color(Color::RGB(0, 200, 200));
+    canvas.fill_rects(&centres)?;
+
+    let actors = std::iter::once(game.pacman.pos).chain(game.ghosts.iter().map(|g| g.pos));
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    for pos in actors {
+        let centre = tile_centre(tile_of(pos));
+        canvas.draw_line(to_point(pos), to_point(centre))?;
+    }
+    Ok(())
+}
+
+// Each ghost's target tile outlined in its colour, with the shortest way
+// there through the maze. Targets off the maze or inside a wall are
+// reached as near as the ghost can get.
+fn draw_targets(canvas: &mut WindowCanvas, game: &Game, theme: &Theme) -> Result<(), String> {
+    for (i, ghost) in game.ghosts.iter().enumerate() {
+        let Some(target) = ghost.target else {
+            continue;
+        };
+        if ghost.state == GhostState::InHouse {
+            continue;
+        }
+        canvas.set_draw_color(theme.ghost(ghost.name));
+        let corner = (
+            (target.0 as f32 * TILE_SIZE) as i32,
+            (target.1 as f32 * TILE_SIZE) as i32,
+        );
+        let size = TILE_SIZE as u32;
+        canvas.draw_rect(Rect::new(corner.0, corner.1, size, size))?;
+        canvas.draw_rect(Rect::new(corner.0 + 1, corner.1 + 1, size - 2, size - 2))?;
+
+        // Where the ghost will go if its target stays put. No line across
+        // the maze where the path goes through a tunnel.
+        let mut previous = tile_of(ghost.pos);
+        for tile in game.planned_path(i, PLANNED_TILES) {
+            if (tile.0 - previous.0).abs() + (tile.1 - previous.1).abs() == 1 {
+                canvas.draw_line(to_point(tile_centre(previous)), to_point(tile_centre(tile)))?;
+            }
+            previous = tile;
+        }
+    }
+    Ok(())
+}
+
+// Pac-Man and the ghosts he can bump into, each with a circle of half the
+// collision reach. Circles that overlap mean a catch, and turn red.
+fn draw_collisions(canvas: &mut WindowCanvas, game: &Game) -> Result<(), String> {
+    let radius = COLLISION_REACH / 2.0;
+    let pacman = game.pacman.pos;
+    let mut touching = false;
+    for ghost in &game.ghosts {
+        if !matches!(ghost.state, GhostState::Active | GhostState::LeavingHouse) {
+            continue;
+        }
+        let caught = (ghost.pos.0 - pacman.0).hypot(ghost.pos.1 - pacman.1) < COLLISION_REACH;
+        touching |= caught;
+        canvas.set_draw_color(circle_color(caught));
+        canvas.draw_points(&circle_points(ghost.pos, radius)[..])?;
+    }
+    canvas.set_draw_color(circle_color(touching));
+    canvas.draw_points(&circle_points(pacman, radius)[..])
+}
+
+fn circle_color(touching: bool) -> Color {
+    if touching {
+        Color::RGB(255, 0, 0)
+    } else {
+        Color::RGB(0, 255, 0)
+    }
+}
+
+fn circle_points(centre: (f32, f32), radius: f32) -> Vec<Point> {
+    let steps = (radius * 8.0) as i32;
+    (0..steps)
+        .map(|i| {
+            let angle = i as f32 / steps as f32 * std::f32::consts::TAU;
+            to_point((
+                centre.0 + radius * angle.cos(),
+                centre.1 + radius * angle.sin(),
+            ))
+        })
+        .collect()
+}
+
+fn to_point(pos: (f32, f32)) -> Point {
+    Point::new(pos.0 as i32, pos.1 as i32)
+}
+
+// How long each part of a frame takes, in milliseconds averaged over the
+// last few frames. Parts are timed one after another with `lap`, and a part
+// timed more than once in a frame adds up.
+#[derive(Default)]
+pub struct FrameTimings {
+    systems: Vec<(&'static str, f32, f32)>, // Name, average, this frame so far
+}
+
+impl FrameTimings {
+    // Puts the time since `started` down to `system`, and starts timing the
+    // next part of the frame
+    pub fn lap(&mut self, system: &'static str, started: &mut Instant) {
+        let now = Instant::now();
+        let ms = now.duration_since(*started).as_secs_f32() * 1000.0;
+        *started = now;
+        match self.systems.iter_mut().find(|(name, ..)| *name == system) {
+            Some((_, _, this_frame)) => *this_frame += ms,
+            None => self.systems.push((system, ms, ms)),
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        for (_, average, this_frame) in &mut self.systems {
+            *average += (*this_frame - *average) * TIMING_SMOOTHING;
+            *this_frame = 0.0;
+        }
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        position: (i32, i32),
+    ) -> Result<(), String> {
+        let line_height = font.recommended_line_spacing();
+        let total: f32 = self.systems.iter().map(|(_, ms, _)| ms).sum();
+        let lines = self
+            .systems
+            .iter()
+            .map(|&(name, ms, _)| (name, ms))
+            .chain(std::iter::once(("frame", total)));
+        for (i, (name, ms)) in lines.enumerate() {
+            let text = if name == "frame" {
+                format!("{} {:.2} ms ({:.0} fps)", name, ms, 1000.0 / ms.max(0.001))
+            } else {
+                format!("{} {:.2} ms", name, ms)
+            };
+            let y = position.1 + i as i32 * line_height;
+            draw_text(
+                canvas,
+                font,
+                &text,
+                (position.0, y),
+                Color::RGB(255, 255, 0),
+            )?;
+        }
+        Ok(())
+    }
+}
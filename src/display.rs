@@ -0,0 +1,183 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::ttf::Font;
+use sdl2::video::{FullscreenType, WindowContext};
+
+use crate::postfx::PostProcessor;
+
+// How the logical playfield is stretched to fill the window
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScalingMode {
+    Integer, // Whole multiples only, crisp pixels
+    Smooth,  // Fill as much of the window as possible with linear filtering
+}
+
+impl ScalingMode {
+    pub fn toggled(self) -> ScalingMode {
+        match self {
+            ScalingMode::Integer => ScalingMode::Smooth,
+            ScalingMode::Smooth => ScalingMode::Integer,
+        }
+    }
+}
+
+// Everything is drawn into the first texture at the logical resolution and
+// then scaled onto the window. The second one holds the post-processed copy
+// of the frame when any effect is on. The filtering is fixed when a texture
+// is created, so both have to be recreated when the scaling mode changes.
+pub fn create_playfield_textures(
+    texture_creator: &TextureCreator<WindowContext>,
+    logical_size: (u32, u32),
+    scaling_mode: ScalingMode,
+) -> Result<(Texture<'_>, Texture<'_>), String> {
+    let scale_quality = match scaling_mode {
+        ScalingMode::Integer => "0", // Nearest neighbour
+        ScalingMode::Smooth => "1",  // Linear
+    };
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", scale_quality);
+
+    let playfield = texture_creator
+        .create_texture_target(PixelFormatEnum::ARGB8888, logical_size.0, logical_size.1)
+        .map_err(|e| e.to_string())?;
+    let processed = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, logical_size.0, logical_size.1)
+        .map_err(|e| e.to_string())?;
+    Ok((playfield, processed))
+}
+
+// Runs the CPU effects over the finished playfield and leaves the result in
+// `processed`
+pub fn post_process_playfield(
+    canvas: &mut WindowCanvas,
+    playfield: &mut Texture,
+    processed: &mut Texture,
+    post_processor: &mut PostProcessor,
+) -> Result<(), String> {
+    let mut pixels = Ok(Vec::new());
+    canvas
+        .with_texture_canvas(playfield, |canvas| {
+            pixels = canvas.read_pixels(None, PixelFormatEnum::RGBA32);
+        })
+        .map_err(|e| e.to_string())?;
+    let mut pixels = pixels?;
+
+    post_processor.apply(&mut pixels);
+
+    let pitch = processed.query().width as usize * 4;
+    processed
+        .update(None, &pixels, pitch)
+        .map_err(|e| e.to_string())
+}
+
+// Where the playfield goes in the window, centred with black bars on the
+// sides that don't fit the aspect ratio
+pub fn letterbox_rect(
+    output_size: (u32, u32),
+    logical_size: (u32, u32),
+    scaling_mode: ScalingMode,
+) -> Rect {
+    let scale_x = output_size.0 as f32 / logical_size.0 as f32;
+    let scale_y = output_size.1 as f32 / logical_size.1 as f32;
+    let mut scale = scale_x.min(scale_y);
+    if scaling_mode == ScalingMode::Integer && scale >= 1.0 {
+        scale = scale.floor(); // Below 1x there is no whole multiple, so just shrink to fit
+    }
+
+    let width = ((logical_size.0 as f32 * scale) as u32).max(1);
+    let height = ((logical_size.1 as f32 * scale) as u32).max(1);
+    Rect::new(
+        (output_size.0 as i32 - width as i32) / 2,
+        (output_size.1 as i32 - height as i32) / 2,
+        width,
+        height,
+    )
+}
+
+// The point on the playfield under a point in the window, such as the mouse
+pub fn window_to_playfield(
+    point: (i32, i32),
+    window_size: (u32, u32),
+    logical_size: (u32, u32),
+    scaling_mode: ScalingMode,
+) -> (i32, i32) {
+    let rect = letterbox_rect(window_size, logical_size, scaling_mode);
+    (
+        (point.0 - rect.x()) * logical_size.0 as i32 / rect.width() as i32,
+        (point.1 - rect.y()) * logical_size.1 as i32 / rect.height() as i32,
+    )
+}
+
+pub fn present_playfield(
+    canvas: &mut WindowCanvas,
+    playfield: &Texture,
+    logical_size: (u32, u32),
+    scaling_mode: ScalingMode,
+) -> Result<(), String> {
+    let output_size = canvas.output_size()?;
+    canvas.set_draw_color(Color::RGB(0, 0, 0)); // Letterbox bars
+    canvas.clear();
+    canvas.copy(
+        playfield,
+        None,
+        letterbox_rect(output_size, logical_size, scaling_mode),
+    )?;
+    canvas.present();
+    Ok(())
+}
+
+pub fn toggle_fullscreen(canvas: &mut WindowCanvas) -> Result<(), String> {
+    let window = canvas.window_mut();
+    let fullscreen_type = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(fullscreen_type)
+}
+
+// Draws a line of text with its top-left corner at `position`
+pub fn draw_text(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    text: &str,
+    position: (i32, i32),
+    color: Color,
+) -> Result<(), String> {
+    if text.is_empty() {
+        return Ok(()); // SDL_ttf refuses to render empty strings
+    }
+    let surface = font
+        .render(text)
+        .blended(color)
+        .map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+    canvas.copy(
+        &texture,
+        None,
+        Rect::new(position.0, position.1, surface.width(), surface.height()),
+    )
+}
+
+// Draws a line of text centred on `center`
+pub fn draw_text_centered(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    text: &str,
+    center: (i32, i32),
+    color: Color,
+) -> Result<(), String> {
+    let (text_width, text_height) = font.size_of(text).map_err(|e| e.to_string())?;
+    draw_text(
+        canvas,
+        font,
+        text,
+        (
+            center.0 - text_width as i32 / 2,
+            center.1 - text_height as i32 / 2,
+        ),
+        color,
+    )
+}
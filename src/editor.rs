@@ -0,0 +1,512 @@
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+use std::time::Duration;
+
+use crate::debug::draw_grid;
+use crate::display::{draw_text, draw_text_centered};
+use crate::game::{tile_centre, Fruit, Ghost, GhostName, TILE_SIZE};
+use crate::hud::{HUD_BOTTOM_ROWS, HUD_TOP_ROWS};
+use crate::level::{parse_level, Level};
+use crate::maze::{autotile, draw_maze, MazeTile};
+use crate::render::{draw_fruit, draw_ghost, draw_pacman};
+use crate::sprites::SpriteAtlas;
+use crate::theme::Theme;
+use crate::validate::validate_level;
+
+const PALETTE_CELL_TILES: i32 = 2; // Width of each tool in the palette
+const UNDO_LIMIT: usize = 200;
+
+// What the mouse paints, in palette order
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool {
+    Wall,
+    Empty,
+    Dot,
+    Energizer,
+    Door,
+    Tunnel,
+    Pacman,
+    Blinky,
+    Pinky,
+    Inky,
+    Clyde,
+    Fruit,
+}
+
+impl Tool {
+    // Picked with the keys along the top row, 1 to 0 then - and =
+    const ALL: [Tool; 12] = [
+        Tool::Wall,
+        Tool::Empty,
+        Tool::Dot,
+        Tool::Energizer,
+        Tool::Door,
+        Tool::Tunnel,
+        Tool::Pacman,
+        Tool::Blinky,
+        Tool::Pinky,
+        Tool::Inky,
+        Tool::Clyde,
+        Tool::Fruit,
+    ];
+    const KEYS: [Keycode; 12] = [
+        Keycode::Num1,
+        Keycode::Num2,
+        Keycode::Num3,
+        Keycode::Num4,
+        Keycode::Num5,
+        Keycode::Num6,
+        Keycode::Num7,
+        Keycode::Num8,
+        Keycode::Num9,
+        Keycode::Num0,
+        Keycode::Minus,
+        Keycode::Equals,
+    ];
+
+    // The level file character the tool paints
+    fn symbol(self) -> char {
+        match self {
+            Tool::Wall => '#',
+            Tool::Empty | Tool::Tunnel => ' ',
+            Tool::Dot => '.',
+            Tool::Energizer => 'o',
+            Tool::Door => '-',
+            Tool::Pacman => 'P',
+            Tool::Blinky => '1',
+            Tool::Pinky => '2',
+            Tool::Inky => '3',
+            Tool::Clyde => '4',
+            Tool::Fruit => 'F',
+        }
+    }
+
+    // Starting points there can only be one of
+    fn is_unique(self) -> bool {
+        matches!(
+            self,
+            Tool::Pacman | Tool::Blinky | Tool::Pinky | Tool::Inky | Tool::Clyde | Tool::Fruit
+        )
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Tool::Wall => "wall",
+            Tool::Empty => "empty",
+            Tool::Dot => "dot",
+            Tool::Energizer => "energizer",
+            Tool::Door => "ghost house door",
+            Tool::Tunnel => "tunnel (opens both ends of the row)",
+            Tool::Pacman => "Pac-Man's start",
+            Tool::Blinky => "Blinky's start",
+            Tool::Pinky => "Pinky's start, the house centre",
+            Tool::Inky => "Inky's start",
+            Tool::Clyde => "Clyde's start",
+            Tool::Fruit => "fruit",
+        }
+    }
+}
+
+// What the editor asks of the rest of the game
+pub enum EditorRequest {
+    PlayTest(Box<Level>),
+    Leave,
+}
+
+// Edits a level in its text form, a grid of level file characters, so it
+// saves exactly as it is drawn. Left click paints with the selected tool,
+// right click clears, and walls are autotiled as they are painted.
+pub struct Editor {
+    pub path: String, // Saved to and loaded from
+    grid: Vec<Vec<char>>,
+    tool: Tool,
+    undo: Vec<Vec<Vec<char>>>,
+    redo: Vec<Vec<Vec<char>>>,
+    painting: Option<Tool>, // While a mouse button is held
+    warnings: Vec<String>,
+    message: String, // What the last save, load or play test did
+}
+
+impl Editor {
+    pub fn new(level: &Level, path: &str) -> Editor {
+        let mut editor = Editor {
+            path: path.to_string(),
+            grid: Vec::new(),
+            tool: Tool::Wall,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            painting: None,
+            warnings: Vec::new(),
+            message: String::new(),
+        };
+        editor.set_text(&level.to_text());
+        editor
+    }
+
+    fn set_text(&mut self, text: &str) {
+        let width = text.lines().map(|line| line.chars().count()).max();
+        self.grid = text
+            .lines()
+            .map(|line| {
+                let mut row: Vec<char> = line.chars().collect();
+                row.resize(width.unwrap_or(0), ' ');
+                row
+            })
+            .collect();
+        self.validate();
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for row in &self.grid {
+            text.extend(row);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn validate(&mut self) {
+        self.warnings = match parse_level(&self.text()) {
+            Ok(level) => validate_level(&level)
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect(),
+            Err(e) => vec![e],
+        };
+    }
+
+    // Takes a key press, with whether Ctrl and Shift are held
+    pub fn handle_key(&mut self, key: Keycode, ctrl: bool, shift: bool) -> Option<EditorRequest> {
+        if let Some(i) = Tool::KEYS.iter().position(|&k| k == key) {
+            self.tool = Tool::ALL[i];
+            return None;
+        }
+        match key {
+            Keycode::Z if ctrl && shift => self.redo(),
+            Keycode::Z if ctrl => self.undo(),
+            Keycode::Y if ctrl => self.redo(),
+            Keycode::S if ctrl => self.save(),
+            Keycode::O if ctrl => self.load(),
+            Keycode::N if ctrl => self.clear(),
+            Keycode::Return | Keycode::KpEnter => return self.play_test(),
+            Keycode::Escape => return Some(EditorRequest::Leave),
+            _ => {}
+        }
+        None
+    }
+
+    // A mouse button going down at a point on the logical playfield. The
+    // palette and the play button are along the bottom, below the maze.
+    pub fn mouse_down(&mut self, point: (i32, i32), erase: bool) -> Option<EditorRequest> {
+        let bottom = self.maze_top() + self.grid.len() as i32 * TILE_SIZE as i32;
+        if point.1 >= bottom {
+            // Clicks in the letterbox around the strip are ignored
+            let width = self.grid.first().map_or(0, Vec::len) as i32 * TILE_SIZE as i32;
+            let strip_bottom = bottom + HUD_BOTTOM_ROWS as i32 * TILE_SIZE as i32;
+            if point.0 < 0 || point.0 >= width || point.1 >= strip_bottom {
+                return None;
+            }
+            let cell = point.0 / (PALETTE_CELL_TILES * TILE_SIZE as i32);
+            match Tool::ALL.get(cell as usize) {
+                Some(&tool) => self.tool = tool,
+                None => return self.play_test(),
+            }
+            return None;
+        }
+
+        self.push_undo();
+        let tool = if erase { Tool::Empty } else { self.tool };
+        self.painting = Some(tool);
+        self.paint(point, tool);
+        None
+    }
+
+    pub fn mouse_moved(&mut self, point: (i32, i32)) {
+        if let Some(tool) = self.painting {
+            self.paint(point, tool);
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        // A click that changed nothing isn't worth undoing
+        if self.painting.take().is_some() && self.undo.last() == Some(&self.grid) {
+            self.undo.pop();
+        }
+    }
+
+    fn maze_top(&self) -> i32 {
+        (HUD_TOP_ROWS as f32 * TILE_SIZE) as i32
+    }
+
+    fn paint(&mut self, point: (i32, i32), tool: Tool) {
+        let y = point.1 - self.maze_top();
+        if point.0 < 0 || y < 0 {
+            return;
+        }
+        let (col, row) = (
+            (point.0 / TILE_SIZE as i32) as usize,
+            (y / TILE_SIZE as i32) as usize,
+        );
+        let width = self.grid.first().map_or(0, |row| row.len());
+        if row >= self.grid.len() || col >= width {
+            return;
+        }
+
+        if tool.is_unique() {
+            for c in self.grid.iter_mut().flatten() {
+                if *c == tool.symbol() {
+                    *c = ' ';
+                }
+            }
+        }
+        if tool == Tool::Tunnel {
+            self.grid[row][0] = ' ';
+            self.grid[row][width - 1] = ' ';
+        } else {
+            self.grid[row][col] = tool.symbol();
+        }
+        self.validate();
+    }
+
+    fn push_undo(&mut self) {
+        self.undo.push(self.grid.clone());
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(grid) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.grid, grid));
+            self.validate();
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(grid) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.grid, grid));
+            self.validate();
+        }
+    }
+
+    // Walls all round an empty maze of the same size
+    fn clear(&mut self) {
+        self.push_undo();
+        let (width, height) = (
+            self.grid.first().map_or(0, |row| row.len()),
+            self.grid.len(),
+        );
+        for (y, row) in self.grid.iter_mut().enumerate() {
+            for (x, c) in row.iter_mut().enumerate() {
+                let border = x == 0 || y == 0 || x + 1 == width || y + 1 == height;
+                *c = if border { '#' } else { ' ' };
+            }
+        }
+        self.validate();
+    }
+
+    fn save(&mut self) {
+        self.message = match std::fs::write(&self.path, self.text()) {
+            Ok(()) => format!("Saved {}", self.path),
+            Err(e) => format!("Could not save {}: {}", self.path, e),
+        };
+    }
+
+    fn load(&mut self) {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => {
+                self.push_undo();
+                self.set_text(&text);
+                self.message = format!("Loaded {}", self.path);
+            }
+            Err(e) => self.message = format!("Could not load {}: {}", self.path, e),
+        }
+    }
+
+    fn play_test(&mut self) -> Option<EditorRequest> {
+        match parse_level(&self.text()) {
+            Ok(level) => {
+                self.message = "Esc comes back to the editor".to_string();
+                Some(EditorRequest::PlayTest(Box::new(level)))
+            }
+            Err(e) => {
+                self.message = format!("Can't play: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        sprite_atlas: Option<&SpriteAtlas>,
+        elapsed: Duration,
+        theme: &Theme,
+    ) -> Result<(), String> {
+        let viewport = canvas.viewport();
+        let width = self.grid.first().map_or(0, |row| row.len());
+        let maze_height = self.grid.len() as u32 * TILE_SIZE as u32;
+        let white = Color::RGB(255, 255, 255);
+        let yellow = Color::RGB(255, 255, 0);
+
+        // The maze, with walls autotiled the way the level will load
+        canvas.set_viewport(Rect::new(0, self.maze_top(), viewport.width(), maze_height));
+        draw_grid(canvas, width, self.grid.len(), theme.grid)?;
+        let walls: Vec<Vec<bool>> = self
+            .grid
+            .iter()
+            .map(|row| row.iter().map(|&c| c == '#').collect())
+            .collect();
+        let mut tiles = autotile(&walls);
+        for (x, y, c) in self.cells() {
+            if c == '-' {
+                tiles[y][x] = MazeTile::Door;
+            }
+        }
+        draw_maze(canvas, &tiles, TILE_SIZE, TILE_SIZE, sprite_atlas, theme)?;
+        for (x, y, c) in self.cells() {
+            let centre = tile_centre((x as i32, y as i32));
+            let tool = Tool::ALL.into_iter().find(|tool| tool.symbol() == c);
+            if let Some(tool) =
+                tool.filter(|tool| !matches!(tool, Tool::Wall | Tool::Door | Tool::Empty))
+            {
+                draw_tool(canvas, font, tool, centre, sprite_atlas, elapsed, theme)?;
+            }
+        }
+        canvas.set_viewport(None);
+
+        // What the level would do wrong, over the top of the maze
+        let line_height = font.recommended_line_spacing();
+        for (i, warning) in self.warnings.iter().enumerate() {
+            let y = self.maze_top() + 4 + i as i32 * line_height;
+            draw_text(canvas, font, warning, (10, y), Color::RGB(255, 80, 80))?;
+        }
+
+        // Tool and file along the top
+        let status = if self.message.is_empty() {
+            format!("EDITING {}", self.path)
+        } else {
+            self.message.clone()
+        };
+        draw_text(canvas, font, &status, (10, 2), white)?;
+        draw_text(
+            canvas,
+            font,
+            &format!("{}   Ctrl+Z/Y undo/redo  Ctrl+S save", self.tool.label()),
+            (10, 2 + line_height),
+            yellow,
+        )?;
+
+        // The palette and the play button along the bottom
+        let bottom = self.maze_top() + maze_height as i32;
+        let cell_width = PALETTE_CELL_TILES * TILE_SIZE as i32;
+        let strip_height = viewport.height() as i32 - bottom;
+        for (i, &tool) in Tool::ALL.iter().enumerate() {
+            let cell = Rect::new(
+                i as i32 * cell_width,
+                bottom,
+                cell_width as u32,
+                strip_height as u32,
+            );
+            let centre = (cell.center().x() as f32, cell.center().y() as f32);
+            match tool {
+                Tool::Wall => {
+                    canvas.set_draw_color(theme.wall_outline);
+                    canvas.fill_rect(Rect::from_center(cell.center(), 16, 16))?;
+                }
+                Tool::Door => {
+                    canvas.set_draw_color(theme.door);
+                    canvas.fill_rect(Rect::from_center(cell.center(), 20, 4))?;
+                }
+                _ => draw_tool(canvas, font, tool, centre, sprite_atlas, elapsed, theme)?,
+            }
+            if tool == self.tool {
+                canvas.set_draw_color(yellow);
+                canvas.draw_rect(cell)?;
+            }
+        }
+        let play_x = Tool::ALL.len() as i32 * cell_width;
+        draw_text_centered(
+            canvas,
+            font,
+            "PLAY",
+            (
+                (play_x + viewport.width() as i32) / 2,
+                bottom + strip_height / 2,
+            ),
+            Color::RGB(0, 255, 0),
+        )
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.grid
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &c)| (x, y, c)))
+    }
+}
+
+// How a tool's mark looks, in the maze and in the palette
+fn draw_tool(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    tool: Tool,
+    centre: (f32, f32),
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let pellet = theme.pellet;
+    let point = (centre.0 as i32, centre.1 as i32);
+    match tool {
+        Tool::Wall | Tool::Door => {}
+        Tool::Empty => {
+            canvas.set_draw_color(Color::RGB(80, 80, 80));
+            canvas.draw_rect(Rect::from_center(point, 12, 12))?;
+        }
+        Tool::Dot => {
+            canvas.set_draw_color(pellet);
+            canvas.fill_rect(Rect::from_center(point, 4, 4))?;
+        }
+        Tool::Energizer => {
+            canvas.set_draw_color(pellet);
+            canvas.fill_rect(Rect::from_center(point, 12, 12))?;
+        }
+        Tool::Tunnel => draw_text_centered(canvas, font, "<>", point, Color::RGB(0, 255, 255))?,
+        Tool::Pacman => draw_pacman(
+            canvas,
+            centre,
+            TILE_SIZE / 2.0,
+            45.0,
+            (1.0, 0.0),
+            theme.pacman,
+        )?,
+        Tool::Blinky | Tool::Pinky | Tool::Inky | Tool::Clyde => {
+            let name = match tool {
+                Tool::Blinky => GhostName::Blinky,
+                Tool::Pinky => GhostName::Pinky,
+                Tool::Inky => GhostName::Inky,
+                _ => GhostName::Clyde,
+            };
+            let mut ghost = Ghost::new(name);
+            ghost.pos = centre;
+            draw_ghost(
+                canvas,
+                &ghost,
+                false,
+                0,
+                TILE_SIZE,
+                sprite_atlas,
+                elapsed,
+                theme,
+            )?;
+        }
+        Tool::Fruit => draw_fruit(canvas, Fruit::Cherry, centre, TILE_SIZE, sprite_atlas)?,
+    }
+    Ok(())
+}
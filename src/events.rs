@@ -0,0 +1,130 @@
+use crate::game::{Fruit, GhostName};
+
+// Things that happen in the simulation. The simulation only puts them on
+// its queue; audio, the HUD, achievements and logging listen for the ones
+// they care about, so none of them has to be called from the game logic.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    DotEaten {
+        tile: (usize, usize),
+    },
+    EnergizerEaten {
+        tile: (usize, usize),
+    },
+    GhostEaten {
+        ghost: GhostName,
+        tile: (usize, usize),
+        points: u32,
+    },
+    PacmanDied {
+        tile: (usize, usize),
+        lives_left: u32,
+    },
+    LevelCleared {
+        level: u32,
+    },
+    FruitSpawned {
+        fruit: Fruit,
+        tile: (usize, usize),
+    },
+    FruitEaten {
+        fruit: Fruit,
+        tile: (usize, usize),
+        points: u32,
+    },
+    DirectionChanged {
+        direction: (f32, f32),
+    },
+    TileEntered {
+        tile: (usize, usize),
+    },
+}
+
+pub trait EventListener {
+    // `tick` is the simulation tick the event happened on
+    fn on_event(&mut self, tick: u64, event: &GameEvent);
+}
+
+#[derive(Default, Clone)]
+pub struct EventQueue {
+    events: Vec<(u64, GameEvent)>,
+}
+
+impl EventQueue {
+    pub fn emit(&mut self, tick: u64, event: GameEvent) {
+        self.events.push((tick, event));
+    }
+
+    // Hands every queued event to each listener in turn, oldest first, and
+    // empties the queue
+    pub fn dispatch(&mut self, listeners: &mut [&mut dyn EventListener]) {
+        for (tick, event) in self.events.drain(..) {
+            for listener in listeners.iter_mut() {
+                listener.on_event(tick, &event);
+            }
+        }
+    }
+}
+
+// Writes every event to stderr, turned on with `log_events` in config.toml
+pub struct EventLog;
+
+impl EventListener for EventLog {
+    fn on_event(&mut self, tick: u64, event: &GameEvent) {
+        eprintln!("[{:>7}] {:?}", tick, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Writes down what it hears, in a log shared with the other listeners
+    struct Recorder<'a> {
+        name: &'static str,
+        heard: &'a RefCell<Vec<(&'static str, u64, GameEvent)>>,
+    }
+
+    impl EventListener for Recorder<'_> {
+        fn on_event(&mut self, tick: u64, event: &GameEvent) {
+            self.heard.borrow_mut().push((self.name, tick, *event));
+        }
+    }
+
+    #[test]
+    fn events_go_to_every_listener_in_order() {
+        let dot = GameEvent::DotEaten { tile: (1, 1) };
+        let died = GameEvent::PacmanDied {
+            tile: (2, 1),
+            lives_left: 2,
+        };
+        let mut queue = EventQueue::default();
+        queue.emit(7, dot);
+        queue.emit(9, died);
+
+        let heard = RefCell::new(Vec::new());
+        let mut first = Recorder {
+            name: "first",
+            heard: &heard,
+        };
+        let mut second = Recorder {
+            name: "second",
+            heard: &heard,
+        };
+        queue.dispatch(&mut [&mut first, &mut second]);
+        assert_eq!(
+            heard.take(),
+            vec![
+                ("first", 7, dot),
+                ("second", 7, dot),
+                ("first", 9, died),
+                ("second", 9, died),
+            ]
+        );
+
+        // Each event is only handed out once
+        queue.dispatch(&mut [&mut first, &mut second]);
+        assert!(heard.borrow().is_empty());
+    }
+}
@@ -0,0 +1,1297 @@
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::assists::Assists;
+use crate::events::{EventQueue, GameEvent};
+use crate::generate::{generate_maze, DEFAULT_PELLETS};
+use crate::level::{Level, LevelRules, Pellet};
+use crate::maze::MazeTile;
+use crate::pack::LevelPack;
+use crate::pathfinding::{Mover, Pathfinder};
+use crate::rng::Rng;
+
+pub const TILE_SIZE: f32 = 24.0; // Logical pixels per maze tile
+pub const TICKS_PER_SECOND: u32 = 60;
+
+// Pixels per tick at 100% speed. Pac-Man runs at 80% on the first level,
+// which is a sixth of a tile per tick.
+const FULL_SPEED: f32 = TILE_SIZE / 4.8;
+const EYES_SPEED: f32 = FULL_SPEED * 1.5;
+
+const START_LIVES: u32 = 3;
+const EXTRA_LIFE_SCORE: u32 = 10_000;
+const DOT_POINTS: u32 = 10;
+const ENERGIZER_POINTS: u32 = 50;
+
+const READY_TICKS: u32 = 2 * TICKS_PER_SECOND;
+pub const DEATH_TICKS: u32 = 2 * TICKS_PER_SECOND;
+const LEVEL_COMPLETE_TICKS: u32 = 2 * TICKS_PER_SECOND;
+const FRUIT_TICKS: u32 = 19 * TICKS_PER_SECOND / 2;
+const FRUIT_DOTS: [u32; 2] = [70, 170]; // Fruit appears after this many dots
+const FLASH_TICKS: u32 = 2 * TICKS_PER_SECOND; // Frightened ghosts flash for the last two seconds
+const FLASH_PERIOD: u32 = 14;
+// A pressed turn is forgotten if it can't be taken by then
+const TURN_BUFFER_TICKS: u32 = TICKS_PER_SECOND / 4;
+// Pac-Man and a ghost meet when their centres are closer than this
+pub const COLLISION_REACH: f32 = TILE_SIZE / 2.0;
+// A ghost is let out of the house when Pac-Man goes this long without eating
+const HOUSE_TIMEOUT_TICKS: u32 = 4 * TICKS_PER_SECOND;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GhostName {
+    Blinky,
+    Pinky,
+    Inky,
+    Clyde,
+}
+
+impl GhostName {
+    pub const ALL: [GhostName; 4] = [
+        GhostName::Blinky,
+        GhostName::Pinky,
+        GhostName::Inky,
+        GhostName::Clyde,
+    ];
+
+    // Used in sprite names and on the console
+    pub fn name(self) -> &'static str {
+        match self {
+            GhostName::Blinky => "blinky",
+            GhostName::Pinky => "pinky",
+            GhostName::Inky => "inky",
+            GhostName::Clyde => "clyde",
+        }
+    }
+
+    // As the arcade introduces them
+    pub fn nickname(self) -> &'static str {
+        match self {
+            GhostName::Blinky => "shadow",
+            GhostName::Pinky => "speedy",
+            GhostName::Inky => "bashful",
+            GhostName::Clyde => "pokey",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fruit {
+    Cherry,
+    Strawberry,
+    Orange,
+    Apple,
+    Melon,
+    Galaxian,
+    Bell,
+    Key,
+}
+
+impl Fruit {
+    // The arcade's fruit order, keys from level 13 on
+    pub fn for_level(level: u32) -> Fruit {
+        match level {
+            0 | 1 => Fruit::Cherry,
+            2 => Fruit::Strawberry,
+            3 | 4 => Fruit::Orange,
+            5 | 6 => Fruit::Apple,
+            7 | 8 => Fruit::Melon,
+            9 | 10 => Fruit::Galaxian,
+            11 | 12 => Fruit::Bell,
+            _ => Fruit::Key,
+        }
+    }
+
+    pub fn points(self) -> u32 {
+        match self {
+            Fruit::Cherry => 100,
+            Fruit::Strawberry => 300,
+            Fruit::Orange => 500,
+            Fruit::Apple => 700,
+            Fruit::Melon => 1000,
+            Fruit::Galaxian => 2000,
+            Fruit::Bell => 3000,
+            Fruit::Key => 5000,
+        }
+    }
+
+    // Used in sprite names
+    pub fn name(self) -> &'static str {
+        match self {
+            Fruit::Cherry => "cherry",
+            Fruit::Strawberry => "strawberry",
+            Fruit::Orange => "orange",
+            Fruit::Apple => "apple",
+            Fruit::Melon => "melon",
+            Fruit::Galaxian => "galaxian",
+            Fruit::Bell => "bell",
+            Fruit::Key => "key",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GhostState {
+    InHouse,       // Waiting to be let out
+    LeavingHouse,  // On the way out through the door
+    Active,        // Roaming the maze, scattering or chasing
+    Eaten,         // Eyes heading back to the door
+    EnteringHouse, // Eyes going through the door to be brought back
+}
+
+// What the ghosts that aren't frightened do, switched on a timer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GhostMode {
+    Scatter, // Each ghost heads for its own corner
+    Chase,   // Each ghost hunts Pac-Man in its own way
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+    Ready(u32), // Ticks until play starts
+    Playing,
+    Dying(u32),         // Ticks left of the death animation
+    LevelComplete(u32), // Ticks until the next level starts
+    GameOver,
+}
+
+#[derive(Clone)]
+pub struct Pacman {
+    pub pos: (f32, f32),
+    pub direction: (f32, f32),
+    pub turn_buffer: Option<(f32, f32)>, // Wanted direction, taken as soon as possible
+    pub turn_buffer_ticks: u32,          // Of play left before the wanted direction is forgotten
+    pub mouth_angle: f32,                // Degrees
+    pub mouth_opening: bool,
+}
+
+#[derive(Clone)]
+pub struct Ghost {
+    pub name: GhostName,
+    pub pos: (f32, f32),
+    pub direction: (f32, f32),
+    pub state: GhostState,
+    pub frightened: bool,
+    pub target: Option<(i32, i32)>, // Tile the ghost is heading for, None when wandering
+    pub forced_mode: Option<GhostMode>, // Scatters or chases regardless of the others, for testing
+    decided_at: Option<(i32, i32)>, // Tile where the ghost last picked a direction
+}
+
+impl Ghost {
+    // Waiting in the house until placed
+    pub fn new(name: GhostName) -> Ghost {
+        Ghost {
+            name,
+            pos: (0.0, 0.0),
+            direction: (0.0, 0.0),
+            state: GhostState::InHouse,
+            frightened: false,
+            target: None,
+            forced_mode: None,
+            decided_at: None,
+        }
+    }
+
+    // Which ways through the maze are open to the ghost as it is now
+    pub fn mover(&self) -> Mover {
+        match self.state {
+            GhostState::LeavingHouse => Mover::LeavingGhost,
+            GhostState::Eaten | GhostState::EnteringHouse => Mover::Eyes,
+            GhostState::InHouse | GhostState::Active => Mover::Ghost,
+        }
+    }
+}
+
+// Speeds for a level as fractions of FULL_SPEED
+struct Speeds {
+    pacman: f32,
+    pacman_frightened: f32,
+    ghost: f32,
+    ghost_frightened: f32,
+    ghost_tunnel: f32,
+}
+
+fn speeds(level: u32) -> Speeds {
+    match level {
+        0 | 1 => Speeds {
+            pacman: 0.8,
+            pacman_frightened: 0.9,
+            ghost: 0.75,
+            ghost_frightened: 0.5,
+            ghost_tunnel: 0.4,
+        },
+        2..=4 => Speeds {
+            pacman: 0.9,
+            pacman_frightened: 0.95,
+            ghost: 0.85,
+            ghost_frightened: 0.55,
+            ghost_tunnel: 0.45,
+        },
+        _ => Speeds {
+            pacman: 1.0,
+            pacman_frightened: 1.0,
+            ghost: 0.95,
+            ghost_frightened: 0.6,
+            ghost_tunnel: 0.5,
+        },
+    }
+}
+
+// Seconds of scatter, chase, scatter and so on. The chase after the last
+// entry lasts for the rest of the level.
+fn mode_schedule(level: u32) -> &'static [u32] {
+    match level {
+        0 | 1 => &[7, 20, 7, 20, 5, 20, 5],
+        2..=4 => &[7, 20, 7, 20, 5, 1033],
+        _ => &[5, 20, 5, 20, 5, 1037],
+    }
+}
+
+fn frightened_seconds(level: u32) -> u32 {
+    const SECONDS: [u32; 18] = [6, 5, 4, 3, 2, 5, 2, 2, 1, 5, 2, 1, 1, 3, 1, 1, 0, 1];
+    SECONDS.get(level.max(1) as usize - 1).copied().unwrap_or(0)
+}
+
+// How many dots Pinky, Inky and Clyde wait for before leaving the house
+fn house_dot_limits(level: u32, lost_life: bool) -> [u32; 3] {
+    if lost_life {
+        return [7, 17, 32];
+    }
+    match level {
+        0 | 1 => [0, 30, 60],
+        2 => [0, 0, 50],
+        _ => [0, 0, 0],
+    }
+}
+
+pub fn tile_of(pos: (f32, f32)) -> (i32, i32) {
+    (
+        (pos.0 / TILE_SIZE).floor() as i32,
+        (pos.1 / TILE_SIZE).floor() as i32,
+    )
+}
+
+pub fn tile_centre(tile: (i32, i32)) -> (f32, f32) {
+    (
+        (tile.0 as f32 + 0.5) * TILE_SIZE,
+        (tile.1 as f32 + 0.5) * TILE_SIZE,
+    )
+}
+
+fn start_position(tile: (usize, usize)) -> (f32, f32) {
+    tile_centre((tile.0 as i32, tile.1 as i32))
+}
+
+fn distance_squared(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).pow(2) + (a.1 - b.1).pow(2)
+}
+
+// Where each level's maze comes from
+#[derive(Clone)]
+pub enum Mazes {
+    Fixed,               // The same level every time
+    Endless,             // A new maze for every level, generated from the game's seed
+    Pack(Rc<LevelPack>), // The pack's levels in order
+}
+
+// The whole simulation. It advances one fixed tick at a time and never
+// looks at the clock, so it runs the same with or without a window.
+#[derive(Clone)]
+pub struct Game {
+    pub level: Level,
+    pub pellets: HashMap<(usize, usize), Pellet>,
+    pub pacman: Pacman,
+    pub ghosts: [Ghost; 4],          // In GhostName::ALL order
+    pub fruit: Option<(Fruit, u32)>, // The fruit on show and the ticks it has left
+    pub score: u32,
+    pub lives: u32,
+    pub level_number: u32, // Counted from 1
+    pub phase: Phase,
+    pub tick: u64,
+    pub ghost_mode: GhostMode,
+    pub events: EventQueue,
+    pub paths: Pathfinder,
+    pub invincible: bool,          // Ghosts can't catch Pac-Man, for testing
+    pub pacman_speed: Option<f32>, // Overrides the level's speed for Pac-Man, for testing
+    pub assists: Assists,
+    pub dev_tools_used: bool, // The console or playback controls, which keep the game out of the high scores
+    pub score_recorded: bool, // In the high scores, so a rewound game over doesn't go in twice
+    mazes: Mazes,
+    seed: u64,
+    mode_step: usize,        // Position in mode_schedule
+    mode_ticks_left: u32,    // Until the next scatter/chase switch
+    frightened_ticks: u32,   // Left of the current energizer
+    ghosts_eaten_chain: u32, // Ghosts eaten on the current energizer
+    dots_eaten: u32,         // This level
+    house_dots: u32,         // Dots eaten since the house counters were reset
+    lost_life: bool,         // This level
+    ticks_since_dot: u32,
+    extra_life_awarded: bool,
+    tunnels: HashSet<(i32, i32)>, // Tiles where ghosts slow down
+    rng: Rng,
+}
+
+impl Game {
+    pub fn new(level: Level, seed: u64) -> Game {
+        let tunnels = find_tunnels(&level);
+        let ghosts = GhostName::ALL.map(Ghost::new);
+        let mut game = Game {
+            pellets: HashMap::new(),
+            pacman: Pacman {
+                pos: (0.0, 0.0),
+                direction: (0.0, 0.0),
+                turn_buffer: None,
+                turn_buffer_ticks: 0,
+                mouth_angle: 45.0,
+                mouth_opening: false,
+            },
+            ghosts,
+            fruit: None,
+            score: 0,
+            lives: START_LIVES,
+            level_number: 1,
+            phase: Phase::Ready(READY_TICKS),
+            tick: 0,
+            ghost_mode: GhostMode::Scatter,
+            events: EventQueue::default(),
+            paths: Pathfinder::new(&level.tiles),
+            invincible: false,
+            pacman_speed: None,
+            assists: Assists::default(),
+            dev_tools_used: false,
+            score_recorded: false,
+            mazes: Mazes::Fixed,
+            seed,
+            mode_step: 0,
+            mode_ticks_left: 0,
+            frightened_ticks: 0,
+            ghosts_eaten_chain: 0,
+            dots_eaten: 0,
+            house_dots: 0,
+            lost_life: false,
+            ticks_since_dot: 0,
+            extra_life_awarded: false,
+            tunnels,
+            rng: Rng::new(seed),
+            level,
+        };
+        game.start_level();
+        game
+    }
+
+    // Skips straight to the start of another level
+    pub fn go_to_level(&mut self, level_number: u32) {
+        self.level_number = level_number.max(1);
+        self.start_level();
+    }
+
+    // Changes where the mazes come from and starts over at `level_number`
+    pub fn set_mazes(&mut self, mazes: Mazes, level_number: u32) {
+        self.mazes = mazes;
+        self.go_to_level(level_number);
+    }
+
+    // Turns on the assists for a new game, giving any extra lives
+    pub fn set_assists(&mut self, assists: Assists) {
+        self.lives += assists.extra_lives;
+        self.assists = assists;
+    }
+
+    pub fn mazes(&self) -> &Mazes {
+        &self.mazes
+    }
+
+    // The rules for a level, which for the level being played are the
+    // current level's own
+    pub fn rules_for(&self, level_number: u32) -> &LevelRules {
+        match &self.mazes {
+            Mazes::Pack(pack) if level_number != self.level_number => {
+                &pack.entry(level_number).level.rules
+            }
+            _ => &self.level.rules,
+        }
+    }
+
+    // The fruit put out on a level, the pack's if it picks one
+    pub fn fruit_for(&self, level_number: u32) -> Fruit {
+        self.rules_for(level_number)
+            .fruit
+            .unwrap_or_else(|| Fruit::for_level(level_number))
+    }
+
+    fn start_level(&mut self) {
+        let level = match &self.mazes {
+            Mazes::Fixed => None,
+            // The same game seed gives the same run of mazes. A maze that
+            // can't be made leaves the last one in place.
+            Mazes::Endless => {
+                let maze_seed = self.seed.wrapping_add(self.level_number as u64);
+                generate_maze(maze_seed, DEFAULT_PELLETS).ok()
+            }
+            Mazes::Pack(pack) => Some(pack.entry(self.level_number).level.clone()),
+        };
+        if let Some(level) = level {
+            self.tunnels = find_tunnels(&level);
+            self.level = level;
+        }
+        self.pellets = self.level.pellets.clone();
+        self.dots_eaten = 0;
+        self.lost_life = false;
+        self.fruit = None;
+        self.reset_actors();
+        self.phase = Phase::Ready(READY_TICKS);
+    }
+
+    // Back to the starting positions, at the start of a level or a life
+    fn reset_actors(&mut self) {
+        self.pacman.pos = start_position(self.level.pacman_start);
+        self.pacman.direction = (0.0, 0.0);
+        self.pacman.turn_buffer = None;
+        for (ghost, start) in self.ghosts.iter_mut().zip(self.level.ghost_starts) {
+            ghost.pos = start_position(start);
+            ghost.direction = (0.0, 0.0);
+            ghost.frightened = false;
+            ghost.target = None;
+            ghost.decided_at = None;
+            // Blinky starts outside, the others wait in the house
+            ghost.state = if ghost.name == GhostName::Blinky {
+                GhostState::Active
+            } else {
+                GhostState::InHouse
+            };
+        }
+        self.ghost_mode = GhostMode::Scatter;
+        self.mode_step = 0;
+        self.mode_ticks_left = mode_schedule(self.level_number)[0] * TICKS_PER_SECOND;
+        self.frightened_ticks = 0;
+        self.house_dots = 0;
+        self.ticks_since_dot = 0;
+    }
+
+    // Advances the game by one tick. `turn` is the direction the player
+    // wants, if any.
+    pub fn tick(&mut self, turn: Option<(f32, f32)>) {
+        self.tick += 1;
+        self.paths.update(&self.level.tiles);
+        if let Some(turn) = turn {
+            self.pacman.turn_buffer = Some(turn);
+            self.pacman.turn_buffer_ticks = TURN_BUFFER_TICKS;
+        }
+
+        match self.phase {
+            Phase::Ready(ticks) => {
+                self.phase = if ticks <= 1 {
+                    Phase::Playing
+                } else {
+                    Phase::Ready(ticks - 1)
+                };
+            }
+            Phase::Playing => self.play_tick(),
+            Phase::Dying(ticks) if ticks > 1 => self.phase = Phase::Dying(ticks - 1),
+            Phase::Dying(_) => {
+                if self.lives > 0 {
+                    self.reset_actors();
+                    self.phase = Phase::Ready(READY_TICKS);
+                } else {
+                    self.phase = Phase::GameOver;
+                }
+            }
+            Phase::LevelComplete(ticks) if ticks > 1 => {
+                self.phase = Phase::LevelComplete(ticks - 1)
+            }
+            Phase::LevelComplete(_) => {
+                self.level_number += 1;
+                self.start_level();
+            }
+            Phase::GameOver => {}
+        }
+    }
+
+    fn play_tick(&mut self) {
+        let speeds = self.speeds();
+
+        // Pac-Man
+        let old_direction = self.pacman.direction;
+        self.apply_buffered_turn();
+        if self.assists.turn_assist {
+            self.assist_turn();
+        }
+        if self.pacman.direction != old_direction {
+            self.events.emit(
+                self.tick,
+                GameEvent::DirectionChanged {
+                    direction: self.pacman.direction,
+                },
+            );
+        }
+        let old_tile = tile_of(self.pacman.pos);
+        let pacman_speed = match self.pacman_speed {
+            Some(speed) => speed,
+            None if self.frightened_ticks > 0 => speeds.pacman_frightened,
+            None => speeds.pacman,
+        };
+        let moved = self.move_pacman(pacman_speed * FULL_SPEED);
+        let tile = tile_of(self.pacman.pos);
+        if let Some(maze_tile) = self.maze_tile(tile) {
+            if tile != old_tile {
+                self.events
+                    .emit(self.tick, GameEvent::TileEntered { tile: maze_tile });
+            }
+            self.eat(maze_tile);
+        }
+        if moved {
+            update_pacman_mouth_angle(&mut self.pacman, 5.0);
+        }
+
+        self.update_timers();
+        self.release_ghosts();
+        for i in 0..self.ghosts.len() {
+            self.move_ghost(i, &speeds);
+        }
+        self.check_collisions();
+        // Caught on the tick the last pellet went, the death comes first and
+        // the level is cleared once he is back
+        if matches!(self.phase, Phase::Dying(_)) {
+            return;
+        }
+
+        if self.pellets.is_empty() {
+            self.events.emit(
+                self.tick,
+                GameEvent::LevelCleared {
+                    level: self.level_number,
+                },
+            );
+            self.phase = Phase::LevelComplete(LEVEL_COMPLETE_TICKS);
+        }
+    }
+
+    // How far through the level's pellets Pac-Man is, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        let total = self.level.pellets.len().max(1);
+        1.0 - self.pellets.len() as f32 / total as f32
+    }
+
+    pub fn any_frightened(&self) -> bool {
+        self.frightened_ticks > 0 && self.ghosts.iter().any(|ghost| ghost.frightened)
+    }
+
+    // Frightened ghosts flash white as frightened mode runs out
+    pub fn frightened_flashing(&self) -> bool {
+        self.frightened_ticks > 0
+            && self.frightened_ticks < FLASH_TICKS
+            && (self.frightened_ticks / FLASH_PERIOD).is_multiple_of(2)
+    }
+
+    // How far through the time frightened ghosts flash, from 0 to 1, for
+    // drawing a fade in place of the flashing
+    pub fn frightened_ending(&self) -> Option<f32> {
+        (self.frightened_ticks > 0 && self.frightened_ticks < FLASH_TICKS)
+            .then(|| 1.0 - self.frightened_ticks as f32 / FLASH_TICKS as f32)
+    }
+
+    // The maze flashes white in the pause after a level is cleared, as in
+    // the arcade. Gives whether it is white now, and how far through the
+    // pause the game is from 0 to 1.
+    pub fn maze_flash(&self) -> Option<(bool, f32)> {
+        let Phase::LevelComplete(ticks_left) = self.phase else {
+            return None;
+        };
+        let white = (ticks_left / FLASH_PERIOD).is_multiple_of(2);
+        Some((white, 1.0 - ticks_left as f32 / LEVEL_COMPLETE_TICKS as f32))
+    }
+
+    fn maze_tile(&self, tile: (i32, i32)) -> Option<(usize, usize)> {
+        let inside = tile.0 >= 0
+            && tile.1 >= 0
+            && (tile.0 as usize) < self.level.width()
+            && (tile.1 as usize) < self.level.height();
+        inside.then_some((tile.0 as usize, tile.1 as usize))
+    }
+
+    fn tile_type(&self, tile: (i32, i32)) -> Option<MazeTile> {
+        let (col, row) = self.maze_tile(tile)?;
+        self.level.tiles[row].get(col).copied()
+    }
+
+    // Closest tile inside the maze, for reporting positions in the tunnels
+    fn clamp_tile(&self, tile: (i32, i32)) -> (usize, usize) {
+        (
+            tile.0.clamp(0, self.level.width() as i32 - 1) as usize,
+            tile.1.clamp(0, self.level.height() as i32 - 1) as usize,
+        )
+    }
+
+    // Off the maze is open, that is where the tunnels wrap around
+    pub fn blocks_pacman(&self, tile: (i32, i32)) -> bool {
+        self.tile_type(tile)
+            .is_some_and(|tile| tile.is_wall() || tile == MazeTile::Door)
+    }
+
+    // Takes the buffered direction once it is possible. Reversing is always
+    // possible, while a turn waits until Pac-Man reaches the centre of a tile
+    // with an opening on that side, so a turn pressed early isn't lost. One
+    // that still can't be taken a moment later is dropped, so an old tap
+    // doesn't turn Pac-Man corridors further on.
+    fn apply_buffered_turn(&mut self) {
+        let pacman = &self.pacman;
+        let Some(wanted) = pacman.turn_buffer else {
+            return;
+        };
+        let direction = pacman.direction;
+        let is_reversal = wanted.0 == -direction.0 && wanted.1 == -direction.1;
+        if wanted == direction || is_reversal || direction == (0.0, 0.0) {
+            self.pacman.direction = wanted;
+            self.pacman.turn_buffer = None;
+            return;
+        }
+
+        let tile = tile_of(pacman.pos);
+        let centre = tile_centre(tile);
+        let speed = FULL_SPEED;
+        let near_centre =
+            (pacman.pos.0 - centre.0).abs() <= speed && (pacman.pos.1 - centre.1).abs() <= speed;
+        let next = (tile.0 + wanted.0 as i32, tile.1 + wanted.1 as i32);
+
+        // No turning in the tunnels off the edge of the maze
+        if self.maze_tile(tile).is_some() && near_centre && !self.blocks_pacman(next) {
+            self.pacman.pos = centre;
+            self.pacman.direction = wanted;
+            self.pacman.turn_buffer = None;
+            return;
+        }
+        self.pacman.turn_buffer_ticks = self.pacman.turn_buffer_ticks.saturating_sub(1);
+        if self.pacman.turn_buffer_ticks == 0 {
+            self.pacman.turn_buffer = None;
+        }
+    }
+
+    // Turns Pac-Man when the way ahead is blocked rather than letting him
+    // stop. A turn the player has asked for goes first, and runs after
+    // apply_buffered_turn, so if one is still waiting it can't be taken here.
+    // With a way open on both sides he goes towards a pellet, or turns right
+    // if neither side has one.
+    fn assist_turn(&mut self) {
+        let direction = self.pacman.direction;
+        if direction == (0.0, 0.0) {
+            return;
+        }
+        let tile = tile_of(self.pacman.pos);
+        if self.maze_tile(tile).is_none() {
+            return;
+        }
+        let ahead = (tile.0 + direction.0 as i32, tile.1 + direction.1 as i32);
+        let centre = tile_centre(tile);
+        let at_centre = (self.pacman.pos.0 - centre.0).abs() <= FULL_SPEED
+            && (self.pacman.pos.1 - centre.1).abs() <= FULL_SPEED;
+        if !at_centre || !self.blocks_pacman(ahead) {
+            return;
+        }
+        let next = |side: (f32, f32)| (tile.0 + side.0 as i32, tile.1 + side.1 as i32);
+        let has_pellet = |side: (f32, f32)| {
+            self.maze_tile(next(side))
+                .is_some_and(|tile| self.pellets.contains_key(&tile))
+        };
+        let right = (-direction.1, direction.0);
+        let left = (direction.1, -direction.0);
+        let open: Vec<(f32, f32)> = [right, left]
+            .into_iter()
+            .filter(|&side| !self.blocks_pacman(next(side)))
+            .collect();
+        let side = match open[..] {
+            [side] => side,
+            [right, left] if !has_pellet(right) && has_pellet(left) => left,
+            [right, _] => right,
+            _ => return,
+        };
+        self.pacman.pos = centre;
+        self.pacman.direction = side;
+    }
+
+    // Moves Pac-Man along his direction, stopping in the middle of the tile
+    // in front of a wall. Returns whether he moved.
+    fn move_pacman(&mut self, speed: f32) -> bool {
+        let direction = self.pacman.direction;
+        if direction == (0.0, 0.0) {
+            return false;
+        }
+        let pos = self.pacman.pos;
+        let tile = tile_of(pos);
+        let ahead = (tile.0 + direction.0 as i32, tile.1 + direction.1 as i32);
+        let step = if self.blocks_pacman(ahead) {
+            let centre = tile_centre(tile);
+            let to_centre = (centre.0 - pos.0) * direction.0 + (centre.1 - pos.1) * direction.1;
+            to_centre.clamp(0.0, speed)
+        } else {
+            speed
+        };
+        self.pacman.pos.0 += direction.0 * step;
+        self.pacman.pos.1 += direction.1 * step;
+        let level_size = self.level_size();
+        wrap_around(&mut self.pacman.pos, level_size);
+        step > 0.0
+    }
+
+    fn level_size(&self) -> (f32, f32) {
+        (
+            self.level.width() as f32 * TILE_SIZE,
+            self.level.height() as f32 * TILE_SIZE,
+        )
+    }
+
+    fn eat(&mut self, tile: (usize, usize)) {
+        let pellet = self.pellets.remove(&tile);
+        match pellet {
+            Some(Pellet::Dot) => {
+                self.add_score(DOT_POINTS);
+                self.events.emit(self.tick, GameEvent::DotEaten { tile });
+            }
+            Some(Pellet::Energizer) => {
+                self.add_score(ENERGIZER_POINTS);
+                self.events
+                    .emit(self.tick, GameEvent::EnergizerEaten { tile });
+                self.frighten_ghosts();
+            }
+            None => {}
+        }
+        if pellet.is_some() {
+            self.dots_eaten += 1;
+            self.house_dots += 1;
+            self.ticks_since_dot = 0;
+            if FRUIT_DOTS.contains(&self.dots_eaten) {
+                self.spawn_fruit();
+            }
+        }
+
+        if let Some((fruit, _)) = self.fruit {
+            if tile == self.level.fruit_tile {
+                self.fruit = None;
+                let points = fruit.points();
+                self.add_score(points);
+                self.events.emit(
+                    self.tick,
+                    GameEvent::FruitEaten {
+                        fruit,
+                        tile,
+                        points,
+                    },
+                );
+            }
+        }
+    }
+
+    // Puts the level's fruit out below the ghost house
+    pub fn spawn_fruit(&mut self) {
+        let fruit = self.fruit_for(self.level_number);
+        self.fruit = Some((fruit, FRUIT_TICKS));
+        self.events.emit(
+            self.tick,
+            GameEvent::FruitSpawned {
+                fruit,
+                tile: self.level.fruit_tile,
+            },
+        );
+    }
+
+    fn add_score(&mut self, points: u32) {
+        self.score += points;
+        if !self.extra_life_awarded && self.score >= EXTRA_LIFE_SCORE {
+            self.extra_life_awarded = true;
+            self.lives += 1;
+        }
+    }
+
+    fn frighten_ghosts(&mut self) {
+        self.ghosts_eaten_chain = 0;
+        let seconds = self
+            .level
+            .rules
+            .frightened_seconds
+            .unwrap_or_else(|| frightened_seconds(self.level_number))
+            + self.assists.extra_frightened_seconds;
+        self.frightened_ticks = seconds * TICKS_PER_SECOND;
+        for ghost in &mut self.ghosts {
+            if matches!(
+                ghost.state,
+                GhostState::InHouse | GhostState::LeavingHouse | GhostState::Active
+            ) {
+                ghost.frightened = self.frightened_ticks > 0;
+            }
+            if ghost.state == GhostState::Active {
+                reverse(ghost);
+            }
+        }
+    }
+
+    fn update_timers(&mut self) {
+        self.ticks_since_dot += 1;
+
+        if let Some((fruit, ticks)) = self.fruit {
+            self.fruit = (ticks > 1).then_some((fruit, ticks - 1));
+        }
+
+        // The scatter/chase timer stands still while the ghosts are frightened
+        if self.frightened_ticks > 0 {
+            self.frightened_ticks -= 1;
+            if self.frightened_ticks == 0 {
+                for ghost in &mut self.ghosts {
+                    ghost.frightened = false;
+                }
+            }
+            return;
+        }
+
+        let schedule = mode_schedule(self.level_number);
+        if self.mode_step >= schedule.len() {
+            return; // Chasing for the rest of the level
+        }
+        self.mode_ticks_left = self.mode_ticks_left.saturating_sub(1);
+        if self.mode_ticks_left == 0 {
+            self.mode_step += 1;
+            self.mode_ticks_left =
+                schedule.get(self.mode_step).copied().unwrap_or(0) * TICKS_PER_SECOND;
+            self.ghost_mode = if self.mode_step.is_multiple_of(2) {
+                GhostMode::Scatter
+            } else {
+                GhostMode::Chase
+            };
+            // Ghosts turn around whenever the mode changes
+            for ghost in &mut self.ghosts {
+                if ghost.state == GhostState::Active {
+                    reverse(ghost);
+                }
+            }
+        }
+    }
+
+    // Lets Pinky, Inky and Clyde out in turn once enough dots are eaten, or
+    // one of them when Pac-Man stops eating for a while
+    fn release_ghosts(&mut self) {
+        let limits = house_dot_limits(self.level_number, self.lost_life);
+        let timed_out = self.ticks_since_dot >= HOUSE_TIMEOUT_TICKS;
+        let waiting = (1..self.ghosts.len()).find(|&i| self.ghosts[i].state == GhostState::InHouse);
+        if let Some(i) = waiting {
+            if timed_out || self.house_dots >= limits[i - 1] {
+                self.ghosts[i].state = GhostState::LeavingHouse;
+                self.ghosts[i].decided_at = None;
+                if timed_out {
+                    self.ticks_since_dot = 0;
+                }
+            }
+        }
+    }
+
+    // The arcade's speeds for the level, with the level's own rules on top
+    fn speeds(&self) -> Speeds {
+        let mut speeds = speeds(self.level_number);
+        let rules = &self.level.rules;
+        speeds.pacman = rules.pacman_speed.unwrap_or(speeds.pacman);
+        speeds.ghost = rules.ghost_speed.unwrap_or(speeds.ghost);
+        speeds
+    }
+
+    fn move_ghost(&mut self, i: usize, speeds: &Speeds) {
+        let ghost = &self.ghosts[i];
+        let tile = tile_of(ghost.pos);
+        let speed = match ghost.state {
+            GhostState::InHouse => return,
+            GhostState::Eaten | GhostState::EnteringHouse => EYES_SPEED,
+            GhostState::LeavingHouse => speeds.ghost_frightened * FULL_SPEED,
+            GhostState::Active if self.tunnels.contains(&tile) => speeds.ghost_tunnel * FULL_SPEED,
+            GhostState::Active if ghost.frightened => speeds.ghost_frightened * FULL_SPEED,
+            GhostState::Active => speeds.ghost * FULL_SPEED,
+        };
+
+        // Ghosts pick a new direction as they reach the centre of each tile
+        let centre = tile_centre(tile);
+        let direction = ghost.direction;
+        let to_centre =
+            (centre.0 - ghost.pos.0) * direction.0 + (centre.1 - ghost.pos.1) * direction.1;
+        let reaches_centre = to_centre >= 0.0 && to_centre < speed;
+        let mut remaining = speed;
+        if reaches_centre && ghost.decided_at != Some(tile) && self.maze_tile(tile).is_some() {
+            self.ghosts[i].pos = centre;
+            remaining -= to_centre;
+            self.arrive(i, tile);
+            let direction = self.choose_direction(i, tile);
+            let ghost = &mut self.ghosts[i];
+            ghost.direction = direction;
+            ghost.decided_at = Some(tile);
+        }
+
+        let ghost = &mut self.ghosts[i];
+        ghost.pos.0 += ghost.direction.0 * remaining;
+        ghost.pos.1 += ghost.direction.1 * remaining;
+        let size = self.level_size();
+        wrap_around(&mut self.ghosts[i].pos, size);
+    }
+
+    // State changes that happen on reaching certain tiles
+    fn arrive(&mut self, i: usize, tile: (i32, i32)) {
+        let below_is_door = self.tile_type((tile.0, tile.1 + 1)) == Some(MazeTile::Door);
+        let house_centre = self.level.house_centre();
+        let ghost = &mut self.ghosts[i];
+        match ghost.state {
+            GhostState::LeavingHouse if below_is_door => ghost.state = GhostState::Active,
+            GhostState::Eaten if below_is_door => ghost.state = GhostState::EnteringHouse,
+            GhostState::EnteringHouse if tile == (house_centre.0 as i32, house_centre.1 as i32) => {
+                ghost.state = GhostState::LeavingHouse; // Back to life and straight out again
+            }
+            _ => {}
+        }
+    }
+
+    fn target_tile(&self, i: usize) -> Option<(i32, i32)> {
+        let ghost = &self.ghosts[i];
+        let exit = (
+            self.level.house_exit.0 as i32,
+            self.level.house_exit.1 as i32,
+        );
+        let centre = self.level.house_centre();
+        match ghost.state {
+            GhostState::InHouse => return None,
+            GhostState::LeavingHouse | GhostState::Eaten => return Some(exit),
+            GhostState::EnteringHouse => return Some((centre.0 as i32, centre.1 as i32)),
+            GhostState::Active if ghost.frightened => return None,
+            GhostState::Active => {}
+        }
+
+        let (width, height) = (self.level.width() as i32, self.level.height() as i32);
+        let corner = match ghost.name {
+            GhostName::Blinky => (width - 3, -4),
+            GhostName::Pinky => (2, -4),
+            GhostName::Inky => (width - 1, height),
+            GhostName::Clyde => (0, height),
+        };
+        if ghost.forced_mode.unwrap_or(self.ghost_mode) == GhostMode::Scatter {
+            return Some(corner);
+        }
+
+        let pacman = tile_of(self.pacman.pos);
+        let facing = (
+            self.pacman.direction.0 as i32,
+            self.pacman.direction.1 as i32,
+        );
+        let ahead = |tiles: i32| (pacman.0 + facing.0 * tiles, pacman.1 + facing.1 * tiles);
+        Some(match ghost.name {
+            GhostName::Blinky => pacman,
+            GhostName::Pinky => ahead(4),
+            GhostName::Inky => {
+                // Twice the vector from Blinky to two tiles ahead of Pac-Man
+                let pivot = ahead(2);
+                let blinky = tile_of(self.ghosts[0].pos);
+                (pivot.0 * 2 - blinky.0, pivot.1 * 2 - blinky.1)
+            }
+            GhostName::Clyde => {
+                // Chases from afar but runs to his corner when close
+                let clyde = tile_of(ghost.pos);
+                if distance_squared(clyde, pacman) > 64 {
+                    pacman
+                } else {
+                    corner
+                }
+            }
+        })
+    }
+
+    // Whether a ghost may step from `tile` in `direction`. The door only
+    // lets ghosts out going up and eyes in going down.
+    fn ghost_can_enter(&self, i: usize, tile: (i32, i32), direction: (i32, i32)) -> bool {
+        let next = (tile.0 + direction.0, tile.1 + direction.1);
+        match self.tile_type(next) {
+            None => direction.1 == 0, // Tunnels only run sideways
+            Some(MazeTile::Door) => match self.ghosts[i].state {
+                GhostState::LeavingHouse => direction == (0, -1),
+                GhostState::Eaten | GhostState::EnteringHouse => direction == (0, 1),
+                _ => false,
+            },
+            Some(tile) => !tile.is_wall(),
+        }
+    }
+
+    // At every tile a ghost takes the open way closest to its target as the
+    // crow flies, never turning back unless it is a dead end. Ties go up,
+    // left, down, right like the arcade. Frightened ghosts pick at random.
+    // Eyes go home the shortest way along the maze, turning back if need be.
+    fn choose_direction(&mut self, i: usize, tile: (i32, i32)) -> (f32, f32) {
+        let target = self.target_tile(i);
+        self.ghosts[i].target = target;
+        let current = self.ghosts[i].direction;
+        let current = (current.0 as i32, current.1 as i32);
+        let chosen = match self.ghost_way(i, tile, current, target) {
+            Ok(chosen) => chosen,
+            Err(options) if options.is_empty() => return (0.0, 0.0), // Boxed in
+            Err(options) => options[self.rng.below(options.len())],
+        };
+        (chosen.0 as f32, chosen.1 as f32)
+    }
+
+    // The way choose_direction's rule has a ghost heading in `current` leave
+    // `tile` for `target`. Without a target it is left to chance, and the
+    // ways it may go are the error, none when it is boxed in.
+    fn ghost_way(
+        &self,
+        i: usize,
+        tile: (i32, i32),
+        current: (i32, i32),
+        target: Option<(i32, i32)>,
+    ) -> Result<(i32, i32), Vec<(i32, i32)>> {
+        const ORDER: [(i32, i32); 4] = [(0, -1), (-1, 0), (0, 1), (1, 0)];
+        let eyes = self.ghosts[i].mover() == Mover::Eyes;
+        let home = target
+            .filter(|_| eyes)
+            .and_then(|target| self.paths.distances_to(Mover::Eyes, target));
+
+        let reverse = (-current.0, -current.1);
+        let mut options: Vec<(i32, i32)> = ORDER
+            .into_iter()
+            .filter(|&d| d != reverse || current == (0, 0) || home.is_some())
+            .filter(|&d| self.ghost_can_enter(i, tile, d))
+            .collect();
+        if options.is_empty() {
+            if !self.ghost_can_enter(i, tile, reverse) {
+                return Err(options);
+            }
+            options.push(reverse);
+        }
+
+        let width = self.level.width() as i32;
+        let chosen = match (target, home) {
+            (_, Some(home)) => options.iter().copied().min_by_key(|d| {
+                let next = ((tile.0 + d.0).rem_euclid(width), tile.1 + d.1);
+                home.get(next).unwrap_or(u32::MAX)
+            }),
+            (Some(target), None) => options
+                .iter()
+                .copied()
+                .min_by_key(|d| distance_squared((tile.0 + d.0, tile.1 + d.1), target)),
+            (None, None) => None,
+        };
+        chosen.ok_or(options)
+    }
+
+    // The tiles a ghost will go through, up to `steps` of them or its
+    // target, if the target stays where it is. It follows choose_direction's
+    // rule, so this is the ghost's real plan rather than the shortest way.
+    // Empty for a frightened ghost, whose turns are down to chance.
+    pub fn planned_path(&self, i: usize, steps: usize) -> Vec<(i32, i32)> {
+        let ghost = &self.ghosts[i];
+        let mut path = Vec::new();
+        let Some(target) = ghost.target.filter(|_| !ghost.frightened) else {
+            return path;
+        };
+        let width = self.level.width() as i32;
+        let mut tile = tile_of(ghost.pos);
+        let mut direction = (ghost.direction.0 as i32, ghost.direction.1 as i32);
+        let step =
+            |tile: (i32, i32), d: (i32, i32)| ((tile.0 + d.0).rem_euclid(width), tile.1 + d.1);
+        // Already on its way out of this tile
+        if ghost.decided_at == Some(tile) && direction != (0, 0) {
+            tile = step(tile, direction);
+            path.push(tile);
+        }
+        while path.len() < steps && tile != target {
+            let Ok(chosen) = self.ghost_way(i, tile, direction, Some(target)) else {
+                break;
+            };
+            direction = chosen;
+            tile = step(tile, direction);
+            path.push(tile);
+        }
+        path
+    }
+
+    fn check_collisions(&mut self) {
+        for i in 0..self.ghosts.len() {
+            let ghost = &self.ghosts[i];
+            if !matches!(ghost.state, GhostState::Active | GhostState::LeavingHouse) {
+                continue;
+            }
+            let dx = ghost.pos.0 - self.pacman.pos.0;
+            let dy = ghost.pos.1 - self.pacman.pos.1;
+            if dx.hypot(dy) >= COLLISION_REACH {
+                continue;
+            }
+
+            if ghost.frightened {
+                let points = 200 << self.ghosts_eaten_chain.min(3);
+                self.ghosts_eaten_chain += 1;
+                let tile = self.clamp_tile(tile_of(ghost.pos));
+                let ghost = &mut self.ghosts[i];
+                ghost.state = GhostState::Eaten;
+                ghost.frightened = false;
+                ghost.decided_at = None;
+                let name = ghost.name;
+                self.add_score(points);
+                self.events.emit(
+                    self.tick,
+                    GameEvent::GhostEaten {
+                        ghost: name,
+                        tile,
+                        points,
+                    },
+                );
+            } else if !self.invincible && !self.assists.tourist_mode {
+                // With no lives left the dying ends in game over
+                self.lives = self.lives.saturating_sub(1);
+                self.lost_life = true;
+                self.fruit = None;
+                self.phase = Phase::Dying(DEATH_TICKS);
+                self.events.emit(
+                    self.tick,
+                    GameEvent::PacmanDied {
+                        tile: self.clamp_tile(tile_of(self.pacman.pos)),
+                        lives_left: self.lives,
+                    },
+                );
+                return;
+            }
+        }
+    }
+}
+
+fn reverse(ghost: &mut Ghost) {
+    ghost.direction = (-ghost.direction.0, -ghost.direction.1);
+    ghost.decided_at = None;
+}
+
+// Open tiles joined to an open edge of the maze without crossing a pellet.
+// Ghosts slow down in them.
+fn find_tunnels(level: &Level) -> HashSet<(i32, i32)> {
+    let open = |col: usize, row: usize| {
+        level.tiles[row]
+            .get(col)
+            .is_some_and(|tile| *tile == MazeTile::Empty)
+            && !level.pellets.contains_key(&(col, row))
+    };
+    let (width, height) = (level.width(), level.height());
+    let mut tunnels = HashSet::new();
+    for row in 0..height {
+        for cols in [
+            &mut (0..width) as &mut dyn Iterator<Item = usize>,
+            &mut (0..width).rev(),
+        ] {
+            for col in cols.take_while(|&col| open(col, row)) {
+                tunnels.insert((col as i32, row as i32));
+            }
+        }
+    }
+    for col in 0..width {
+        for rows in [
+            &mut (0..height) as &mut dyn Iterator<Item = usize>,
+            &mut (0..height).rev(),
+        ] {
+            for row in rows.take_while(|&row| open(col, row)) {
+                tunnels.insert((col as i32, row as i32));
+            }
+        }
+    }
+    tunnels
+}
+
+// Going off one edge of the maze comes back on the other
+fn wrap_around(pos: &mut (f32, f32), level_size: (f32, f32)) {
+    let (width, height) = level_size;
+    let radius = TILE_SIZE / 2.0;
+    if pos.0 < -TILE_SIZE {
+        pos.0 = width + radius;
+    } else if pos.0 > width + TILE_SIZE {
+        pos.0 = -radius;
+    } else if pos.1 < -TILE_SIZE {
+        pos.1 = height + radius;
+    } else if pos.1 > height + TILE_SIZE {
+        pos.1 = -radius;
+    }
+}
+
+fn update_pacman_mouth_angle(pacman: &mut Pacman, pacman_mouth_speed: f32) {
+    if pacman.mouth_opening {
+        pacman.mouth_angle += pacman_mouth_speed;
+        if pacman.mouth_angle >= 70.0 {
+            pacman.mouth_angle = 70.0;
+            pacman.mouth_opening = false;
+        }
+    } else {
+        pacman.mouth_angle -= pacman_mouth_speed;
+        if pacman.mouth_angle <= 5.0 {
+            pacman.mouth_angle = 5.0;
+            pacman.mouth_opening = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    const LEFT: (f32, f32) = (-1.0, 0.0);
+    const DOWN: (f32, f32) = (0.0, 1.0);
+
+    // Pac-Man heading left from his start along the bottom corridor, where
+    // the first way down is at column 9
+    fn heading_left() -> Game {
+        let mut game = Game::new(parse_level(CLASSIC_LEVEL).unwrap(), 1);
+        game.phase = Phase::Playing;
+        game.tick(Some(LEFT));
+        assert_eq!(game.pacman.direction, LEFT);
+        game
+    }
+
+    // Runs until Pac-Man reaches the centre of the column
+    fn run_to_column(game: &mut Game, col: i32) {
+        for _ in 0..TICKS_PER_SECOND * 10 {
+            if game.pacman.pos.0 <= tile_centre((col, 0)).0 {
+                return;
+            }
+            game.tick(None);
+        }
+        panic!("Pac-Man never got to column {}", col);
+    }
+
+    #[test]
+    fn a_turn_pressed_early_is_taken() {
+        let mut game = heading_left();
+        run_to_column(&mut game, 11);
+        game.tick(Some(DOWN));
+        for _ in 0..TURN_BUFFER_TICKS {
+            game.tick(None);
+        }
+        assert_eq!(game.pacman.direction, DOWN);
+        assert_eq!(tile_of(game.pacman.pos).0, 9);
+        assert_eq!(game.pacman.turn_buffer, None);
+    }
+
+    #[test]
+    fn a_turn_pressed_too_early_is_forgotten() {
+        let mut game = heading_left();
+        run_to_column(&mut game, 12);
+        game.tick(Some(DOWN));
+        for _ in 0..TURN_BUFFER_TICKS {
+            game.tick(None);
+        }
+        assert_eq!(game.pacman.turn_buffer, None);
+        run_to_column(&mut game, 8);
+        assert_eq!(game.pacman.direction, LEFT);
+    }
+
+    #[test]
+    fn a_held_turn_waits_for_the_opening() {
+        let mut game = heading_left();
+        run_to_column(&mut game, 13);
+        for _ in 0..4 * TURN_BUFFER_TICKS {
+            game.tick(Some(DOWN));
+        }
+        assert_eq!(game.pacman.direction, DOWN);
+        assert_eq!(tile_of(game.pacman.pos).0, 9);
+    }
+
+    #[test]
+    fn reversing_is_immediate() {
+        let mut game = heading_left();
+        run_to_column(&mut game, 12);
+        game.tick(Some((1.0, 0.0)));
+        assert_eq!(game.pacman.direction, (1.0, 0.0));
+    }
+}
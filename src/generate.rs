@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::level::{parse_level, Level};
+use crate::rng::Rng;
+use crate::validate::validate_level;
+
+// The arcade's pellet count, 240 dots and 4 energizers
+pub const DEFAULT_PELLETS: usize = 244;
+
+// Generated mazes are the arcade's size, so a window opened for one fits
+// them all. Corridors run along these rows and columns, where the classic
+// maze has them, and meet at the crossings.
+const WIDTH: usize = 28;
+const HEIGHT: usize = 31;
+const COLUMNS: [usize; 8] = [1, 6, 9, 12, 15, 18, 21, 26];
+const ROWS: [usize; 10] = [1, 5, 8, 11, 14, 17, 20, 23, 26, 29];
+
+// The ghost house, as in the classic maze, with its door in the top wall
+const HOUSE: &str = "\
+###--###
+#      #
+#3 2 4 #
+#      #
+########";
+const HOUSE_SIZE: (usize, usize) = (8, 5);
+const HOUSE_CORNER: (usize, usize) = (10, 12);
+const BLINKY_START: (usize, usize) = (13, 11);
+const FRUIT_TILE: (usize, usize) = (13, 17);
+const PACMAN_START: (usize, usize) = (13, 23);
+
+// The corridors around the house and under Pac-Man's start always stay
+const KEPT: [((usize, usize), (usize, usize)); 7] = [
+    ((9, 11), (12, 11)),
+    ((12, 11), (15, 11)),
+    ((9, 11), (9, 14)),
+    ((9, 14), (9, 17)),
+    ((9, 17), (12, 17)),
+    ((12, 17), (15, 17)),
+    ((12, 23), (15, 23)),
+];
+const TUNNEL_ROWS: [usize; 5] = [8, 11, 14, 17, 20]; // Where tunnels can go
+const TUNNEL_END: usize = 6; // The column a left-hand tunnel leads to
+
+// No pellets around the ghost house, where the arcade has none
+const BARE_COLUMNS: std::ops::RangeInclusive<usize> = 7..=20;
+const BARE_ROWS: std::ops::RangeInclusive<usize> = 9..=19;
+
+const ATTEMPTS: u32 = 100; // Mazes tried before giving up
+
+type Node = (usize, usize);
+type Edge = (Node, Node);
+
+// Makes an arcade-style maze from `seed`: the same both sides of the middle,
+// with no dead ends, the ghost house in the centre, one or two tunnels and
+// as close to `pellets` pellets as the corridors allow. The same seed always
+// makes the same maze, and every maze made passes validate_level.
+pub fn generate_maze(seed: u64, pellets: usize) -> Result<Level, String> {
+    let mut rng = Rng::new(seed);
+    for _ in 0..ATTEMPTS {
+        let level = parse_level(&maze_text(&mut rng, pellets))?;
+        if validate_level(&level).is_empty() {
+            return Ok(level);
+        }
+    }
+    Err(format!("Couldn't make a maze from seed {}", seed))
+}
+
+fn maze_text(rng: &mut Rng, target_pellets: usize) -> String {
+    let tunnel_count = 1 + rng.below(2);
+    let mut tunnel_rows = Vec::new();
+    while tunnel_rows.len() < tunnel_count {
+        let row = TUNNEL_ROWS[rng.below(TUNNEL_ROWS.len())];
+        if !tunnel_rows.contains(&row) {
+            tunnel_rows.push(row);
+        }
+    }
+    let mut maze = Lattice::new(&tunnel_rows);
+
+    // Knock out corridors, each with its mirror image, as long as that
+    // leaves no dead ends and everything joined up, until there are few
+    // enough pellets
+    let mut removable: Vec<Vec<Edge>> = maze
+        .pairs()
+        .into_iter()
+        .filter(|pair| pair.iter().all(|edge| !KEPT.contains(edge)))
+        .collect();
+    for i in (1..removable.len()).rev() {
+        removable.swap(i, rng.below(i + 1));
+    }
+    for pair in removable {
+        if maze.pellet_count() <= target_pellets {
+            break;
+        }
+        for edge in &pair {
+            maze.edges.remove(edge);
+        }
+        if !maze.is_sound() {
+            maze.edges.extend(pair);
+        }
+    }
+    maze.to_text()
+}
+
+// Corridors between neighbouring crossings, and the tunnels. Every
+// corridor is straight, so the maze is drawn by opening the tiles between
+// the ends of each one.
+struct Lattice {
+    edges: HashSet<Edge>,
+    tunnel_rows: Vec<usize>,
+}
+
+impl Lattice {
+    // Every corridor there could be, leaving out the ghost house and the
+    // crossings a tunnel passes through
+    fn new(tunnel_rows: &[usize]) -> Lattice {
+        let house = |(x, y): Node| {
+            (HOUSE_CORNER.0..HOUSE_CORNER.0 + HOUSE_SIZE.0).contains(&x)
+                && (HOUSE_CORNER.1..HOUSE_CORNER.1 + HOUSE_SIZE.1).contains(&y)
+        };
+        let usable = |node: Node| !house(node) && !in_tunnel(tunnel_rows, node);
+
+        let mut edges = HashSet::new();
+        for &y in &ROWS {
+            for x in COLUMNS.windows(2) {
+                let edge = ((x[0], y), (x[1], y));
+                if usable(edge.0) && usable(edge.1) {
+                    edges.insert(edge);
+                }
+            }
+        }
+        for &x in &COLUMNS {
+            for y in ROWS.windows(2) {
+                let edge = ((x, y[0]), (x, y[1]));
+                // A corridor down past the house would go through it
+                let through_house = (y[0]..=y[1]).any(|row| house((x, row)));
+                if usable(edge.0) && usable(edge.1) && !through_house {
+                    edges.insert(edge);
+                }
+            }
+        }
+        Lattice {
+            edges,
+            tunnel_rows: tunnel_rows.to_vec(),
+        }
+    }
+
+    // The corridors grouped with their mirror images, in a fixed order so
+    // the same seed always makes the same maze
+    fn pairs(&self) -> Vec<Vec<Edge>> {
+        let mut edges: Vec<Edge> = self.edges.iter().copied().collect();
+        edges.sort();
+        let mut pairs: Vec<Vec<Edge>> = Vec::new();
+        for edge in edges {
+            let mirror = mirror_edge(edge);
+            if pairs.iter().any(|pair| pair.contains(&edge)) {
+                continue;
+            }
+            if mirror == edge {
+                pairs.push(vec![edge]);
+            } else {
+                pairs.push(vec![edge, mirror]);
+            }
+        }
+        pairs
+    }
+
+    // The crossings each crossing is joined to, through tunnels too
+    fn links(&self) -> HashMap<Node, Vec<Node>> {
+        let mut links: HashMap<Node, Vec<Node>> = HashMap::new();
+        let tunnels = self.tunnel_rows.iter().map(|&y| {
+            let end = (TUNNEL_END, y);
+            (end, mirror(end))
+        });
+        for (a, b) in self.edges.iter().copied().chain(tunnels) {
+            links.entry(a).or_default().push(b);
+            links.entry(b).or_default().push(a);
+        }
+        links
+    }
+
+    // No crossing with only one way out, and every corridor can be reached
+    // from every other
+    fn is_sound(&self) -> bool {
+        let links = self.links();
+        if links.values().any(|joined| joined.len() == 1) {
+            return false;
+        }
+        let Some(&start) = links.keys().min() else {
+            return false;
+        };
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &next in &links[&node] {
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen.len() == links.len()
+    }
+
+    fn open_tiles(&self) -> HashSet<Node> {
+        let mut open = HashSet::new();
+        for &((x0, y0), (x1, y1)) in &self.edges {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    open.insert((x, y));
+                }
+            }
+        }
+        for &y in &self.tunnel_rows {
+            for x in 0..TUNNEL_END {
+                open.insert((x, y));
+                open.insert(mirror((x, y)));
+            }
+        }
+        open
+    }
+
+    fn has_pellet(&self, (x, y): Node) -> bool {
+        let bare = BARE_COLUMNS.contains(&x) && BARE_ROWS.contains(&y);
+        let tunnel = in_tunnel(&self.tunnel_rows, (x, y));
+        let start = y == PACMAN_START.1 && (x == PACMAN_START.0 || x == mirror(PACMAN_START).0);
+        !bare && !tunnel && !start
+    }
+
+    fn pellet_count(&self) -> usize {
+        self.open_tiles()
+            .into_iter()
+            .filter(|&tile| self.has_pellet(tile))
+            .count()
+    }
+
+    fn to_text(&self) -> String {
+        let mut rows = vec![vec!['#'; WIDTH]; HEIGHT];
+        let open = self.open_tiles();
+        for &(x, y) in &open {
+            rows[y][x] = if self.has_pellet((x, y)) { '.' } else { ' ' };
+        }
+
+        // Energizers on the pellets nearest the arcade's four
+        let mut pellets: Vec<Node> = open
+            .iter()
+            .copied()
+            .filter(|&tile| self.has_pellet(tile) && tile.0 < WIDTH / 2)
+            .collect();
+        pellets.sort();
+        for corner in [(1, 3), (1, 23)] {
+            let nearest = pellets
+                .iter()
+                .copied()
+                .min_by_key(|&(x, y)| x.abs_diff(corner.0) + y.abs_diff(corner.1));
+            if let Some(tile) = nearest {
+                let mirrored = mirror(tile);
+                rows[tile.1][tile.0] = 'o';
+                rows[mirrored.1][mirrored.0] = 'o';
+            }
+        }
+
+        for (dy, line) in HOUSE.lines().enumerate() {
+            for (dx, c) in line.chars().enumerate() {
+                rows[HOUSE_CORNER.1 + dy][HOUSE_CORNER.0 + dx] = c;
+            }
+        }
+        rows[BLINKY_START.1][BLINKY_START.0] = '1';
+        rows[FRUIT_TILE.1][FRUIT_TILE.0] = 'F';
+        rows[PACMAN_START.1][PACMAN_START.0] = 'P';
+
+        let mut text = String::new();
+        for row in rows {
+            text.extend(row);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// Tunnels run in from both edges as far as TUNNEL_END
+fn in_tunnel(tunnel_rows: &[usize], (x, y): Node) -> bool {
+    tunnel_rows.contains(&y) && !(TUNNEL_END..=WIDTH - 1 - TUNNEL_END).contains(&x)
+}
+
+fn mirror((x, y): Node) -> Node {
+    (WIDTH - 1 - x, y)
+}
+
+fn mirror_edge((a, b): Edge) -> Edge {
+    let (a, b) = (mirror(a), mirror(b));
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_seed_makes_a_valid_maze() {
+        for seed in 0..300 {
+            let level = generate_maze(seed, DEFAULT_PELLETS)
+                .unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            assert!(validate_level(&level).is_empty(), "seed {}", seed);
+            assert_eq!((level.width(), level.height()), (WIDTH, HEIGHT));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_maze() {
+        for seed in [0, 7, 1234] {
+            let first = generate_maze(seed, DEFAULT_PELLETS).unwrap();
+            let second = generate_maze(seed, DEFAULT_PELLETS).unwrap();
+            assert_eq!(first.to_text(), second.to_text());
+        }
+        let a = generate_maze(1, DEFAULT_PELLETS).unwrap();
+        let b = generate_maze(2, DEFAULT_PELLETS).unwrap();
+        assert_ne!(a.to_text(), b.to_text());
+    }
+
+    #[test]
+    fn mazes_are_mirrored() {
+        for seed in 0..20 {
+            let level = generate_maze(seed, DEFAULT_PELLETS).unwrap();
+            for row in &level.tiles {
+                for (x, tile) in row.iter().enumerate() {
+                    assert_eq!(tile.is_wall(), row[WIDTH - 1 - x].is_wall());
+                }
+            }
+            for &(x, y) in level.pellets.keys() {
+                assert!(level.pellets.contains_key(&(WIDTH - 1 - x, y)));
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+use crate::events::{EventListener, GameEvent};
+use crate::game::{tile_of, Game, GhostState, Mazes, Phase, TILE_SIZE};
+use crate::level::{Level, Pellet};
+use crate::maze::MazeTile;
+use crate::render::fruit_color;
+use crate::theme::Theme;
+
+// The planes of the tile observation, one value per tile each
+pub const CHANNELS: [&str; 9] = [
+    "wall",
+    "door",
+    "dot",
+    "energizer",
+    "fruit",
+    "pacman",
+    "ghost",
+    "frightened_ghost",
+    "eyes",
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Noop, // Keep going
+    Up,
+    Left,
+    Down,
+    Right,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Noop,
+        Action::Up,
+        Action::Left,
+        Action::Down,
+        Action::Right,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Noop => "noop",
+            Action::Up => "up",
+            Action::Left => "left",
+            Action::Down => "down",
+            Action::Right => "right",
+        }
+    }
+
+    fn direction(self) -> Option<(f32, f32)> {
+        match self {
+            Action::Noop => None,
+            Action::Up => Some((0.0, -1.0)),
+            Action::Left => Some((-1.0, 0.0)),
+            Action::Down => Some((0.0, 1.0)),
+            Action::Right => Some((1.0, 0.0)),
+        }
+    }
+}
+
+// Neither is the picture the player sees: the window's drawing needs SDL
+// and would hold the environment far below the speed training wants. The
+// schematic is a raster with the same layout, in fixed colours.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObservationKind {
+    Tiles,     // channels x rows x columns of 0 or 1, see CHANNELS
+    Schematic, // rows x columns x RGB, flat blocks and discs in the arcade's colours
+}
+
+impl ObservationKind {
+    pub fn from_name(name: &str) -> Result<ObservationKind, String> {
+        match name {
+            "tiles" => Ok(ObservationKind::Tiles),
+            "schematic" => Ok(ObservationKind::Schematic),
+            _ => Err(format!(
+                "Unknown observation '{}', expected tiles or schematic",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ObservationKind::Tiles => "tiles",
+            ObservationKind::Schematic => "schematic",
+        }
+    }
+}
+
+pub struct EnvOptions {
+    pub observation: ObservationKind,
+    pub pixels_per_tile: u32, // Size of the schematic observation
+    pub frame_skip: u32,      // Game ticks per step, the action is held for all of them
+    pub max_ticks: u64,       // The episode is cut off after this many ticks
+    pub death_penalty: f32,   // Taken off the reward when Pac-Man dies
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        EnvOptions {
+            observation: ObservationKind::Tiles,
+            pixels_per_tile: 4,
+            frame_skip: 4,
+            max_ticks: 100_000,
+            death_penalty: 500.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Observation {
+    pub shape: [usize; 3],
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct StepInfo {
+    pub score: u32,
+    pub lives: u32,
+    pub level: u32,
+    pub tick: u64,
+    pub pellets_left: usize,
+    pub truncated: bool, // The episode hit max_ticks rather than ending
+}
+
+#[derive(Serialize)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Default)]
+struct Deaths(u32);
+
+impl EventListener for Deaths {
+    fn on_event(&mut self, _tick: u64, event: &GameEvent) {
+        if let GameEvent::PacmanDied { .. } = event {
+            self.0 += 1;
+        }
+    }
+}
+
+// The game as a reinforcement learning environment. Every step holds an
+// action for `frame_skip` ticks and rewards the points scored, less the
+// death penalty. The ticks in which Pac-Man can't move (the READY! pause,
+// dying, between levels) are skipped, so every step is a real choice.
+// Episodes play through `mazes` as a game would, so with endless mazes the
+// episode's seed also picks them.
+pub struct Env {
+    level: Level,
+    mazes: Mazes,
+    options: EnvOptions,
+    game: Game,
+}
+
+impl Env {
+    pub fn new(level: Level, mazes: Mazes, options: EnvOptions) -> Env {
+        let game = Game::new(level.clone(), 0);
+        Env {
+            level,
+            mazes,
+            options,
+            game,
+        }
+    }
+
+    pub fn reset(&mut self, seed: u64) -> (Observation, StepInfo) {
+        self.game = Game::new(self.level.clone(), seed);
+        self.game.set_mazes(self.mazes.clone(), 1);
+        self.skip_to_play(&mut Deaths::default());
+        (self.observe(), self.info())
+    }
+
+    pub fn step(&mut self, action: Action) -> Step {
+        let score = self.game.score;
+        let mut deaths = Deaths::default();
+        for _ in 0..self.options.frame_skip.max(1) {
+            if self.game.phase != Phase::Playing {
+                break;
+            }
+            self.game.tick(action.direction());
+            self.game.events.dispatch(&mut [&mut deaths]);
+        }
+        self.skip_to_play(&mut deaths);
+
+        let reward =
+            (self.game.score - score) as f32 - deaths.0 as f32 * self.options.death_penalty;
+        let info = self.info();
+        Step {
+            observation: self.observe(),
+            reward,
+            done: self.game.phase == Phase::GameOver || info.truncated,
+            info,
+        }
+    }
+
+    fn skip_to_play(&mut self, deaths: &mut Deaths) {
+        while !matches!(self.game.phase, Phase::Playing | Phase::GameOver)
+            && self.game.tick < self.options.max_ticks
+        {
+            self.game.tick(None);
+            self.game.events.dispatch(&mut [&mut *deaths]);
+        }
+    }
+
+    fn info(&self) -> StepInfo {
+        StepInfo {
+            score: self.game.score,
+            lives: self.game.lives,
+            level: self.game.level_number,
+            tick: self.game.tick,
+            pellets_left: self.game.pellets.len(),
+            truncated: self.game.phase != Phase::GameOver
+                && self.game.tick >= self.options.max_ticks,
+        }
+    }
+
+    pub fn observation_shape(&self) -> [usize; 3] {
+        let (width, height) = (self.level.width(), self.level.height());
+        let scale = self.options.pixels_per_tile.max(1) as usize;
+        match self.options.observation {
+            ObservationKind::Tiles => [CHANNELS.len(), height, width],
+            ObservationKind::Schematic => [height * scale, width * scale, 3],
+        }
+    }
+
+    pub fn observe(&self) -> Observation {
+        match self.options.observation {
+            ObservationKind::Tiles => self.observe_tiles(),
+            ObservationKind::Schematic => self.observe_schematic(),
+        }
+    }
+
+    fn observe_tiles(&self) -> Observation {
+        let shape = self.observation_shape();
+        let [channels, height, width] = shape;
+        let mut data = vec![0u8; channels * height * width];
+        let mut set = |channel: usize, tile: (i32, i32)| {
+            let inside = tile.0 >= 0
+                && tile.1 >= 0
+                && (tile.0 as usize) < width
+                && (tile.1 as usize) < height;
+            if inside {
+                data[(channel * height + tile.1 as usize) * width + tile.0 as usize] = 1;
+            }
+        };
+
+        let game = &self.game;
+        for (row, tiles) in game.level.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                let tile_pos = (col as i32, row as i32);
+                if *tile == MazeTile::Door {
+                    set(1, tile_pos);
+                } else if tile.is_wall() {
+                    set(0, tile_pos);
+                }
+            }
+        }
+        for (&(col, row), pellet) in &game.pellets {
+            let channel = match pellet {
+                Pellet::Dot => 2,
+                Pellet::Energizer => 3,
+            };
+            set(channel, (col as i32, row as i32));
+        }
+        if game.fruit.is_some() {
+            let (col, row) = game.level.fruit_tile;
+            set(4, (col as i32, row as i32));
+        }
+        set(5, tile_of(game.pacman.pos));
+        for ghost in &game.ghosts {
+            let channel = match ghost.state {
+                GhostState::Eaten | GhostState::EnteringHouse => 8,
+                _ if ghost.frightened => 7,
+                _ => 6,
+            };
+            set(channel, tile_of(ghost.pos));
+        }
+        Observation { shape, data }
+    }
+
+    // Blocks for walls, squares for the pellets, discs for the fruit,
+    // Pac-Man and the ghosts. The colours are the arcade's whatever theme
+    // the window uses, so a trained agent sees the same thing everywhere.
+    fn observe_schematic(&self) -> Observation {
+        let shape = self.observation_shape();
+        let [height, width, _] = shape;
+        let scale = self.options.pixels_per_tile.max(1) as f32;
+        let mut frame = Frame {
+            width,
+            height,
+            data: vec![0u8; height * width * 3],
+        };
+
+        let game = &self.game;
+        for (row, tiles) in game.level.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                let color = match tile {
+                    MazeTile::Door => (255, 184, 255),
+                    tile if tile.is_wall() => (0, 0, 255),
+                    _ => continue,
+                };
+                let centre = ((col as f32 + 0.5) * scale, (row as f32 + 0.5) * scale);
+                frame.fill_square(centre, scale / 2.0, color);
+            }
+        }
+        for (&(col, row), pellet) in &game.pellets {
+            let size = match pellet {
+                Pellet::Dot => scale / 8.0,
+                Pellet::Energizer => scale / 3.0,
+            };
+            let centre = ((col as f32 + 0.5) * scale, (row as f32 + 0.5) * scale);
+            frame.fill_square(centre, size.max(0.5), (255, 184, 151));
+        }
+        let to_frame = |pos: (f32, f32)| (pos.0 / TILE_SIZE * scale, pos.1 / TILE_SIZE * scale);
+        if let Some((fruit, _)) = game.fruit {
+            let (col, row) = game.level.fruit_tile;
+            let centre = ((col as f32 + 0.5) * scale, (row as f32 + 0.5) * scale);
+            let color = fruit_color(fruit);
+            frame.fill_disc(centre, scale / 2.0, (color.r, color.g, color.b));
+        }
+        frame.fill_disc(to_frame(game.pacman.pos), scale / 2.0, (255, 255, 0));
+        for ghost in &game.ghosts {
+            let (color, radius) = match ghost.state {
+                GhostState::Eaten | GhostState::EnteringHouse => ((255, 255, 255), scale / 4.0),
+                _ if ghost.frightened && game.frightened_flashing() => {
+                    ((255, 255, 255), scale / 2.0)
+                }
+                _ if ghost.frightened => ((33, 33, 255), scale / 2.0),
+                _ => {
+                    let color = Theme::ARCADE.ghost(ghost.name);
+                    ((color.r, color.g, color.b), scale / 2.0)
+                }
+            };
+            frame.fill_disc(to_frame(ghost.pos), radius, color);
+        }
+        Observation {
+            shape,
+            data: frame.data,
+        }
+    }
+}
+
+// An RGB image to draw the schematic observation into
+struct Frame {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    fn fill(
+        &mut self,
+        centre: (f32, f32),
+        radius: f32,
+        inside: impl Fn(f32, f32) -> bool,
+        color: (u8, u8, u8),
+    ) {
+        let top = (centre.1 - radius).floor().max(0.0) as usize;
+        let left = (centre.0 - radius).floor().max(0.0) as usize;
+        let bottom = ((centre.1 + radius).ceil().max(0.0) as usize).min(self.height);
+        let right = ((centre.0 + radius).ceil().max(0.0) as usize).min(self.width);
+        for y in top..bottom {
+            for x in left..right {
+                // Pixels count as covered when their centre is
+                let dx = x as f32 + 0.5 - centre.0;
+                let dy = y as f32 + 0.5 - centre.1;
+                if inside(dx, dy) {
+                    let i = (y * self.width + x) * 3;
+                    self.data[i..i + 3].copy_from_slice(&[color.0, color.1, color.2]);
+                }
+            }
+        }
+    }
+
+    fn fill_square(&mut self, centre: (f32, f32), half_size: f32, color: (u8, u8, u8)) {
+        self.fill(
+            centre,
+            half_size,
+            |dx, dy| dx.abs() <= half_size && dy.abs() <= half_size,
+            color,
+        );
+    }
+
+    fn fill_disc(&mut self, centre: (f32, f32), radius: f32, color: (u8, u8, u8)) {
+        self.fill(centre, radius, |dx, dy| dx.hypot(dy) <= radius, color);
+    }
+}
+
+// One request per line on stdin:
+//   {"cmd": "spec"}
+//   {"cmd": "reset", "seed": 7}
+//   {"cmd": "step", "action": "left"}   (or the action's index in "actions")
+//   {"cmd": "close"}
+// Each is answered with one line of JSON on stdout, {"error": "..."} if it
+// couldn't be carried out.
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    Spec,
+    Reset {
+        #[serde(default)]
+        seed: u64,
+    },
+    Step {
+        action: ActionArg,
+    },
+    Close,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ActionArg {
+    Index(usize),
+    Name(String),
+}
+
+impl ActionArg {
+    fn action(&self) -> Result<Action, String> {
+        let action = match self {
+            ActionArg::Index(index) => Action::ALL.get(*index).copied(),
+            ActionArg::Name(name) => Action::ALL.into_iter().find(|a| a.name() == name),
+        };
+        action.ok_or_else(|| {
+            let names: Vec<&str> = Action::ALL.iter().map(|a| a.name()).collect();
+            format!("Unknown action, expected one of {}", names.join(", "))
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Spec {
+    actions: Vec<&'static str>,
+    observation: &'static str,
+    observation_shape: [usize; 3],
+    channels: Option<[&'static str; 9]>,
+    frame_skip: u32,
+    max_ticks: u64,
+}
+
+#[derive(Serialize)]
+struct ResetReply {
+    observation: Observation,
+    info: StepInfo,
+}
+
+// Serves the environment over stdin and stdout until "close" or the end of
+// the input, so a trainer in any language can drive it as a child process
+pub fn serve_stdio(level: Level, mazes: Mazes, options: EnvOptions) -> Result<(), String> {
+    let mut env = Env::new(level, mazes, options);
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                write_reply(&mut stdout, &serde_json::json!({ "error": e.to_string() }))?;
+                continue;
+            }
+        };
+        match request {
+            Request::Spec => {
+                let spec = Spec {
+                    actions: Action::ALL.iter().map(|a| a.name()).collect(),
+                    observation: env.options.observation.name(),
+                    observation_shape: env.observation_shape(),
+                    channels: (env.options.observation == ObservationKind::Tiles)
+                        .then_some(CHANNELS),
+                    frame_skip: env.options.frame_skip,
+                    max_ticks: env.options.max_ticks,
+                };
+                write_reply(&mut stdout, &spec)?;
+            }
+            Request::Reset { seed } => {
+                let (observation, info) = env.reset(seed);
+                write_reply(&mut stdout, &ResetReply { observation, info })?;
+            }
+            Request::Step { action } => match action.action() {
+                Ok(action) => write_reply(&mut stdout, &env.step(action))?,
+                Err(e) => write_reply(&mut stdout, &serde_json::json!({ "error": e }))?,
+            },
+            Request::Close => break,
+        }
+    }
+    Ok(())
+}
+
+fn write_reply(out: &mut impl Write, reply: &impl Serialize) -> Result<(), String> {
+    serde_json::to_writer(&mut *out, reply).map_err(|e| e.to_string())?;
+    out.write_all(b"\n").map_err(|e| e.to_string())?;
+    out.flush().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{parse_level, Pellet, CLASSIC_LEVEL};
+
+    fn env(options: EnvOptions) -> Env {
+        Env::new(parse_level(CLASSIC_LEVEL).unwrap(), Mazes::Fixed, options)
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_episode() {
+        let actions = [Action::Left, Action::Up, Action::Right, Action::Down];
+        let mut first = env(EnvOptions::default());
+        let mut second = env(EnvOptions::default());
+        assert_eq!(first.reset(7).0.data, second.reset(7).0.data);
+        for i in 0..200 {
+            let action = actions[i / 10 % actions.len()];
+            let (a, b) = (first.step(action), second.step(action));
+            assert_eq!(a.observation.data, b.observation.data);
+            assert_eq!(a.reward, b.reward);
+            assert_eq!(a.done, b.done);
+        }
+    }
+
+    #[test]
+    fn every_step_holds_the_action_for_frame_skip_ticks() {
+        let mut env = env(EnvOptions {
+            frame_skip: 3,
+            ..EnvOptions::default()
+        });
+        let (_, info) = env.reset(1);
+        let step = env.step(Action::Left);
+        assert_eq!(step.info.tick, info.tick + 3);
+    }
+
+    #[test]
+    fn dying_costs_the_penalty_and_the_last_life_ends_the_episode() {
+        let mut env = env(EnvOptions::default());
+        let (_, info) = env.reset(1);
+        let mut lives = info.lives;
+        let mut deaths = 0;
+        for _ in 0..20_000 {
+            let step = env.step(Action::Noop);
+            if step.info.lives < lives {
+                assert!(step.reward < 0.0);
+                lives = step.info.lives;
+                deaths += 1;
+            }
+            if step.done {
+                assert!(!step.info.truncated);
+                assert_eq!(env.game.phase, Phase::GameOver);
+                assert!(deaths > 0);
+                return;
+            }
+        }
+        panic!("Pac-Man never lost his last life");
+    }
+
+    #[test]
+    fn clearing_a_level_scores_and_plays_on() {
+        let mut env = env(EnvOptions::default());
+        env.reset(1);
+        let (x, y) = env.game.level.pacman_start;
+        env.game.pellets = [((x - 1, y), Pellet::Dot)].into_iter().collect();
+        for _ in 0..20 {
+            let step = env.step(Action::Left);
+            if step.info.level == 2 {
+                assert!(step.reward > 0.0);
+                assert!(!step.done);
+                assert!(step.info.pellets_left > 0);
+                assert_eq!(env.game.phase, Phase::Playing);
+                return;
+            }
+            assert_eq!(step.reward, 0.0);
+        }
+        panic!("the level was never cleared");
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::Game;
+
+pub const HIGH_SCORES_PATH: &str = "./high_scores.toml";
+const TABLE_SIZE: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScore {
+    pub score: u32,
+    pub level: u32,
+    #[serde(default)]
+    pub assists: Vec<String>, // Those the game was played with, none for an unassisted run
+}
+
+impl HighScore {
+    pub fn assisted(&self) -> bool {
+        !self.assists.is_empty()
+    }
+}
+
+// The best finished games, highest first, kept in ./high_scores.toml
+#[derive(Serialize, Deserialize, Default)]
+pub struct HighScores {
+    #[serde(default)]
+    entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    // No scores yet if the file isn't there or can't be read
+    pub fn load(path: &str) -> HighScores {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return HighScores::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Could not read {}, starting afresh: {}", path, e);
+            HighScores::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    // Adds a finished game if it makes the table, returning whether it did.
+    // Ties go below the scores already there. Games the console or playback
+    // controls were used on are left out.
+    pub fn record(&mut self, game: &Game) -> bool {
+        if game.score == 0 || game.dev_tools_used {
+            return false;
+        }
+        let place = self
+            .entries
+            .iter()
+            .position(|entry| entry.score < game.score)
+            .unwrap_or(self.entries.len());
+        if place >= TABLE_SIZE {
+            return false;
+        }
+        self.entries.insert(
+            place,
+            HighScore {
+                score: game.score,
+                level: game.level_number,
+                assists: game.assists.labels(),
+            },
+        );
+        self.entries.truncate(TABLE_SIZE);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assists::Assists;
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    fn finished(score: u32) -> Game {
+        let mut game = Game::new(parse_level(CLASSIC_LEVEL).unwrap(), 1);
+        game.score = score;
+        game
+    }
+
+    fn scores(table: &HighScores) -> Vec<u32> {
+        table.entries().iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn scores_go_in_order() {
+        let mut table = HighScores::default();
+        for score in [300, 100, 200, 200] {
+            assert!(table.record(&finished(score)));
+        }
+        assert_eq!(scores(&table), vec![300, 200, 200, 100]);
+    }
+
+    #[test]
+    fn only_the_best_are_kept() {
+        let mut table = HighScores::default();
+        for score in 1..=TABLE_SIZE as u32 {
+            assert!(table.record(&finished(score * 10)));
+        }
+        assert!(!table.record(&finished(5)));
+        assert!(!table.record(&finished(10)));
+        assert!(table.record(&finished(15)));
+        assert_eq!(table.entries().len(), TABLE_SIZE);
+        assert_eq!(table.entries().last().unwrap().score, 15);
+    }
+
+    #[test]
+    fn nothing_scored_isnt_recorded() {
+        let mut table = HighScores::default();
+        assert!(!table.record(&finished(0)));
+        assert!(table.entries().is_empty());
+    }
+
+    #[test]
+    fn assisted_games_are_marked() {
+        let mut table = HighScores::default();
+        let mut game = finished(500);
+        game.set_assists(Assists {
+            tourist_mode: true,
+            ..Assists::default()
+        });
+        table.record(&game);
+        table.record(&finished(400));
+        let entries = table.entries();
+        assert!(entries[0].assisted());
+        assert_eq!(entries[0].assists, vec!["TOURIST".to_string()]);
+        assert!(!entries[1].assisted());
+    }
+
+    #[test]
+    fn games_with_dev_tools_are_left_out() {
+        let mut table = HighScores::default();
+        let mut game = finished(500);
+        game.dev_tools_used = true;
+        assert!(!table.record(&game));
+        assert!(table.entries().is_empty());
+    }
+
+    #[test]
+    fn the_table_survives_saving() {
+        let mut table = HighScores::default();
+        let mut game = finished(700);
+        game.set_assists(Assists {
+            extra_lives: 2,
+            ..Assists::default()
+        });
+        table.record(&game);
+        table.record(&finished(300));
+        let text = toml::to_string(&table).unwrap();
+        let loaded: HighScores = toml::from_str(&text).unwrap();
+        assert_eq!(scores(&loaded), vec![700, 300]);
+        assert!(loaded.entries()[0].assisted());
+        assert!(!loaded.entries()[1].assisted());
+    }
+}
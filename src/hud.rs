@@ -0,0 +1,140 @@
+use sdl2::pixels::Color;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+
+use crate::display::{draw_text, draw_text_centered};
+use crate::events::{EventListener, GameEvent};
+use crate::game::{tile_centre, Game, TICKS_PER_SECOND};
+use crate::render::{draw_fruit, draw_pacman};
+use crate::sprites::SpriteAtlas;
+use crate::theme::Theme;
+
+// Rows of tiles above and below the maze for the score, lives and fruit
+pub const HUD_TOP_ROWS: u32 = 2;
+pub const HUD_BOTTOM_ROWS: u32 = 2;
+
+const POPUP_TICKS: u64 = TICKS_PER_SECOND as u64;
+
+// Score along the top, spare lives and the level's fruit along the bottom.
+// Drawn over the whole playfield, `maze_height` being the height of the
+// maze between the two strips.
+pub fn draw_hud(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    game: &Game,
+    tile_size: f32,
+    maze_height: i32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    theme: &Theme,
+) -> Result<(), String> {
+    let width = canvas.viewport().width() as i32;
+
+    draw_text(
+        canvas,
+        font,
+        &format!("SCORE {}", game.score),
+        (10, 10),
+        theme.hud,
+    )?;
+    draw_text_centered(
+        canvas,
+        font,
+        &format!("LEVEL {}", game.level_number),
+        (width / 2, (tile_size * HUD_TOP_ROWS as f32 / 2.0) as i32),
+        theme.hud,
+    )?;
+    // A reminder that this run will be marked in the high scores
+    if game.assists.any() {
+        draw_text_centered(
+            canvas,
+            font,
+            "ASSISTS",
+            (
+                width * 5 / 6,
+                (tile_size * HUD_TOP_ROWS as f32 / 2.0) as i32,
+            ),
+            theme.hud,
+        )?;
+    }
+
+    // The life being played isn't shown
+    let bottom = (tile_size * HUD_TOP_ROWS as f32) as i32 + maze_height;
+    let icon_y = bottom as f32 + tile_size * HUD_BOTTOM_ROWS as f32 / 2.0;
+    for life in 1..game.lives {
+        let x = tile_size * (life as f32 * 1.5);
+        draw_pacman(
+            canvas,
+            (x, icon_y),
+            tile_size / 2.0,
+            60.0,
+            (-1.0, 0.0),
+            theme.pacman,
+        )?;
+    }
+
+    // The fruit of this level and the ones before it, newest on the right
+    let first_level = game.level_number.saturating_sub(6).max(1);
+    for (i, level) in (first_level..=game.level_number).rev().enumerate() {
+        let x = width as f32 - tile_size * (i as f32 * 1.5 + 1.5);
+        draw_fruit(
+            canvas,
+            game.fruit_for(level),
+            (x, icon_y),
+            tile_size,
+            sprite_atlas,
+        )?;
+    }
+    Ok(())
+}
+
+struct Popup {
+    text: String,
+    tile: (usize, usize),
+    color: Color,
+    until_tick: u64,
+}
+
+// Points shown for a moment where a ghost or a fruit was eaten
+#[derive(Default)]
+pub struct ScorePopups {
+    popups: Vec<Popup>,
+}
+
+impl ScorePopups {
+    // Drawn in maze coordinates
+    pub fn draw(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        tick: u64,
+    ) -> Result<(), String> {
+        self.popups.retain(|popup| popup.until_tick > tick);
+        for popup in &self.popups {
+            let center = tile_centre((popup.tile.0 as i32, popup.tile.1 as i32));
+            draw_text_centered(
+                canvas,
+                font,
+                &popup.text,
+                (center.0 as i32, center.1 as i32),
+                popup.color,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl EventListener for ScorePopups {
+    fn on_event(&mut self, tick: u64, event: &GameEvent) {
+        let (tile, points, color) = match *event {
+            GameEvent::GhostEaten { tile, points, .. } => (tile, points, Color::RGB(0, 255, 255)),
+            GameEvent::FruitEaten { tile, points, .. } => (tile, points, Color::RGB(255, 184, 255)),
+            _ => return,
+        };
+        self.popups.push(Popup {
+            text: points.to_string(),
+            tile,
+            color,
+            until_tick: tick + POPUP_TICKS,
+        });
+    }
+}
@@ -0,0 +1,692 @@
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::keyboard::{KeyboardState, Keycode, Mod, Scancode};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+// Everything the player can bind a key to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Start,
+    ToggleGrid,
+    ToggleScent,
+    ToggleTargets,
+    ToggleTileCentres,
+    ToggleCollisions,
+    ToggleTimings,
+    Freeze,
+    Step,
+    Slower,
+    Faster,
+    Rewind,
+    ToggleFullscreen,
+    ToggleScaling,
+    ToggleScanlines,
+    ToggleCurvature,
+    ToggleGlow,
+    ControlsMenu,
+    Console,
+    Editor,
+    LevelSelect,
+}
+
+impl Action {
+    // In the order the controls menu lists them
+    pub const ALL: [Action; 26] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Pause,
+        Action::Start,
+        Action::ToggleGrid,
+        Action::ToggleScent,
+        Action::ToggleTargets,
+        Action::ToggleTileCentres,
+        Action::ToggleCollisions,
+        Action::ToggleTimings,
+        Action::Freeze,
+        Action::Step,
+        Action::Slower,
+        Action::Faster,
+        Action::Rewind,
+        Action::ToggleFullscreen,
+        Action::ToggleScaling,
+        Action::ToggleScanlines,
+        Action::ToggleCurvature,
+        Action::ToggleGlow,
+        Action::ControlsMenu,
+        Action::Console,
+        Action::Editor,
+        Action::LevelSelect,
+    ];
+
+    // Key in the [controls] table of config.toml
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Pause => "pause",
+            Action::Start => "start",
+            Action::ToggleGrid => "toggle_grid",
+            Action::ToggleScent => "toggle_scent",
+            Action::ToggleTargets => "toggle_targets",
+            Action::ToggleTileCentres => "toggle_tile_centres",
+            Action::ToggleCollisions => "toggle_collisions",
+            Action::ToggleTimings => "toggle_timings",
+            Action::Freeze => "freeze",
+            Action::Step => "step",
+            Action::Slower => "slower",
+            Action::Faster => "faster",
+            Action::Rewind => "rewind",
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::ToggleScaling => "toggle_scaling",
+            Action::ToggleScanlines => "toggle_scanlines",
+            Action::ToggleCurvature => "toggle_curvature",
+            Action::ToggleGlow => "toggle_glow",
+            Action::ControlsMenu => "controls_menu",
+            Action::Console => "console",
+            Action::Editor => "editor",
+            Action::LevelSelect => "level_select",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Pause => "Pause",
+            Action::Start => "Start",
+            Action::ToggleGrid => "Debug: grid",
+            Action::ToggleScent => "Debug: scent",
+            Action::ToggleTargets => "Debug: ghost targets",
+            Action::ToggleTileCentres => "Debug: tile centres",
+            Action::ToggleCollisions => "Debug: collisions",
+            Action::ToggleTimings => "Debug: timings",
+            Action::Freeze => "Debug: freeze",
+            Action::Step => "Debug: step one tick",
+            Action::Slower => "Debug: slower",
+            Action::Faster => "Debug: faster",
+            Action::Rewind => "Debug: rewind (hold)",
+            Action::ToggleFullscreen => "Fullscreen",
+            Action::ToggleScaling => "Scaling mode",
+            Action::ToggleScanlines => "Scanlines",
+            Action::ToggleCurvature => "Curvature",
+            Action::ToggleGlow => "Glow",
+            Action::ControlsMenu => "Controls menu",
+            Action::Console => "Console",
+            Action::Editor => "Maze editor",
+            Action::LevelSelect => "Level select",
+        }
+    }
+
+    fn default_bindings(self) -> Vec<Binding> {
+        let keys: &[&str] = match self {
+            Action::Up => &["W", "Up", "Pad:dpup", "Pad:lefty-"],
+            Action::Down => &["S", "Down", "Pad:dpdown", "Pad:lefty+"],
+            Action::Left => &["A", "Left", "Pad:dpleft", "Pad:leftx-"],
+            Action::Right => &["D", "Right", "Pad:dpright", "Pad:leftx+"],
+            Action::Pause => &["P", "Pad:start"],
+            Action::Start => &["Return", "Pad:a"],
+            Action::ToggleGrid => &["F1"],
+            Action::ToggleScent => &["F2"],
+            Action::ToggleTargets => &["F3"],
+            Action::ToggleTileCentres => &["F4"],
+            Action::ToggleCollisions => &["F8"],
+            Action::ToggleTimings => &["F10"],
+            Action::Freeze => &["Shift+P"],
+            Action::Step => &["."],
+            Action::Slower => &["["],
+            Action::Faster => &["]"],
+            Action::Rewind => &["Backspace"],
+            Action::ToggleFullscreen => &["F11", "Alt+Return"],
+            Action::ToggleScaling => &["F9"],
+            Action::ToggleScanlines => &["F5"],
+            Action::ToggleCurvature => &["F6"],
+            Action::ToggleGlow => &["F7"],
+            Action::ControlsMenu => &["F12"],
+            Action::Console => &["`"],
+            Action::Editor => &["Ctrl+E"],
+            Action::LevelSelect => &["L"],
+        };
+        keys.iter()
+            .map(|key| Binding::parse(key).expect("default bindings use valid key names"))
+            .collect()
+    }
+}
+
+// Something that can trigger an action. In the config file keys are
+// written as "Ctrl+Alt+Shift+Key", controller buttons as "Pad:<button>" and
+// stick directions as "Pad:<axis>+" or "Pad:<axis>-", using SDL's names
+// ("Pad:dpup", "Pad:start", "Pad:leftx-").
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key {
+        key: Keycode,
+        ctrl: bool,
+        alt: bool,
+        shift: bool,
+    },
+    Button(Button),
+    Stick(Axis, bool), // The bool is true for the positive direction
+}
+
+// What is currently held down on the keyboard and every connected controller
+pub struct HeldInputs<'a> {
+    pub keyboard: KeyboardState<'a>,
+    pub controllers: &'a [GameController],
+    pub stick_deadzone: i16,
+}
+
+impl Binding {
+    pub fn parse(text: &str) -> Result<Binding, String> {
+        let text = text.trim();
+        if let Some(name) = text.strip_prefix("Pad:") {
+            if let Some(button) = Button::from_string(name) {
+                return Ok(Binding::Button(button));
+            }
+            let stick = match name.strip_suffix('+') {
+                Some(axis) => Some((axis, true)),
+                None => name.strip_suffix('-').map(|axis| (axis, false)),
+            };
+            return match stick
+                .and_then(|(axis, positive)| Some((Axis::from_string(axis)?, positive)))
+            {
+                Some((axis, positive)) => Ok(Binding::Stick(axis, positive)),
+                None => Err(format!("Unknown controller input '{}'", text)),
+            };
+        }
+
+        let (mut ctrl, mut alt, mut shift) = (false, false, false);
+        // Only strip known modifiers, since some key names contain a '+'
+        let mut key_name = text;
+        loop {
+            let lower = key_name.to_ascii_lowercase();
+            if lower.starts_with("ctrl+") {
+                ctrl = true;
+                key_name = &key_name[5..];
+            } else if lower.starts_with("alt+") {
+                alt = true;
+                key_name = &key_name[4..];
+            } else if lower.starts_with("shift+") {
+                shift = true;
+                key_name = &key_name[6..];
+            } else {
+                break;
+            }
+        }
+        let key = Keycode::from_name(key_name)
+            .ok_or_else(|| format!("Unknown key '{}' in '{}'", key_name, text))?;
+        Ok(Binding::Key {
+            key,
+            ctrl,
+            alt,
+            shift,
+        })
+    }
+
+    pub fn from_key_press(key: Keycode, keymod: Mod) -> Binding {
+        Binding::Key {
+            key,
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        }
+    }
+
+    // Held keys only need the binding's own modifiers, so holding Shift
+    // doesn't stop Pac-Man
+    fn is_held(&self, held: &HeldInputs) -> bool {
+        match *self {
+            Binding::Key {
+                key,
+                ctrl,
+                alt,
+                shift,
+            } => {
+                let keyboard = &held.keyboard;
+                let any_held = |scancodes: [Scancode; 2]| {
+                    scancodes
+                        .iter()
+                        .any(|&scancode| keyboard.is_scancode_pressed(scancode))
+                };
+                Scancode::from_keycode(key).is_some_and(|s| keyboard.is_scancode_pressed(s))
+                    && (!ctrl || any_held([Scancode::LCtrl, Scancode::RCtrl]))
+                    && (!alt || any_held([Scancode::LAlt, Scancode::RAlt]))
+                    && (!shift || any_held([Scancode::LShift, Scancode::RShift]))
+            }
+            Binding::Button(button) => held.controllers.iter().any(|c| c.button(button)),
+            Binding::Stick(axis, positive) => held.controllers.iter().any(|controller| {
+                let value = controller.axis(axis) as i32;
+                let value = if positive { value } else { -value };
+                // On a diagonal only the axis pushed furthest counts, so the
+                // stick behaves like a four-way joystick
+                let cross = other_stick_axis(axis).map_or(0, |a| (controller.axis(a) as i32).abs());
+                value > held.stick_deadzone as i32 && value >= cross
+            }),
+        }
+    }
+}
+
+// The other half of the same stick
+fn other_stick_axis(axis: Axis) -> Option<Axis> {
+    match axis {
+        Axis::LeftX => Some(Axis::LeftY),
+        Axis::LeftY => Some(Axis::LeftX),
+        Axis::RightX => Some(Axis::RightY),
+        Axis::RightY => Some(Axis::RightX),
+        Axis::TriggerLeft | Axis::TriggerRight => None,
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Key {
+                key,
+                ctrl,
+                alt,
+                shift,
+            } => {
+                if ctrl {
+                    write!(f, "Ctrl+")?;
+                }
+                if alt {
+                    write!(f, "Alt+")?;
+                }
+                if shift {
+                    write!(f, "Shift+")?;
+                }
+                write!(f, "{}", key.name())
+            }
+            Binding::Button(button) => write!(f, "Pad:{}", button.string()),
+            Binding::Stick(axis, positive) => {
+                write!(
+                    f,
+                    "Pad:{}{}",
+                    axis.string(),
+                    if positive { "+" } else { "-" }
+                )
+            }
+        }
+    }
+}
+
+// Turns stick movement into presses, one each time a stick is pushed past
+// the deadzone in a new direction, so sticks can be bound and captured like
+// buttons
+#[derive(Default)]
+pub struct StickPresses {
+    pushed: Vec<(u32, Axis, bool)>, // Controller, axis and direction held past the deadzone
+}
+
+impl StickPresses {
+    pub fn motion(
+        &mut self,
+        controller: u32,
+        axis: Axis,
+        value: i16,
+        deadzone: i16,
+    ) -> Option<Binding> {
+        let value = value as i32;
+        let direction = if value > deadzone as i32 {
+            Some(true)
+        } else if value < -(deadzone as i32) {
+            Some(false)
+        } else {
+            None
+        };
+        let before = self
+            .pushed
+            .iter()
+            .position(|&(c, a, _)| c == controller && a == axis);
+        if let Some(i) = before {
+            if Some(self.pushed[i].2) == direction {
+                return None;
+            }
+            self.pushed.remove(i);
+        }
+        let positive = direction?;
+        self.pushed.push((controller, axis, positive));
+        Some(Binding::Stick(axis, positive))
+    }
+
+    pub fn forget(&mut self, controller: u32) {
+        self.pushed.retain(|&(c, _, _)| c != controller);
+    }
+}
+
+// Bindings for every action. An action can have any number of bindings,
+// including none.
+pub struct Bindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Bindings {
+    // Actions missing from the config keep their default bindings
+    pub fn from_config(controls: &BTreeMap<String, Vec<String>>) -> Result<Bindings, String> {
+        let mut bindings: HashMap<Action, Vec<Binding>> = Action::ALL
+            .iter()
+            .map(|&action| (action, action.default_bindings()))
+            .collect();
+
+        for (name, keys) in controls {
+            let action = Action::ALL
+                .iter()
+                .find(|action| action.config_name() == name)
+                .ok_or_else(|| format!("Unknown action '{}' in [controls]", name))?;
+            let parsed = keys
+                .iter()
+                .map(|key| Binding::parse(key))
+                .collect::<Result<Vec<Binding>, String>>()
+                .map_err(|e| format!("[controls] {}: {}", name, e))?;
+            bindings.insert(*action, parsed);
+        }
+
+        Ok(Bindings { bindings })
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |b| &b[..])
+    }
+
+    pub fn add(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.insert(action, Vec::new());
+    }
+
+    // Every action bound to this key or button press. Presses need the exact
+    // modifiers, so Alt+Return doesn't also count as Return. Conflicting
+    // bindings fire all of their actions.
+    pub fn pressed_actions(&self, pressed: Binding) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(|&action| self.get(action).contains(&pressed))
+            .collect()
+    }
+
+    pub fn is_held(&self, action: Action, held: &HeldInputs) -> bool {
+        self.get(action).iter().any(|binding| binding.is_held(held))
+    }
+
+    // Bindings shared by more than one action, with the actions sharing them
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut conflicts: Vec<(Binding, Vec<Action>)> = Vec::new();
+        for action in Action::ALL {
+            for &binding in self.get(action) {
+                match conflicts.iter_mut().find(|(b, _)| *b == binding) {
+                    Some((_, actions)) => actions.push(action),
+                    None => conflicts.push((binding, vec![action])),
+                }
+            }
+        }
+        conflicts.retain(|(_, actions)| actions.len() > 1);
+        conflicts
+    }
+
+    // Rewrites only the [controls] table, so the rest of the file keeps its
+    // comments and layout
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        let mut document: toml_edit::DocumentMut =
+            text.parse().map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut controls = toml_edit::Table::new();
+        for action in Action::ALL {
+            let keys: toml_edit::Array = self.get(action).iter().map(|b| b.to_string()).collect();
+            controls.insert(action.config_name(), toml_edit::value(keys));
+        }
+        document.insert("controls", toml_edit::Item::Table(controls));
+
+        std::fs::write(path, document.to_string()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// Remembers the order the four directions were pressed in, so when several
+// are held the most recent one wins, and letting go of it falls back to the
+// one held before it
+pub struct DirectionInput {
+    held: Vec<Action>, // Oldest first
+}
+
+impl DirectionInput {
+    pub fn new() -> DirectionInput {
+        DirectionInput { held: Vec::new() }
+    }
+
+    // The direction the player wants to go in right now, if any
+    pub fn update(&mut self, bindings: &Bindings, held: &HeldInputs) -> Option<Action> {
+        self.track(|action| bindings.is_held(action, held))
+    }
+
+    fn track(&mut self, is_held: impl Fn(Action) -> bool) -> Option<Action> {
+        // Directions pressed on the same frame are added in this fixed order
+        for action in [Action::Up, Action::Down, Action::Left, Action::Right] {
+            let is_held = is_held(action);
+            let was_held = self.held.contains(&action);
+            if is_held && !was_held {
+                self.held.push(action);
+            } else if !is_held && was_held {
+                self.held.retain(|&a| a != action);
+            }
+        }
+        self.held.last().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Binding {
+        Binding::parse(text).unwrap()
+    }
+
+    fn controls(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys.iter().map(|key| key.to_string()).collect();
+                (action.to_string(), keys)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bindings_are_written_as_they_are_read() {
+        for text in [
+            "W",
+            "Up",
+            "F11",
+            "`",
+            "Ctrl+E",
+            "Alt+Return",
+            "Ctrl+Alt+Shift+Backspace",
+            "Pad:start",
+            "Pad:dpleft",
+            "Pad:leftx-",
+            "Pad:righty+",
+        ] {
+            let binding = parse(text);
+            assert_eq!(binding.to_string(), text);
+            assert_eq!(parse(&binding.to_string()), binding);
+        }
+    }
+
+    #[test]
+    fn names_are_read_loosely() {
+        assert_eq!(parse(" ctrl+shift+p "), parse("Ctrl+Shift+P"));
+        assert_eq!(parse("return"), parse("Return"));
+        assert_eq!(
+            parse("Shift+W"),
+            Binding::Key {
+                key: Keycode::W,
+                ctrl: false,
+                alt: false,
+                shift: true,
+            }
+        );
+    }
+
+    #[test]
+    fn bad_names_are_rejected() {
+        for text in [
+            "",
+            "Nope",
+            "Ctrl+",
+            "Hyper+X",
+            "Pad:nothing",
+            "Pad:leftx",
+            "Pad:dpup+",
+        ] {
+            assert!(Binding::parse(text).is_err(), "{}", text);
+        }
+        let e = Bindings::from_config(&controls(&[("jump", &["Space"])])).err();
+        assert_eq!(e.unwrap(), "Unknown action 'jump' in [controls]");
+        let e = Bindings::from_config(&controls(&[("pause", &["P", "Nope"])])).err();
+        assert!(e
+            .unwrap()
+            .starts_with("[controls] pause: Unknown key 'Nope'"));
+    }
+
+    #[test]
+    fn the_defaults_dont_conflict() {
+        let bindings = Bindings::from_config(&BTreeMap::new()).unwrap();
+        assert!(bindings.conflicts().is_empty());
+        assert_eq!(
+            bindings.get(Action::Pause),
+            [parse("P"), parse("Pad:start")]
+        );
+    }
+
+    #[test]
+    fn shared_bindings_are_conflicts() {
+        let config = controls(&[("pause", &["P", "F1"]), ("start", &["F1", "Return"])]);
+        let bindings = Bindings::from_config(&config).unwrap();
+        let conflicts = bindings.conflicts();
+        assert_eq!(
+            conflicts,
+            vec![(
+                parse("F1"),
+                vec![Action::Pause, Action::Start, Action::ToggleGrid]
+            )]
+        );
+        assert_eq!(
+            bindings.pressed_actions(parse("F1")),
+            vec![Action::Pause, Action::Start, Action::ToggleGrid]
+        );
+        // Presses need exactly the binding's modifiers
+        assert!(bindings.pressed_actions(parse("Shift+F1")).is_empty());
+    }
+
+    #[test]
+    fn a_stick_presses_once_per_push() {
+        let mut sticks = StickPresses::default();
+        let deadzone = 8000;
+        assert_eq!(sticks.motion(0, Axis::LeftX, 4000, deadzone), None);
+        assert_eq!(
+            sticks.motion(0, Axis::LeftX, 9000, deadzone),
+            Some(Binding::Stick(Axis::LeftX, true))
+        );
+        assert_eq!(sticks.motion(0, Axis::LeftX, 32767, deadzone), None);
+        // Another controller and another axis are pressed separately
+        assert_eq!(
+            sticks.motion(1, Axis::LeftX, 9000, deadzone),
+            Some(Binding::Stick(Axis::LeftX, true))
+        );
+        assert_eq!(
+            sticks.motion(0, Axis::LeftY, -9000, deadzone),
+            Some(Binding::Stick(Axis::LeftY, false))
+        );
+        // Straight across to the other side, and back out after letting go
+        assert_eq!(
+            sticks.motion(0, Axis::LeftX, -9000, deadzone),
+            Some(Binding::Stick(Axis::LeftX, false))
+        );
+        assert_eq!(sticks.motion(0, Axis::LeftX, 0, deadzone), None);
+        assert_eq!(
+            sticks.motion(0, Axis::LeftX, -9000, deadzone),
+            Some(Binding::Stick(Axis::LeftX, false))
+        );
+        // A controller that was unplugged starts afresh
+        sticks.forget(1);
+        assert_eq!(
+            sticks.motion(1, Axis::LeftX, 9000, deadzone),
+            Some(Binding::Stick(Axis::LeftX, true))
+        );
+    }
+
+    // Feeds DirectionInput the directions held on each frame
+    fn directions(frames: &[&[Action]]) -> Vec<Option<Action>> {
+        let mut input = DirectionInput::new();
+        frames
+            .iter()
+            .map(|held| input.track(|action| held.contains(&action)))
+            .collect()
+    }
+
+    #[test]
+    fn the_last_direction_pressed_wins() {
+        use Action::{Down, Left, Right, Up};
+        assert_eq!(
+            directions(&[
+                &[Right],
+                &[Right, Left],
+                &[Right, Left, Up],
+                &[Left, Up, Right]
+            ]),
+            vec![Some(Right), Some(Left), Some(Up), Some(Up)]
+        );
+        // Down doesn't beat Up just because it is checked later
+        assert_eq!(
+            directions(&[&[Down], &[Down, Up]]),
+            vec![Some(Down), Some(Up)]
+        );
+    }
+
+    #[test]
+    fn letting_go_falls_back_to_the_direction_held_before() {
+        use Action::{Left, Right, Up};
+        assert_eq!(
+            directions(&[
+                &[Up],
+                &[Up, Left],
+                &[Up, Left, Right],
+                &[Up, Right], // Released out of order, Right still wins
+                &[Up],
+                &[Up, Left],
+                &[Left],
+                &[],
+            ]),
+            vec![
+                Some(Up),
+                Some(Left),
+                Some(Right),
+                Some(Right),
+                Some(Up),
+                Some(Left),
+                Some(Left),
+                None
+            ]
+        );
+    }
+}
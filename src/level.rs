@@ -0,0 +1,191 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::game::Fruit;
+use crate::maze::{autotile, MazeTile};
+use crate::theme::ThemeSettings;
+use crate::tiled::{is_tiled_map, load_tiled_map};
+
+// The built-in level, used when no level file is given
+pub const CLASSIC_LEVEL: &str = include_str!("../levels/classic.txt");
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pellet {
+    Dot,
+    Energizer,
+}
+
+// Everything a level file describes. Positions are (col, row) tiles.
+#[derive(Clone)]
+pub struct Level {
+    pub tiles: Vec<Vec<MazeTile>>,
+    pub pellets: HashMap<(usize, usize), Pellet>,
+    pub pacman_start: (usize, usize),
+    pub ghost_starts: [(usize, usize); 4], // Blinky, Pinky, Inky, Clyde
+    pub fruit_tile: (usize, usize),
+    pub house_exit: (usize, usize), // The tile just above the ghost house door
+    pub rules: LevelRules,
+}
+
+// Changes a level pack makes to how one of its levels plays and looks.
+// Whatever isn't given is as the arcade has it for the level number.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LevelRules {
+    pub pacman_speed: Option<f32>, // Fraction of full speed
+    pub ghost_speed: Option<f32>,  // Fraction of full speed, outside the tunnels
+    pub frightened_seconds: Option<u32>,
+    pub fruit: Option<Fruit>,
+    pub theme: Option<ThemeSettings>, // Laid over the theme from config.toml
+}
+
+impl Level {
+    pub fn width(&self) -> usize {
+        self.tiles.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+
+    // Eaten ghosts are brought back to life here, in the middle of the house
+    pub fn house_centre(&self) -> (usize, usize) {
+        self.ghost_starts[1]
+    }
+
+    // The level in the form parse_level reads
+    pub fn to_text(&self) -> String {
+        let mut rows: Vec<Vec<char>> = self
+            .tiles
+            .iter()
+            .map(|row| {
+                let mut chars: Vec<char> = row
+                    .iter()
+                    .map(|tile| match tile {
+                        MazeTile::Empty => ' ',
+                        MazeTile::Door => '-',
+                        _ => '#',
+                    })
+                    .collect();
+                chars.resize(self.width(), ' ');
+                chars
+            })
+            .collect();
+        for (&(x, y), pellet) in &self.pellets {
+            rows[y][x] = match pellet {
+                Pellet::Dot => '.',
+                Pellet::Energizer => 'o',
+            };
+        }
+        rows[self.fruit_tile.1][self.fruit_tile.0] = 'F';
+        for (ghost, &(x, y)) in self.ghost_starts.iter().enumerate() {
+            rows[y][x] = (b'1' + ghost as u8) as char;
+        }
+        rows[self.pacman_start.1][self.pacman_start.0] = 'P';
+
+        let mut text = String::new();
+        for row in rows {
+            text.extend(row);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// Reads a level from its text form:
+//   '#'  wall             ' '  open space
+//   '.'  dot              'o'  energizer
+//   '-'  ghost house door 'P'  Pac-Man's start
+//   'F'  where fruit appears
+//   '1' to '4'  where Blinky, Pinky, Inky and Clyde start
+// Short rows are padded with open space. Open space at the edge of the
+// level wraps around to the other side, which is how tunnels are made.
+pub fn parse_level(text: &str) -> Result<Level, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines.iter().map(|line| line.chars().count()).max();
+    let width = width.ok_or("Level is empty")?;
+
+    let mut walls = vec![vec![false; width]; lines.len()];
+    let mut doors = Vec::new();
+    let mut pellets = HashMap::new();
+    let mut pacman_start = None;
+    let mut ghost_starts = [None; 4];
+    let mut fruit_tile = None;
+
+    for (y, line) in lines.iter().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            let duplicate =
+                |what: &str| format!("More than one {} at line {}, column {}", what, y + 1, x + 1);
+            match c {
+                '#' => walls[y][x] = true,
+                ' ' => {}
+                '.' => {
+                    pellets.insert((x, y), Pellet::Dot);
+                }
+                'o' => {
+                    pellets.insert((x, y), Pellet::Energizer);
+                }
+                '-' => doors.push((x, y)),
+                'P' => {
+                    if pacman_start.replace((x, y)).is_some() {
+                        return Err(duplicate("Pac-Man start"));
+                    }
+                }
+                'F' => {
+                    if fruit_tile.replace((x, y)).is_some() {
+                        return Err(duplicate("fruit tile"));
+                    }
+                }
+                '1'..='4' => {
+                    let ghost = c as usize - '1' as usize;
+                    if ghost_starts[ghost].replace((x, y)).is_some() {
+                        return Err(duplicate(&format!("start for ghost {}", c)));
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Unknown level character '{}' at line {}, column {}",
+                        c,
+                        y + 1,
+                        x + 1
+                    ))
+                }
+            }
+        }
+    }
+
+    let pacman_start = pacman_start.ok_or("Level has no Pac-Man start ('P')")?;
+    let fruit_tile = fruit_tile.ok_or("Level has no fruit tile ('F')")?;
+    let mut starts = [(0, 0); 4];
+    for (ghost, start) in ghost_starts.iter().enumerate() {
+        starts[ghost] = start.ok_or(format!("Level has no start for ghost {}", ghost + 1))?;
+    }
+    let &(door_col, door_row) = doors.first().ok_or("Level has no ghost house door ('-')")?;
+    if door_row == 0 {
+        return Err("The ghost house door can't be on the top row".to_string());
+    }
+
+    let mut tiles = autotile(&walls);
+    for &(x, y) in &doors {
+        tiles[y][x] = MazeTile::Door;
+    }
+
+    Ok(Level {
+        tiles,
+        pellets,
+        pacman_start,
+        ghost_starts: starts,
+        fruit_tile,
+        house_exit: (door_col, door_row - 1),
+        rules: LevelRules::default(),
+    })
+}
+
+// Loads a level file, or a map from the Tiled editor
+pub fn load_level(path: &str) -> Result<Level, String> {
+    if is_tiled_map(path) {
+        return load_tiled_map(path);
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_level(&text).map_err(|e| format!("{}: {}", path, e))
+}
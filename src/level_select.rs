@@ -0,0 +1,177 @@
+use sdl2::controller::Button;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use sdl2::ttf::Font;
+
+use crate::display::draw_text;
+use crate::input::Binding;
+use crate::pack::{LevelPack, PackProgress, PACKS_DIR};
+
+// Lists the level packs to pick one to play, and the level to start it on
+// out of the ones already reached. Packs that are locked, or made for a
+// different size of maze than the window, are shown but can't be picked.
+// The keys are fixed like the controls menu's: arrows, Return and Escape,
+// or the D-pad, A and B.
+pub struct LevelSelect {
+    pub open: bool,
+    selected: usize,
+    start_level: u32,
+}
+
+impl LevelSelect {
+    pub fn new() -> LevelSelect {
+        LevelSelect {
+            open: false,
+            selected: 0,
+            start_level: 1,
+        }
+    }
+
+    pub fn show(&mut self) {
+        self.open = true;
+    }
+
+    // Takes a key or controller button press, returning the pack and level
+    // to start when one is picked
+    pub fn handle_press(
+        &mut self,
+        pressed: Binding,
+        packs: &[LevelPack],
+        progress: &PackProgress,
+        maze_size: (usize, usize),
+    ) -> Option<(usize, u32)> {
+        let command = menu_command(pressed)?;
+        if packs.is_empty() {
+            self.open = command != MenuCommand::Close;
+            return None;
+        }
+        let count = packs.len();
+        match command {
+            MenuCommand::Previous => {
+                self.selected = (self.selected + count - 1) % count;
+                self.start_level = 1;
+            }
+            MenuCommand::Next => {
+                self.selected = (self.selected + 1) % count;
+                self.start_level = 1;
+            }
+            MenuCommand::Lower => self.start_level = self.start_level.saturating_sub(1).max(1),
+            MenuCommand::Higher => {
+                let reached = progress.reached(&packs[self.selected]);
+                self.start_level = (self.start_level + 1).min(reached);
+            }
+            MenuCommand::Choose => {
+                let pack = &packs[self.selected];
+                if progress.is_unlocked(pack, packs) && pack.size() == maze_size {
+                    self.open = false;
+                    return Some((self.selected, self.start_level));
+                }
+            }
+            MenuCommand::Close => self.open = false,
+        }
+        None
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        font: &Font,
+        packs: &[LevelPack],
+        progress: &PackProgress,
+        maze_size: (usize, usize),
+    ) -> Result<(), String> {
+        let viewport = canvas.viewport();
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 220));
+        canvas.fill_rect(Rect::new(0, 0, viewport.width(), viewport.height()))?;
+        canvas.set_blend_mode(sdl2::render::BlendMode::None);
+
+        let white = Color::RGB(255, 255, 255);
+        let yellow = Color::RGB(255, 255, 0);
+        let grey = Color::RGB(110, 110, 110);
+        let line_height = font.recommended_line_spacing();
+        let mut y = 40;
+
+        draw_text(canvas, font, "LEVEL SELECT", (40, y), yellow)?;
+        y += line_height * 2;
+        if packs.is_empty() {
+            let text = format!("No level packs in {}", PACKS_DIR);
+            draw_text(canvas, font, &text, (40, y), white)?;
+        }
+
+        for (i, pack) in packs.iter().enumerate() {
+            let selected = i == self.selected;
+            let (status, playable) = if !progress.is_unlocked(pack, packs) {
+                let required = pack.requires.as_deref().unwrap_or_default();
+                (format!("locked, finish {} first", required), false)
+            } else if pack.size() != maze_size {
+                ("made for a different size of maze".to_string(), false)
+            } else if selected {
+                let reached = progress.reached(pack);
+                (
+                    format!("< level {} of {} >", self.start_level, reached),
+                    true,
+                )
+            } else {
+                (format!("{} levels", pack.levels.len()), true)
+            };
+            let color = match (playable, selected) {
+                (false, _) => grey,
+                (true, true) => yellow,
+                (true, false) => white,
+            };
+            if selected {
+                draw_text(canvas, font, ">", (40, y), color)?;
+            }
+            draw_text(canvas, font, &pack.name, (70, y), color)?;
+            draw_text(canvas, font, &status, (330, y), color)?;
+            y += line_height;
+        }
+
+        y += line_height;
+        draw_text(
+            canvas,
+            font,
+            "Up/Down: pack   Left/Right: level   Return: play   Esc: close",
+            (40, y),
+            white,
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(PartialEq)]
+enum MenuCommand {
+    Previous,
+    Next,
+    Lower,
+    Higher,
+    Choose,
+    Close,
+}
+
+fn menu_command(pressed: Binding) -> Option<MenuCommand> {
+    match pressed {
+        Binding::Key { key, .. } => match key {
+            Keycode::Up => Some(MenuCommand::Previous),
+            Keycode::Down => Some(MenuCommand::Next),
+            Keycode::Left => Some(MenuCommand::Lower),
+            Keycode::Right => Some(MenuCommand::Higher),
+            Keycode::Return => Some(MenuCommand::Choose),
+            Keycode::Escape => Some(MenuCommand::Close),
+            _ => None,
+        },
+        Binding::Button(button) => match button {
+            Button::DPadUp => Some(MenuCommand::Previous),
+            Button::DPadDown => Some(MenuCommand::Next),
+            Button::DPadLeft => Some(MenuCommand::Lower),
+            Button::DPadRight => Some(MenuCommand::Higher),
+            Button::A => Some(MenuCommand::Choose),
+            Button::B => Some(MenuCommand::Close),
+            _ => None,
+        },
+        Binding::Stick(..) => None,
+    }
+}
@@ -4,47 +4,213 @@
 // export C_INCLUDE_PATH="/opt/homebrew/include:$C_INCLUDE_PATH"
 //
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::WindowCanvas;
 use sdl2::ttf::Font;
-use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-#[derive(Clone, Copy, PartialEq)]
-enum MazeTile {
-    Empty,          // Open space (Pac-Man can move here)
-    WallVertical,   // | (vertical wall)
-    WallHorizontal, // - (horizontal wall)
-    WallCornerNE,   // ⌜ (northeast corner)
-    WallCornerNW,   // ⌝ (northwest corner)
-    WallCornerSE,   // ⌞ (southeast corner)
-    WallCornerSW,   // ⌟ (southwest corner)
-}
+mod achievements;
+mod assists;
+mod attract;
+mod audio;
+mod bot;
+mod cli;
+mod config;
+mod console;
+mod controls_menu;
+mod debug;
+mod display;
+mod editor;
+mod events;
+mod game;
+mod generate;
+mod gym;
+mod high_scores;
+mod hud;
+mod input;
+mod level;
+mod level_select;
+mod maze;
+mod pack;
+mod pathfinding;
+mod playback;
+mod postfx;
+mod render;
+mod rng;
+mod scent;
+mod sprites;
+mod theme;
+mod tiled;
+mod validate;
+
+use achievements::Achievements;
+use assists::Assists;
+use attract::{draw_character_intro, draw_intermission, draw_title, Screen};
+use audio::{intro_duration, Audio, Siren, Sound};
+use bot::{bot_by_name, play_headless, Controller, GameView, GreedyBot};
+use cli::{parse_args, Options, Subcommand};
+use config::Config;
+use console::{apply_to_game, parse_command, Command, Console};
+use controls_menu::ControlsMenu;
+use debug::{DebugOverlay, FrameTimings};
+use display::{
+    create_playfield_textures, draw_text_centered, post_process_playfield, present_playfield,
+    toggle_fullscreen, window_to_playfield, ScalingMode,
+};
+use editor::{Editor, EditorRequest};
+use events::{EventListener, EventLog};
+use game::{tile_centre, Game, Mazes, Phase, TICKS_PER_SECOND, TILE_SIZE};
+use generate::generate_maze;
+use gym::{serve_stdio, EnvOptions};
+use high_scores::{HighScores, HIGH_SCORES_PATH};
+use hud::{draw_hud, ScorePopups, HUD_BOTTOM_ROWS, HUD_TOP_ROWS};
+use input::{Action, Binding, Bindings, DirectionInput, HeldInputs, StickPresses};
+use level::{load_level, parse_level, Level, CLASSIC_LEVEL};
+use level_select::LevelSelect;
+use maze::draw_maze;
+use pack::{load_packs, LevelPack, PackProgress, PACKS_DIR, PROGRESS_PATH};
+use playback::Playback;
+use postfx::PostProcessor;
+use render::{draw_fruit, draw_ghosts, draw_pellets, draw_player, maze_look};
+use scent::ScentMap;
+use sprites::SpriteAtlas;
+use theme::Theme;
+use tiled::is_tiled_map;
+use validate::{validate_level, Severity};
+
+const CONFIG_PATH: &str = "./config.toml";
+const EDITOR_DEFAULT_PATH: &str = "./levels/custom.txt"; // Where a level made from scratch is saved
+
+// A headless game that goes on this long is stopped, in case a bot finds a
+// way to never die
+const HEADLESS_MAX_TICKS: u64 = 60 * 60 * TICKS_PER_SECOND as u64;
 
 fn main() -> Result<(), String> {
+    let options = parse_args(std::env::args().skip(1))?;
+
+    // A level file can be passed as the first argument
+    let mut level = match &options.level_path {
+        Some(path) => load_level(path)?,
+        None => parse_level(CLASSIC_LEVEL)?,
+    };
+
+    match options.subcommand {
+        Subcommand::Play => {}
+        Subcommand::Validate => {
+            let name = options.level_path.as_deref().unwrap_or("classic level");
+            return run_validate(&level, name);
+        }
+        Subcommand::Generate => return run_generate(&options),
+    }
+    // The window is sized for the first maze, and the generated ones or
+    // the pack's that follow are all the same size
+    let mut mazes = Mazes::Fixed;
+    if let Some(path) = &options.pack {
+        let pack = LevelPack::load(path)?;
+        level = pack.levels[0].level.clone();
+        mazes = Mazes::Pack(Rc::new(pack));
+    } else if options.endless {
+        level = generate_maze(options.seed.unwrap_or(0), options.pellets)?;
+        mazes = Mazes::Endless;
+    }
+    let mut first_level = 1; // Where a new game starts, later in a pack picked from the level select
+    if options.headless {
+        return run_headless(&level, &mazes, &options);
+    }
+    if options.gym {
+        let defaults = EnvOptions::default();
+        let env_options = EnvOptions {
+            observation: options.observation,
+            frame_skip: options.frame_skip.unwrap_or(defaults.frame_skip),
+            ..defaults
+        };
+        return serve_stdio(level, mazes, env_options);
+    }
+    let mut bot = options.bot.as_deref().map(bot_by_name).transpose()?;
+
     // Initialize SDL2
     let sdl_context = sdl2::init()?;
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
     let video_subsystem = sdl_context.video()?;
+    let game_controller_subsystem = sdl_context.game_controller()?;
 
     let font_path = "./font.ttf"; // Replace with a valid TTF font path
     let font = ttf_context.load_font(font_path, 24)?;
+    let _image_context = sdl2::image::init(sdl2::image::InitFlag::PNG)?;
+
+    let config = Config::load(CONFIG_PATH)?;
+    let config_theme = config
+        .theme
+        .apply(&Theme::ARCADE)
+        .map_err(|e| format!("{}: {}", CONFIG_PATH, e))?;
+
+    let mut bindings = Bindings::from_config(&config.controls)?;
+    for (binding, actions) in bindings.conflicts() {
+        let names: Vec<&str> = actions.iter().map(|a| a.config_name()).collect();
+        eprintln!("Warning: {} is bound to {}", binding, names.join(", "));
+    }
+    let mut controls_menu = ControlsMenu::new();
+    let mut console = Console::new();
 
-    let player_radius = 12.0;
-    let player_diameter = player_radius * 2.0;
-    let speed = player_radius / 3.0; // Pixels per second
-    let tile_width = player_radius * 2.0;
-    let tile_height = player_radius * 2.0;
+    // The packs the level select offers, and how far each has been played
+    let (packs, pack_errors) = load_packs(PACKS_DIR);
+    for e in pack_errors {
+        eprintln!("Could not load level pack {}", e);
+    }
+    let mut progress = PackProgress::load(PROGRESS_PATH);
+    let mut high_scores = HighScores::load(HIGH_SCORES_PATH);
+    let mut level_select = LevelSelect::new();
+    let mut intermission_title = String::new();
+    let mut saved_game = None; // Kept by the console's save state
+
+    // The game plays on without sound if there is no audio device
+    let mut audio = if config.audio.enabled {
+        match Audio::open(&sdl_context, &config.audio) {
+            Ok(audio) => Some(audio),
+            Err(e) => {
+                eprintln!("Could not open audio, playing without sound: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    let grid_width = tile_width * 34.0;
-    let grid_height = tile_height * 34.0;
+    // Filled in by the device added events, which SDL also sends for the
+    // controllers already plugged in at startup
+    let mut controllers = Vec::new();
+    let mut stick_presses = StickPresses::default();
+    let stick_deadzone =
+        (config.controller.stick_deadzone.clamp(0.0, 1.0) * i16::MAX as f32) as i16;
+
+    let tile_width = TILE_SIZE;
+    let tile_height = TILE_SIZE;
+    let player_radius = tile_width / 2.0;
+
+    // The playfield is drawn at a fixed logical resolution and scaled to the
+    // window: the maze with a strip for the HUD above and below it
+    let grid_width = level.width() as f32;
+    let grid_height = level.height() as f32;
+    let maze_height = (grid_height * tile_height) as u32;
+    let hud_top = (HUD_TOP_ROWS as f32 * tile_height) as i32;
+    let logical_size = (
+        (grid_width * tile_width) as u32,
+        maze_height + ((HUD_TOP_ROWS + HUD_BOTTOM_ROWS) as f32 * tile_height) as u32,
+    );
+    let maze_viewport = Rect::new(0, hud_top, logical_size.0, maze_height);
+    let maze_size = (level.width(), level.height()); // Packs have to match to be picked
+    let mut scaling_mode = ScalingMode::Integer;
 
     // Create a window
     let window = video_subsystem
-        .window("Pacman", grid_width as u32, grid_height as u32)
+        .window("Pacman", logical_size.0, logical_size.1)
         .position_centered()
+        .resizable()
         .build()
         .map_err(|e| e.to_string())?;
 
@@ -55,21 +221,80 @@ fn main() -> Result<(), String> {
         .map_err(|e| e.to_string())?;
     let mut event_pump = sdl_context.event_pump()?;
 
-    // Set the initial position of the circle
-    let mut player_pos = (
-        canvas.window().size().0 as f32 / 2.0,
-        canvas.window().size().1 as f32 / 2.0,
-    );
-    let mut player_direction = (0.0, 0.0);
-    let mut last_direction = player_direction;
-
-    let mut tile_scents: HashMap<(usize, usize), PacmanScent> = HashMap::new();
+    // Typing is only wanted while the console is open
+    let text_input = video_subsystem.text_input();
+    text_input.stop();
 
-    let mut pacman_mouth_angle = 45.0; // Degrees
-    let mut pacman_mouth_opening = false;
-    let mut pacman_mouth_speed = 5.0; // degrees per second (could increase as pacman gets faster)
-
-    let maze = initialize_maze();
+    // The sprite atlas is optional, without it everything is drawn procedurally
+    let texture_creator = canvas.texture_creator();
+    let sprite_atlas_path = "./assets/sprites.toml";
+    let sprite_atlas = if std::path::Path::new(sprite_atlas_path).exists() {
+        match SpriteAtlas::load(sprite_atlas_path, &texture_creator) {
+            Ok(atlas) => Some(atlas),
+            Err(e) => {
+                eprintln!(
+                    "Could not load sprite atlas, using procedural graphics: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let animation_start = Instant::now();
+
+    let (mut playfield, mut processed_playfield) =
+        create_playfield_textures(&texture_creator, logical_size, scaling_mode)?;
+    let mut post_processor = PostProcessor::new(&config.post_processing, logical_size);
+
+    // With a bot given it plays straight away, otherwise the title comes first
+    let (mut screen, mut game) = if bot.is_some() {
+        (
+            Screen::Playing,
+            new_game(
+                &level,
+                options.seed,
+                &mazes,
+                first_level,
+                config.assists,
+                &mut audio,
+            ),
+        )
+    } else if options.edit {
+        (Screen::Editor, demo_game(&level))
+    } else {
+        (Screen::title(), demo_game(&level))
+    };
+    // The editor saves the text format, so a Tiled map is saved alongside
+    // itself rather than over the top
+    let editor_path = match options.level_path.as_deref() {
+        Some(path) if is_tiled_map(path) => Path::new(path)
+            .with_extension("txt")
+            .to_string_lossy()
+            .into_owned(),
+        Some(path) => path.to_string(),
+        None => EDITOR_DEFAULT_PATH.to_string(),
+    };
+    let mut editor = Editor::new(&level, &editor_path);
+    // The editor's level while it is being tried out, Escape goes back to the
+    // editor. It is kept apart from the level games are started on, so
+    // unsaved changes don't reach the demo or the next game.
+    let mut play_test: Option<Level> = None;
+    let mut demo_bot = GreedyBot;
+    let mut direction_input = DirectionInput::new();
+
+    // Everything that reacts to what happens in the game
+    let mut scent_map = ScentMap::default();
+    let mut score_popups = ScorePopups::default();
+    let mut achievements = Achievements::default();
+    let mut event_log = EventLog;
+
+    let mut paused = false;
+    let mut playback = Playback::new();
+    let mut debug = DebugOverlay::default();
+    let mut timings = FrameTimings::default();
+    let mut lap = Instant::now();
 
     'running: loop {
         //let current_time = std::time::Instant::now();
@@ -78,430 +303,644 @@ fn main() -> Result<(), String> {
 
         // Handle events
         for event in event_pump.poll_iter() {
-            if let Event::Quit { .. } = event {
-                break 'running;
+            // The editor has the mouse and keyboard to itself
+            if screen == Screen::Editor {
+                let window_size = canvas.window().size();
+                let point =
+                    |x, y| window_to_playfield((x, y), window_size, logical_size, scaling_mode);
+                let request = match event {
+                    Event::Quit { .. } => break 'running,
+                    Event::MouseButtonDown {
+                        x, y, mouse_btn, ..
+                    } => editor.mouse_down(point(x, y), mouse_btn == MouseButton::Right),
+                    Event::MouseMotion { x, y, .. } => {
+                        editor.mouse_moved(point(x, y));
+                        None
+                    }
+                    Event::MouseButtonUp { .. } => {
+                        editor.mouse_up();
+                        None
+                    }
+                    Event::KeyDown {
+                        keycode: Some(key),
+                        keymod,
+                        repeat: false,
+                        ..
+                    } => editor.handle_key(
+                        key,
+                        keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+                        keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+                    ),
+                    _ => None,
+                };
+                match request {
+                    Some(EditorRequest::PlayTest(edited)) => {
+                        game = Game::new((*edited).clone(), time_seed());
+                        play_test = Some(*edited);
+                        playback.reset();
+                        scent_map = ScentMap::default();
+                        score_popups = ScorePopups::default();
+                        screen = Screen::Playing;
+                    }
+                    Some(EditorRequest::Leave) => screen = Screen::title(),
+                    None => {}
+                }
+                continue;
             }
-        }
-
-        handle_keypress(&mut player_direction, &event_pump);
 
-        // Light up the tile where the player is located
-        let player_tile = get_tile(&player_pos, tile_width, tile_height, player_radius);
-        if let Some(tile) = player_tile {
-            light_up_tile(tile, 2000, &mut tile_scents);
-        }
-
-        // update_player_position(&mut player_pos, &player_direction, speed);
-        update_player_position(
-            &mut player_pos,
-            &player_direction,
-            speed,
-            &maze,
-            tile_width,
-            tile_height,
-            player_radius,
-        );
-
-        adjust_player_position_delete_me_later(
-            tile_width,
-            tile_height,
-            &mut player_pos,
-            &mut player_direction,
-            &mut last_direction,
-        );
-
-        handle_player_screen_wrapping(
-            &mut player_pos,
-            player_diameter,
-            canvas.window().size().0 as f32,
-            canvas.window().size().1 as f32,
-        );
-
-        // Clear the screen
-        clear_background(&mut canvas);
-
-        // Draw the grid
-        draw_grid(
-            &mut canvas,
-            tile_width,
-            tile_height,
-            grid_width,
-            grid_height,
-            1.0,
-        )?;
-
-        draw_maze(&mut canvas, &maze, tile_width, tile_height)?;
-
-        // Draw the circle
-        draw_pacman(
-            &mut canvas,
-            (player_pos.0, player_pos.1),
-            player_radius,
-            pacman_mouth_angle,
-            player_direction,
-        )?;
-
-        // Update the mouth angle
-        update_pacman_mouth_angle(
-            &mut pacman_mouth_opening,
-            &mut pacman_mouth_angle,
-            pacman_mouth_speed,
-        );
+            let pressed = match event {
+                Event::Quit { .. } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(key),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => Binding::from_key_press(key, keymod),
+                Event::ControllerButtonDown { button, .. } => Binding::Button(button),
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => match stick_presses.motion(which, axis, value, stick_deadzone) {
+                    Some(pressed) => pressed,
+                    None => continue,
+                },
+                Event::TextInput { text, .. } => {
+                    if console.open {
+                        console.type_text(&text);
+                    }
+                    continue;
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match game_controller_subsystem.open(which) {
+                        Ok(controller) => controllers.push(controller),
+                        Err(e) => eprintln!("Could not open controller {}: {}", which, e),
+                    }
+                    continue;
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|controller| controller.instance_id() != which);
+                    stick_presses.forget(which);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if controls_menu.open {
+                if controls_menu.handle_press(pressed, &mut bindings) {
+                    if let Err(e) = bindings.save(CONFIG_PATH) {
+                        eprintln!("Could not save controls: {}", e);
+                    }
+                }
+                continue;
+            }
 
-        // Update and draw lit tiles
-        update_tile_scent(
-            &mut canvas,
-            tile_width,
-            tile_height,
-            &mut tile_scents,
-            false,
-        );
+            if level_select.open {
+                if let Some((index, level_number)) =
+                    level_select.handle_press(pressed, &packs, &progress, maze_size)
+                {
+                    mazes = Mazes::Pack(Rc::new(packs[index].clone()));
+                    first_level = level_number;
+                    game = new_game(
+                        &level,
+                        options.seed,
+                        &mazes,
+                        first_level,
+                        config.assists,
+                        &mut audio,
+                    );
+                    playback.reset();
+                    scent_map = ScentMap::default();
+                    score_popups = ScorePopups::default();
+                    play_test = None;
+                    paused = false;
+                    screen = Screen::Playing;
+                }
+                continue;
+            }
 
-        // Render the player's position as text
-        render_player_position_hud(
-            &mut canvas,
-            &player_pos,
-            &font,
-            tile_width,
-            tile_height,
-            player_radius,
-        );
+            if console.open {
+                if let Binding::Key { key, .. } = pressed {
+                    if let Some(line) = console.handle_key(key) {
+                        let reply = match parse_command(&line) {
+                            Ok(Command::Clear) => {
+                                console.clear();
+                                Ok(String::new())
+                            }
+                            Ok(Command::Seed(seed)) => {
+                                game = new_game(
+                                    &level,
+                                    Some(seed),
+                                    &mazes,
+                                    first_level,
+                                    config.assists,
+                                    &mut audio,
+                                );
+                                playback.reset();
+                                scent_map = ScentMap::default();
+                                score_popups = ScorePopups::default();
+                                play_test = None;
+                                screen = Screen::Playing;
+                                Ok(format!("New game with seed {}", seed))
+                            }
+                            Ok(Command::SaveState) => {
+                                saved_game = Some(game.clone());
+                                Ok("State saved".to_string())
+                            }
+                            Ok(Command::LoadState) => match &saved_game {
+                                Some(saved) => {
+                                    game = saved.clone();
+                                    game.dev_tools_used = true;
+                                    playback.reset();
+                                    Ok("State loaded".to_string())
+                                }
+                                None => Err("No state saved yet".to_string()),
+                            },
+                            Ok(command) => apply_to_game(&command, &mut game),
+                            Err(e) => Err(e),
+                        };
+                        console.reply(reply);
+                    }
+                }
+                if !console.open {
+                    text_input.stop();
+                }
+                continue;
+            }
 
-        // Present the canvas
-        canvas.present();
+            if play_test.is_some()
+                && matches!(
+                    pressed,
+                    Binding::Key {
+                        key: Keycode::Escape,
+                        ..
+                    }
+                )
+            {
+                play_test = None;
+                paused = false;
+                screen = Screen::Editor;
+                continue;
+            }
 
-        // Delay to cap the frame rate at ~60 FPS
-        std::thread::sleep(Duration::from_millis(16));
-    }
+            // Any key skips a cutscene
+            if matches!(screen, Screen::Intermission { .. }) {
+                screen = Screen::Playing;
+                continue;
+            }
 
-    Ok(())
-} // main
+            let actions = bindings.pressed_actions(pressed);
+            if screen != Screen::Playing {
+                // Start begins a game from the title, any other key goes back to it
+                if matches!(screen, Screen::Title { .. }) && actions.contains(&Action::Start) {
+                    game = new_game(
+                        &level,
+                        options.seed,
+                        &mazes,
+                        first_level,
+                        config.assists,
+                        &mut audio,
+                    );
+                    playback.reset();
+                    scent_map = ScentMap::default();
+                    score_popups = ScorePopups::default();
+                    play_test = None;
+                    screen = Screen::Playing;
+                    continue;
+                }
+                play_test = None;
+                screen = Screen::title();
+            }
 
-// Add this near the top of main.rs
-fn initialize_maze() -> Vec<Vec<MazeTile>> {
-    let width = 34;
-    let height = 34;
-    let mut maze = vec![vec![MazeTile::Empty; width]; height];
+            for action in actions {
+                match action {
+                    Action::Pause if screen == Screen::Playing => paused = !paused,
+                    Action::Pause => {}
+                    Action::Start if game.phase == Phase::GameOver => {
+                        game = match &play_test {
+                            Some(tested) => Game::new(tested.clone(), time_seed()),
+                            None => new_game(
+                                &level,
+                                options.seed,
+                                &mazes,
+                                first_level,
+                                config.assists,
+                                &mut audio,
+                            ),
+                        };
+                        playback.reset();
+                    }
+                    Action::Start => paused = false,
+                    Action::ToggleGrid => debug.grid = !debug.grid,
+                    Action::ToggleScent => debug.scent = !debug.scent,
+                    Action::ToggleTargets => debug.targets = !debug.targets,
+                    Action::ToggleTileCentres => debug.tile_centres = !debug.tile_centres,
+                    Action::ToggleCollisions => debug.collisions = !debug.collisions,
+                    Action::ToggleTimings => debug.timings = !debug.timings,
+                    Action::Freeze if config.debug.playback_controls => playback.toggle_frozen(),
+                    Action::Step if config.debug.playback_controls => playback.step(),
+                    Action::Slower if config.debug.playback_controls => playback.slower(),
+                    Action::Faster if config.debug.playback_controls => playback.faster(),
+                    Action::Freeze | Action::Step | Action::Slower | Action::Faster => {}
+                    Action::ToggleFullscreen => toggle_fullscreen(&mut canvas)?,
+                    Action::ToggleScaling => {
+                        scaling_mode = scaling_mode.toggled();
+                        (playfield, processed_playfield) = create_playfield_textures(
+                            &texture_creator,
+                            logical_size,
+                            scaling_mode,
+                        )?;
+                    }
+                    Action::ToggleScanlines => post_processor.scanlines.toggle(),
+                    Action::ToggleCurvature => post_processor.curvature.toggle(),
+                    Action::ToggleGlow => post_processor.glow.toggle(),
+                    Action::ControlsMenu => controls_menu.show(),
+                    Action::LevelSelect => level_select.show(),
+                    Action::Editor => {
+                        play_test = None;
+                        paused = false;
+                        screen = Screen::Editor;
+                    }
+                    Action::Console => {
+                        console.open = true;
+                        text_input.start();
+                    }
+                    // Movement and rewinding follow the held keys and buttons
+                    Action::Up | Action::Down | Action::Left | Action::Right => {}
+                    Action::Rewind => {}
+                }
+            }
+        }
 
-    // Simple test maze: a box around the edges
-    for x in 0..width {
-        maze[0][x] = MazeTile::WallHorizontal; // Top edge
-        maze[height - 1][x] = MazeTile::WallHorizontal; // Bottom edge
-    }
-    for y in 0..height {
-        maze[y][0] = MazeTile::WallVertical; // Left edge
-        maze[y][width - 1] = MazeTile::WallVertical; // Right edge
-    }
-    // Corners
-    maze[0][0] = MazeTile::WallCornerNW;
-    maze[0][width - 1] = MazeTile::WallCornerNE;
-    maze[height - 1][0] = MazeTile::WallCornerSW;
-    maze[height - 1][width - 1] = MazeTile::WallCornerSE;
-
-    // Add a few internal walls for testing
-    maze[5][5] = MazeTile::WallVertical;
-    maze[5][6] = MazeTile::WallVertical;
-    maze[6][5] = MazeTile::WallHorizontal;
-    maze[6][6] = MazeTile::WallCornerSE;
-
-    maze
-} // initialize_maze
-
-fn draw_maze(
-    canvas: &mut WindowCanvas,
-    maze: &Vec<Vec<MazeTile>>,
-    tile_width: f32,
-    tile_height: f32,
-) -> Result<(), String> {
-    canvas.set_draw_color(Color::RGB(0, 0, 255)); // Blue walls
-
-    let wall_thickness = (tile_width / 4.0) as u32; // Thinner walls
-
-    for (y, row) in maze.iter().enumerate() {
-        for (x, tile) in row.iter().enumerate() {
-            let x_pos = (x as f32 * tile_width) as i32;
-            let y_pos = (y as f32 * tile_height) as i32;
-
-            match tile {
-                MazeTile::WallVertical => {
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos,
-                        wall_thickness,
-                        tile_height as u32,
-                    ))?;
+        timings.lap("input", &mut lap);
+
+        // The game stands still while paused, in the controls menu, the
+        // level select or the console. The demo plays silently.
+        let in_game = matches!(screen, Screen::Playing | Screen::Demo { .. });
+        let menu_open = controls_menu.open || level_select.open || console.open;
+        let simulating = in_game && !paused && !menu_open;
+        if let Some(audio) = &mut audio {
+            audio.set_siren(match game.phase {
+                _ if screen != Screen::Playing => Siren::Off,
+                _ if !simulating || playback.frozen => Siren::Off,
+                Phase::Playing if game.any_frightened() => Siren::Frightened,
+                Phase::Playing => Siren::Normal(game.progress()),
+                _ => Siren::Off,
+            });
+        }
+        if simulating {
+            let held = HeldInputs {
+                keyboard: event_pump.keyboard_state(),
+                controllers: &controllers,
+                stick_deadzone,
+            };
+
+            // The speed and rewind controls are for the player's game, the
+            // demo runs as normal. A game they have been used on keeps the
+            // marks of it when rewound.
+            let ticks = if screen != Screen::Playing {
+                1
+            } else if config.debug.playback_controls && bindings.is_held(Action::Rewind, &held) {
+                if let Some(mut earlier) = playback.rewind() {
+                    earlier.dev_tools_used = true;
+                    earlier.score_recorded = game.score_recorded;
+                    game = earlier;
                 }
-                MazeTile::WallHorizontal => {
-                    canvas.fill_rect(Rect::new(
-                        x_pos,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        tile_width as u32,
-                        wall_thickness,
-                    ))?;
+                0
+            } else {
+                if !playback.is_normal() {
+                    game.dev_tools_used = true;
                 }
-                MazeTile::WallCornerNW => {
-                    // Top-left corner: vertical down from top, horizontal right
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos,
-                        wall_thickness,
-                        (tile_height / 2.0) as u32 + wall_thickness / 2,
-                    ))?;
-                    canvas.fill_rect(Rect::new(
-                        x_pos,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        (tile_width / 2.0) as u32 + wall_thickness / 2,
-                        wall_thickness,
-                    ))?;
+                playback.ticks_this_frame(game.assists.game_speed())
+            };
+
+            let level_before = game.level_number;
+            for _ in 0..ticks {
+                if screen == Screen::Playing && config.debug.playback_controls {
+                    playback.record(&game);
                 }
-                MazeTile::WallCornerNE => {
-                    // Top-right corner: vertical down from top, horizontal left
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos,
-                        wall_thickness,
-                        (tile_height / 2.0) as u32 + wall_thickness / 2,
-                    ))?;
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        (tile_width / 2.0) as u32 + wall_thickness / 2,
-                        wall_thickness,
-                    ))?;
+                let view = GameView {
+                    game: &game,
+                    scent: &scent_map,
+                };
+                let turn = match &mut bot {
+                    _ if screen != Screen::Playing => demo_bot.decide(&view),
+                    Some(bot) => bot.decide(&view),
+                    None => handle_keypress(&mut direction_input, &held, &bindings),
+                };
+                timings.lap("ai", &mut lap);
+                game.tick(turn);
+
+                // The demo doesn't count towards achievements
+                let mut listeners: Vec<&mut dyn EventListener> =
+                    vec![&mut scent_map, &mut score_popups];
+                if screen == Screen::Playing {
+                    listeners.push(&mut achievements);
+                    if let Some(audio) = &mut audio {
+                        listeners.push(audio);
+                    }
                 }
-                MazeTile::WallCornerSW => {
-                    // Bottom-left corner: vertical up from bottom, horizontal right
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        wall_thickness,
-                        (tile_height / 2.0) as u32 + wall_thickness / 2,
-                    ))?;
-                    canvas.fill_rect(Rect::new(
-                        x_pos,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        (tile_width / 2.0) as u32 + wall_thickness / 2,
-                        wall_thickness,
-                    ))?;
+                if config.debug.log_events {
+                    listeners.push(&mut event_log);
                 }
-                MazeTile::WallCornerSE => {
-                    // Bottom-right corner: vertical up from bottom, horizontal left
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        wall_thickness,
-                        (tile_height / 2.0) as u32 + wall_thickness / 2,
-                    ))?;
-                    canvas.fill_rect(Rect::new(
-                        x_pos + (tile_width / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        y_pos + (tile_height / 2.0) as i32 - (wall_thickness / 2) as i32,
-                        (tile_width / 2.0) as u32 + wall_thickness / 2,
-                        wall_thickness,
-                    ))?;
+                game.events.dispatch(&mut listeners);
+                scent_map.update(game.tick);
+                timings.lap("simulation", &mut lap);
+            }
+
+            // Clearing a pack's level keeps it unlocked for the level select,
+            // and may be followed by a cutscene
+            if let Mazes::Pack(pack) = game.mazes() {
+                if screen == Screen::Playing && game.level_number > level_before {
+                    if progress.reach(pack, game.level_number) {
+                        if let Err(e) = progress.save(PROGRESS_PATH) {
+                            eprintln!("Could not save progress: {}", e);
+                        }
+                    }
+                    if let Some(title) = &pack.entry(level_before).intermission {
+                        intermission_title = title.clone();
+                        screen = Screen::Intermission { ticks: 0 };
+                    }
                 }
-                MazeTile::Empty => {} // No drawing for empty tiles
             }
-        }
-    }
-    Ok(())
-}
 
-fn adjust_player_position_delete_me_later(
-    tile_width: f32,
-    tile_height: f32,
-    player_pos: &mut (f32, f32),
-    player_direction: &mut (f32, f32),
-    last_direction: &mut (f32, f32),
-) {
-    // Check if the direction has changed
-    if *player_direction != *last_direction {
-        if player_direction.0 != 0.0 {
-            // Moving horizontally, re-center y position
-            player_pos.0 = ((player_pos.0 / tile_width).round() * tile_width) + tile_width / 2.0;
-        }
-        if player_direction.1 != 0.0 {
-            // Moving vertically, re-center x position
-            player_pos.1 = ((player_pos.1 / tile_height).round() * tile_height) + tile_height / 2.0;
+            // Only the player's own games go in the high scores, not a
+            // bot's or a level being tried out in the editor
+            let finished = game.phase == Phase::GameOver && !game.score_recorded;
+            let counts = screen == Screen::Playing && bot.is_none() && play_test.is_none();
+            if finished && counts {
+                game.score_recorded = true;
+                if high_scores.record(&game) {
+                    if let Err(e) = high_scores.save(HIGH_SCORES_PATH) {
+                        eprintln!("Could not save high scores: {}", e);
+                    }
+                }
+            }
         }
-        *last_direction = *player_direction;
-    }
-}
 
-fn clear_background(canvas: &mut WindowCanvas) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0)); // Purple background
-    canvas.clear();
-}
-
-fn update_pacman_mouth_angle(
-    pacman_mouth_opening: &mut bool,
-    pacman_mouth_angle: &mut f32,
-    pacman_mouth_speed: f32,
-) {
-    if *pacman_mouth_opening {
-        *pacman_mouth_angle += pacman_mouth_speed;
-        if *pacman_mouth_angle >= 70.0 {
-            *pacman_mouth_angle = 70.0;
-            *pacman_mouth_opening = false;
+        // Idling on the title runs through the attract sequence
+        if screen != Screen::Playing && !menu_open {
+            let next = screen.advance(&game);
+            if matches!(next, Screen::Demo { .. }) && !matches!(screen, Screen::Demo { .. }) {
+                game = demo_game(&level);
+                scent_map = ScentMap::default();
+                score_popups = ScorePopups::default();
+            }
+            screen = next;
         }
-    } else {
-        *pacman_mouth_angle -= pacman_mouth_speed;
-        if *pacman_mouth_angle <= 5.0 {
-            *pacman_mouth_angle = 5.0;
-            *pacman_mouth_opening = true;
+        timings.lap("simulation", &mut lap);
+
+        // The game is drawn in its level's colours, everything else in the
+        // configured ones. Packs check their themes when they load. The
+        // accessibility settings go over either.
+        let in_game = matches!(screen, Screen::Playing | Screen::Demo { .. });
+        let theme = match &game.level.rules.theme {
+            Some(settings) if in_game => settings
+                .apply(&config_theme)
+                .map(|theme| config.theme.apply_colors(theme))
+                .unwrap_or(config_theme),
+            _ => config_theme,
         }
-    }
-}
-
-fn draw_grid(
-    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
-    tile_width: f32,
-    tile_height: f32,
-    grid_width: f32,
-    grid_height: f32,
-    grid_line_thickness: f32,
-) -> Result<(), String> {
-    canvas.set_draw_color(Color::RGB(255, 255, 255)); // White lines
-
-    // Draw vertical lines
-    for col in 0..=grid_width as i32 {
-        let x = (col as f32 * tile_width) as i32;
-        canvas.fill_rect(Rect::new(
-            x,
-            0,
-            grid_line_thickness as u32,
-            (grid_height * tile_height) as u32,
-        ))?;
-    }
+        .accessible(&config.accessibility);
+        post_processor.set_glow_colors(&[theme.wall_outline, theme.pacman]);
+
+        // Draw everything into the playfield texture at the logical resolution
+        let mut render_result = Ok(());
+        canvas
+            .with_texture_canvas(&mut playfield, |canvas| {
+                render_result = (|| -> Result<(), String> {
+                    // Clear the screen
+                    clear_background(canvas, &theme);
+
+                    match screen {
+                        Screen::Title { idle_ticks } => {
+                            draw_title(canvas, &font, idle_ticks, &high_scores)?
+                        }
+                        Screen::CharacterIntro { ticks } => draw_character_intro(
+                            canvas,
+                            &font,
+                            ticks,
+                            tile_width,
+                            sprite_atlas.as_ref(),
+                            animation_start.elapsed(),
+                            &theme,
+                        )?,
+                        Screen::Intermission { ticks } => draw_intermission(
+                            canvas,
+                            &font,
+                            ticks,
+                            &intermission_title,
+                            tile_width,
+                            sprite_atlas.as_ref(),
+                            animation_start.elapsed(),
+                            &theme,
+                        )?,
+                        Screen::Editor => editor.draw(
+                            canvas,
+                            &font,
+                            sprite_atlas.as_ref(),
+                            animation_start.elapsed(),
+                            &theme,
+                        )?,
+                        Screen::Demo { .. } | Screen::Playing => {
+                            // The maze and everything in it is drawn in maze coordinates
+                            canvas.set_viewport(maze_viewport);
+
+                            debug.draw_under(canvas, &game, &scent_map, &theme)?;
+                            draw_maze(
+                                canvas,
+                                &game.level.tiles,
+                                tile_width,
+                                tile_height,
+                                sprite_atlas.as_ref(),
+                                &maze_look(&game, &theme),
+                            )?;
+                            draw_pellets(
+                                canvas,
+                                &game,
+                                tile_width,
+                                sprite_atlas.as_ref(),
+                                theme.pellet,
+                            )?;
+                            if let Some((fruit, _)) = game.fruit {
+                                let tile = game.level.fruit_tile;
+                                draw_fruit(
+                                    canvas,
+                                    fruit,
+                                    tile_centre((tile.0 as i32, tile.1 as i32)),
+                                    tile_width,
+                                    sprite_atlas.as_ref(),
+                                )?;
+                            }
+
+                            // Draw the circle, or the chomp animation when there is a sprite for it
+                            let elapsed = animation_start.elapsed();
+                            draw_player(
+                                canvas,
+                                &game,
+                                tile_width,
+                                sprite_atlas.as_ref(),
+                                elapsed,
+                                &theme,
+                            )?;
+                            if !matches!(game.phase, Phase::Dying(_) | Phase::LevelComplete(_)) {
+                                draw_ghosts(
+                                    canvas,
+                                    &game,
+                                    tile_width,
+                                    sprite_atlas.as_ref(),
+                                    elapsed,
+                                    &theme,
+                                )?;
+                            }
+                            debug.draw_over(canvas, &game, &theme)?;
+                            score_popups.draw(canvas, &font, game.tick)?;
+
+                            // Messages go where the fruit appears, below the ghost house
+                            let message_tile = game.level.fruit_tile;
+                            let message_center =
+                                tile_centre((message_tile.0 as i32, message_tile.1 as i32));
+                            let message_center = (message_center.0 as i32, message_center.1 as i32);
+                            match game.phase {
+                                Phase::Ready(_) => draw_text_centered(
+                                    canvas,
+                                    &font,
+                                    "READY!",
+                                    message_center,
+                                    Color::RGB(255, 255, 0),
+                                )?,
+                                Phase::GameOver => draw_text_centered(
+                                    canvas,
+                                    &font,
+                                    "GAME OVER",
+                                    message_center,
+                                    Color::RGB(255, 0, 0),
+                                )?,
+                                _ if screen != Screen::Playing => draw_text_centered(
+                                    canvas,
+                                    &font,
+                                    "DEMO",
+                                    message_center,
+                                    Color::RGB(255, 0, 0),
+                                )?,
+                                _ => {}
+                            }
+                            achievements.draw(
+                                canvas,
+                                &font,
+                                (message_center.0, message_center.1 + tile_height as i32 * 2),
+                                game.tick,
+                            )?;
+
+                            canvas.set_viewport(None);
+                            draw_hud(
+                                canvas,
+                                &font,
+                                &game,
+                                tile_width,
+                                maze_height as i32,
+                                sprite_atlas.as_ref(),
+                                &theme,
+                            )?;
+                            if let Some(status) =
+                                playback.status().filter(|_| screen == Screen::Playing)
+                            {
+                                draw_text_centered(
+                                    canvas,
+                                    &font,
+                                    &status,
+                                    (
+                                        logical_size.0 as i32 / 2,
+                                        logical_size.1 as i32 - tile_height as i32,
+                                    ),
+                                    Color::RGB(0, 255, 255),
+                                )?;
+                            }
+
+                            // Render the player's position as text
+                            render_player_position_hud(
+                                canvas,
+                                &game.pacman.pos,
+                                &font,
+                                tile_width,
+                                tile_height,
+                                player_radius,
+                            );
+                        }
+                    }
+
+                    if debug.timings {
+                        timings.draw(canvas, &font, (10, hud_top + 10))?;
+                    }
+                    if console.open {
+                        let cursor_on =
+                            (animation_start.elapsed().as_millis() / 500).is_multiple_of(2);
+                        console.draw(canvas, &font, cursor_on)?;
+                    } else if controls_menu.open {
+                        controls_menu.draw(canvas, &font, &bindings)?;
+                    } else if level_select.open {
+                        level_select.draw(canvas, &font, &packs, &progress, maze_size)?;
+                    } else if paused {
+                        draw_text_centered(
+                            canvas,
+                            &font,
+                            "PAUSED",
+                            (logical_size.0 as i32 / 2, logical_size.1 as i32 / 2),
+                            Color::RGB(255, 255, 0),
+                        )?;
+                    }
+                    Ok(())
+                })();
+            })
+            .map_err(|e| e.to_string())?;
+        render_result?;
+        timings.lap("render", &mut lap);
+
+        // Scale the playfield onto the window and present it, after the retro effects if any are on
+        if post_processor.any_enabled() {
+            post_process_playfield(
+                &mut canvas,
+                &mut playfield,
+                &mut processed_playfield,
+                &mut post_processor,
+            )?;
+            present_playfield(
+                &mut canvas,
+                &processed_playfield,
+                logical_size,
+                scaling_mode,
+            )?;
+        } else {
+            present_playfield(&mut canvas, &playfield, logical_size, scaling_mode)?;
+        }
+        timings.lap("present", &mut lap);
 
-    // Draw horizontal lines
-    for row in 0..=grid_height as i32 {
-        let y = (row as f32 * tile_height) as i32;
-        canvas.fill_rect(Rect::new(
-            0,
-            y,
-            (grid_width * tile_width) as u32,
-            grid_line_thickness as u32,
-        ))?;
+        // Delay to cap the frame rate at ~60 FPS
+        std::thread::sleep(Duration::from_millis(16));
+        timings.lap("sleep", &mut lap);
+        timings.end_frame();
     }
 
     Ok(())
-}
-
-fn draw_pacman(
-    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
-    center: (f32, f32),
-    radius: f32,
-    mouth_angle: f32,
-    player_direction: (f32, f32),
-) -> Result<(), String> {
-    let (cx, cy) = center;
-    let r = radius as i32;
-
-    // Helper function to compute minimal angle difference
-    fn angle_diff(a: f32, b: f32) -> f32 {
-        let mut diff = a - b;
-        while diff < -180.0 {
-            diff += 360.0;
-        }
-        while diff > 180.0 {
-            diff -= 360.0;
-        }
-        diff.abs()
-    }
-
-    // Determine the desired angle based on player direction
-    let desired_angle = if player_direction.0 == 1.0 {
-        0.0
-    } else if player_direction.0 == -1.0 {
-        180.0
-    } else if player_direction.1 == -1.0 {
-        -90.0
-    } else if player_direction.1 == 1.0 {
-        90.0
-    } else {
-        0.0 // Default to facing right if no direction
-    };
-
-    for w in 0..r * 2 {
-        for h in 0..r * 2 {
-            let dx = w - r; // Horizontal offset from center
-            let dy = h - r; // Vertical offset from center
-
-            // Convert dx and dy to få2
-            let dx_f32 = dx as f32;
-            let dy_f32 = dy as f32;
-
-            // Compute angle from positive x-axis to point (dx, dy)
-            let angle = dy_f32.atan2(dx_f32).to_degrees();
-            let delta_angle = angle_diff(angle, desired_angle);
+} // main
 
-            // Check if the point is outside the mouth's opening
-            if dx * dx + dy * dy <= r * r && delta_angle > mouth_angle / 2.0 {
-                canvas.set_draw_color(Color::RGB(255, 255, 0)); // Yellow circle
-                canvas.draw_point((cx as i32 + dx, cy as i32 + dy))?;
-            }
-        }
-    }
-    Ok(())
+fn clear_background(canvas: &mut WindowCanvas, theme: &Theme) {
+    canvas.set_draw_color(theme.background);
+    canvas.clear();
 }
 
-fn handle_keypress(player_direction: &mut (f32, f32), event_pump: &sdl2::EventPump) {
-    // Handle key presses
-    let keys: Vec<Keycode> = event_pump
-        .keyboard_state()
-        .pressed_scancodes()
-        .filter_map(Keycode::from_scancode)
-        .collect();
-
-    if keys.contains(&Keycode::W) || keys.contains(&Keycode::Up) {
-        player_direction.1 = -1.0;
-        player_direction.0 = 0.0;
-    }
-    if keys.contains(&Keycode::S) || keys.contains(&Keycode::Down) {
-        player_direction.1 = 1.0;
-        player_direction.0 = 0.0;
-    }
-    if keys.contains(&Keycode::A) || keys.contains(&Keycode::Left) {
-        player_direction.0 = -1.0;
-        player_direction.1 = 0.0;
-    }
-    if keys.contains(&Keycode::D) || keys.contains(&Keycode::Right) {
-        player_direction.0 = 1.0;
-        player_direction.1 = 0.0;
-    }
-}
-
-// fn update_player_position(player_pos: &mut (f32, f32), player_direction: &(f32, f32), speed: f32) {
-//     player_pos.0 += speed * player_direction.0;
-//     player_pos.1 += speed * player_direction.1;
-// }
-
-fn update_player_position(
-    player_pos: &mut (f32, f32),
-    player_direction: &(f32, f32),
-    speed: f32,
-    maze: &Vec<Vec<MazeTile>>,
-    tile_width: f32,
-    tile_height: f32,
-    player_radius: f32,
-) {
-    let next_x = player_pos.0 + speed * player_direction.0;
-    let next_y = player_pos.1 + speed * player_direction.1;
-
-    let next_tile = get_tile(&(next_x, next_y), tile_width, tile_height, player_radius);
-    let can_move = if let Some((col, row)) = next_tile {
-        if col < maze[0].len() && row < maze.len() {
-            maze[row][col] == MazeTile::Empty
-        } else {
-            true // Allow movement outside maze (for wrapping)
-        }
-    } else {
-        true // No tile means edge case, allow for now
-    };
-
-    if can_move {
-        player_pos.0 = next_x;
-        player_pos.1 = next_y;
+// The direction to buffer for Pac-Man, None when nothing is held so the
+// buffered one stays
+fn handle_keypress(
+    direction_input: &mut DirectionInput,
+    held: &HeldInputs,
+    bindings: &Bindings,
+) -> Option<(f32, f32)> {
+    // The most recently pressed direction that is still held wins
+    match direction_input.update(bindings, held) {
+        Some(Action::Up) => Some((0.0, -1.0)),
+        Some(Action::Down) => Some((0.0, 1.0)),
+        Some(Action::Left) => Some((-1.0, 0.0)),
+        Some(Action::Right) => Some((1.0, 0.0)),
+        _ => None,
     }
 }
 
@@ -552,31 +991,6 @@ fn render_player_position_hud(
     canvas.copy(&texture, None, dst_rect).unwrap();
 }
 
-fn handle_player_screen_wrapping(
-    player_pos: &mut (f32, f32),
-    player_diameter: f32,
-    screen_width: f32,
-    screen_height: f32,
-) {
-    let radius = player_diameter / 2.0;
-    // west border
-    if player_pos.0 < -player_diameter {
-        player_pos.0 = screen_width + radius;
-    }
-    // east border
-    else if player_pos.0 > screen_width + player_diameter {
-        player_pos.0 = -radius;
-    }
-    // north border
-    else if player_pos.1 < -player_diameter {
-        player_pos.1 = screen_height + radius;
-    }
-    // south border
-    else if player_pos.1 > screen_height + player_diameter {
-        player_pos.1 = -radius;
-    }
-}
-
 fn get_tile(
     pos: &(f32, f32),
     tile_width: f32,
@@ -600,55 +1014,124 @@ fn get_tile(
     }
 }
 
-struct PacmanScent {
-    start_time: Instant,
-    duration: Duration,
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or(0)
 }
 
-fn light_up_tile(
-    tile_pos: (usize, usize),
-    duration: u64,
-    tile_lights: &mut HashMap<(usize, usize), PacmanScent>,
-) {
-    let now = Instant::now();
-    let duration = Duration::from_millis(duration);
-    tile_lights.insert(
-        tile_pos,
-        PacmanScent {
-            start_time: now,
-            duration,
-        },
+// The bot's game in attract mode, over after one life
+fn demo_game(level: &Level) -> Game {
+    let mut game = Game::new(level.clone(), time_seed());
+    game.lives = 1;
+    game
+}
+
+// A fresh game that waits for the intro tune before it starts
+fn new_game(
+    level: &Level,
+    seed: Option<u64>,
+    mazes: &Mazes,
+    first_level: u32,
+    assists: Assists,
+    audio: &mut Option<Audio>,
+) -> Game {
+    let seed = seed.unwrap_or_else(time_seed);
+    let mut game = Game::new(level.clone(), seed);
+    game.set_mazes(mazes.clone(), first_level);
+    game.set_assists(assists);
+    let intro_ticks = intro_duration().as_secs_f32() * TICKS_PER_SECOND as f32;
+    game.phase = Phase::Ready(intro_ticks as u32);
+    if let Some(audio) = audio {
+        audio.play(Sound::Intro);
+    }
+    game
+}
+
+// Prints what is wrong with the level, failing if anything stops it being
+// played properly
+fn run_validate(level: &Level, name: &str) -> Result<(), String> {
+    let problems = validate_level(level);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    let count = |n: usize, what: &str| match n {
+        1 => format!("1 {}", what),
+        n => format!("{} {}s", n, what),
+    };
+    println!(
+        "{}: {}, {}",
+        name,
+        count(errors, "error"),
+        count(problems.len() - errors, "warning")
     );
+    if errors > 0 {
+        return Err(format!("{} can't be played properly", name));
+    }
+    Ok(())
 }
 
-fn update_tile_scent(
-    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
-    tile_width: f32,
-    tile_height: f32,
-    tile_lights: &mut HashMap<(usize, usize), PacmanScent>,
-    render_scent: bool,
-) {
-    let now = Instant::now();
-    tile_lights.retain(|&(col, row), tile_light| {
-        let elapsed = now.duration_since(tile_light.start_time);
-        if elapsed < tile_light.duration {
-            if render_scent {
-                let progress = elapsed.as_secs_f32() / tile_light.duration.as_secs_f32();
-                let brightness = (1.0 - progress) * 200.0;
-                canvas.set_draw_color(Color::RGB(
-                    brightness as u8,
-                    brightness as u8,
-                    brightness as u8,
-                ));
-                let x = (col as f32 * tile_width) as i32;
-                let y = (row as f32 * tile_height) as i32;
-                canvas
-                    .fill_rect(Rect::new(x, y, tile_width as u32, tile_height as u32))
-                    .unwrap();
-            }
-            true
-        } else {
-            false
+// Makes a maze from the seed and writes it out, or prints it
+fn run_generate(options: &Options) -> Result<(), String> {
+    let seed = options.seed.unwrap_or_else(time_seed);
+    let text = generate_maze(seed, options.pellets)?.to_text();
+    match &options.out {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?;
+            println!("Wrote the maze from seed {} to {}", seed, path);
         }
-    });
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+// Plays the bot's games one after another without a window, each with the
+// next seed, and reports how they went
+fn run_headless(level: &Level, mazes: &Mazes, options: &Options) -> Result<(), String> {
+    let bot_name = options.bot.as_deref().unwrap_or_default();
+    let first_seed = options.seed.unwrap_or_else(time_seed);
+    let started = Instant::now();
+    let (mut total_score, mut total_cleared, mut total_ticks) = (0u64, 0u64, 0u64);
+
+    for i in 0..options.games {
+        let seed = first_seed.wrapping_add(i as u64);
+        let mut bot = bot_by_name(bot_name)?;
+        let mut game = Game::new(level.clone(), seed);
+        game.set_mazes(mazes.clone(), 1);
+        let stats = play_headless(&mut game, bot.as_mut(), HEADLESS_MAX_TICKS);
+        println!(
+            "seed {}: score {}, level {}, {} cleared, {} deaths, {} ghosts eaten, {} ticks{}",
+            seed,
+            game.score,
+            game.level_number,
+            stats.levels_cleared,
+            stats.deaths,
+            stats.ghosts_eaten,
+            game.tick,
+            if game.phase == Phase::GameOver {
+                ""
+            } else {
+                " (stopped)"
+            }
+        );
+        total_score += game.score as u64;
+        total_cleared += stats.levels_cleared as u64;
+        total_ticks += game.tick;
+    }
+
+    let games = options.games.max(1) as f64;
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "{} games: average score {:.0}, average levels cleared {:.2}, {:.0} ticks per second",
+        options.games,
+        total_score as f64 / games,
+        total_cleared as f64 / games,
+        total_ticks as f64 / seconds.max(0.001)
+    );
+    Ok(())
 }
@@ -0,0 +1,382 @@
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+
+use crate::sprites::SpriteAtlas;
+use crate::theme::Theme;
+
+// Wall pieces are named after where they sit on a block, so WallCornerNW is
+// the top-left corner of a block. Level files don't pick these by hand:
+// they mark walls and `autotile` works out the piece from the neighbours.
+// Maps imported from Tiled can pick them, see `load_tiled_map`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MazeTile {
+    Empty,          // Open space (Pac-Man can move here)
+    WallVertical,   // │ connects north and south
+    WallHorizontal, // ─ connects east and west
+    WallCornerNW,   // ┌ connects east and south
+    WallCornerNE,   // ┐ connects west and south
+    WallCornerSW,   // └ connects north and east
+    WallCornerSE,   // ┘ connects north and west
+    WallTeeN,       // ┴ stem points north, connects east, west and north
+    WallTeeE,       // ├ stem points east, connects north, south and east
+    WallTeeS,       // ┬ stem points south, connects east, west and south
+    WallTeeW,       // ┤ stem points west, connects north, south and west
+    WallCross,      // ┼ connects all four sides
+    WallEndN,       // ╵ end cap, only connects north
+    WallEndE,       // ╶ end cap, only connects east
+    WallEndS,       // ╷ end cap, only connects south
+    WallEndW,       // ╴ end cap, only connects west
+    WallPillar,     // ▪ wall with no wall neighbours
+    WallSolid,      // Inside a thick wall, nothing to draw
+    Door,           // The ghost house door, ghosts pass it one way and Pac-Man never
+}
+
+// Which sides of a wall tile connect to a neighbouring wall
+pub const WALL_N: u8 = 1;
+pub const WALL_E: u8 = 2;
+pub const WALL_S: u8 = 4;
+pub const WALL_W: u8 = 8;
+
+impl MazeTile {
+    pub fn is_wall(&self) -> bool {
+        !matches!(self, MazeTile::Empty | MazeTile::Door)
+    }
+
+    // Used as the sprite frame name and in level reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeTile::Empty => "empty",
+            MazeTile::WallVertical => "wall_vertical",
+            MazeTile::WallHorizontal => "wall_horizontal",
+            MazeTile::WallCornerNW => "wall_corner_nw",
+            MazeTile::WallCornerNE => "wall_corner_ne",
+            MazeTile::WallCornerSW => "wall_corner_sw",
+            MazeTile::WallCornerSE => "wall_corner_se",
+            MazeTile::WallTeeN => "wall_tee_n",
+            MazeTile::WallTeeE => "wall_tee_e",
+            MazeTile::WallTeeS => "wall_tee_s",
+            MazeTile::WallTeeW => "wall_tee_w",
+            MazeTile::WallCross => "wall_cross",
+            MazeTile::WallEndN => "wall_end_n",
+            MazeTile::WallEndE => "wall_end_e",
+            MazeTile::WallEndS => "wall_end_s",
+            MazeTile::WallEndW => "wall_end_w",
+            MazeTile::WallPillar => "wall_pillar",
+            MazeTile::WallSolid => "wall_solid",
+            MazeTile::Door => "door",
+        }
+    }
+
+    // Sides this piece connects to, None for non-wall tiles
+    pub fn connections(&self) -> Option<u8> {
+        let connections = match self {
+            MazeTile::Empty | MazeTile::Door => return None,
+            MazeTile::WallVertical => WALL_N | WALL_S,
+            MazeTile::WallHorizontal => WALL_E | WALL_W,
+            MazeTile::WallCornerNW => WALL_E | WALL_S,
+            MazeTile::WallCornerNE => WALL_W | WALL_S,
+            MazeTile::WallCornerSW => WALL_N | WALL_E,
+            MazeTile::WallCornerSE => WALL_N | WALL_W,
+            MazeTile::WallTeeN => WALL_E | WALL_W | WALL_N,
+            MazeTile::WallTeeE => WALL_N | WALL_S | WALL_E,
+            MazeTile::WallTeeS => WALL_E | WALL_W | WALL_S,
+            MazeTile::WallTeeW => WALL_N | WALL_S | WALL_W,
+            MazeTile::WallCross => WALL_N | WALL_E | WALL_S | WALL_W,
+            MazeTile::WallEndN => WALL_N,
+            MazeTile::WallEndE => WALL_E,
+            MazeTile::WallEndS => WALL_S,
+            MazeTile::WallEndW => WALL_W,
+            MazeTile::WallPillar | MazeTile::WallSolid => 0,
+        };
+        Some(connections)
+    }
+
+    // The wall piece that connects to exactly these sides
+    pub fn from_connections(connections: u8) -> MazeTile {
+        match connections & (WALL_N | WALL_E | WALL_S | WALL_W) {
+            0 => MazeTile::WallPillar,
+            WALL_N => MazeTile::WallEndN,
+            WALL_E => MazeTile::WallEndE,
+            WALL_S => MazeTile::WallEndS,
+            WALL_W => MazeTile::WallEndW,
+            c if c == WALL_N | WALL_S => MazeTile::WallVertical,
+            c if c == WALL_E | WALL_W => MazeTile::WallHorizontal,
+            c if c == WALL_E | WALL_S => MazeTile::WallCornerNW,
+            c if c == WALL_W | WALL_S => MazeTile::WallCornerNE,
+            c if c == WALL_N | WALL_E => MazeTile::WallCornerSW,
+            c if c == WALL_N | WALL_W => MazeTile::WallCornerSE,
+            c if c == WALL_E | WALL_W | WALL_N => MazeTile::WallTeeN,
+            c if c == WALL_N | WALL_S | WALL_E => MazeTile::WallTeeE,
+            c if c == WALL_E | WALL_W | WALL_S => MazeTile::WallTeeS,
+            c if c == WALL_N | WALL_S | WALL_W => MazeTile::WallTeeW,
+            _ => MazeTile::WallCross,
+        }
+    }
+}
+
+// Picks the wall piece for every wall in the mask from its neighbours
+pub fn autotile(walls: &[Vec<bool>]) -> Vec<Vec<MazeTile>> {
+    walls
+        .iter()
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, &wall)| {
+                    if wall {
+                        match wall_connections(walls, x, y) {
+                            0 if is_surrounded(walls, x, y) => MazeTile::WallSolid,
+                            connections => MazeTile::from_connections(connections),
+                        }
+                    } else {
+                        MazeTile::Empty
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn is_wall_at(walls: &[Vec<bool>], col: isize, row: isize) -> bool {
+    col >= 0
+        && row >= 0
+        && walls
+            .get(row as usize)
+            .and_then(|tiles| tiles.get(col as usize))
+            .is_some_and(|&wall| wall)
+}
+
+// Two neighbouring walls only connect along the edge of a wall, where there
+// is open space on at least one side of the pair. That way a thick block is
+// drawn as its outline rather than a mesh of crossings.
+fn wall_connections(walls: &[Vec<bool>], col: usize, row: usize) -> u8 {
+    let (col, row) = (col as isize, row as isize);
+    let wall = |c: isize, r: isize| is_wall_at(walls, c, r);
+
+    let mut connections = 0;
+    for (side, dx, dy) in [
+        (WALL_N, 0, -1),
+        (WALL_E, 1, 0),
+        (WALL_S, 0, 1),
+        (WALL_W, -1, 0),
+    ] {
+        let (next_col, next_row) = (col + dx, row + dy);
+        if !wall(next_col, next_row) {
+            continue;
+        }
+        // The tiles on both sides of the pair, across the line joining them
+        let (across_x, across_y) = (dy, dx);
+        let on_edge = !wall(col + across_x, row + across_y)
+            || !wall(col - across_x, row - across_y)
+            || !wall(next_col + across_x, next_row + across_y)
+            || !wall(next_col - across_x, next_row - across_y);
+        if on_edge {
+            connections |= side;
+        }
+    }
+    connections
+}
+
+// A wall with walls all around it, including the diagonals
+fn is_surrounded(walls: &[Vec<bool>], col: usize, row: usize) -> bool {
+    let (col, row) = (col as isize, row as isize);
+    (-1..=1).all(|dy| (-1..=1).all(|dx| is_wall_at(walls, col + dx, row + dy)))
+}
+
+pub fn draw_maze(
+    canvas: &mut WindowCanvas,
+    maze: &[Vec<MazeTile>],
+    tile_width: f32,
+    tile_height: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    theme: &Theme,
+) -> Result<(), String> {
+    let mut points = Vec::new();
+    let mut fill = Vec::new();
+    let mut doors = Vec::new();
+    for (y, row) in maze.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            if matches!(tile, MazeTile::Empty | MazeTile::WallSolid) {
+                continue;
+            }
+            let x_pos = (x as f32 * tile_width) as i32;
+            let y_pos = (y as f32 * tile_height) as i32;
+
+            if let Some(atlas) = sprite_atlas {
+                let dst = Rect::new(x_pos, y_pos, tile_width as u32, tile_height as u32);
+                if atlas.draw_frame(canvas, tile.name(), dst)? {
+                    continue;
+                }
+            }
+            let Some(connections) = tile.connections() else {
+                // A bar across the middle of the tile
+                let thickness = (tile_height / 6.0).max(1.0);
+                doors.push(Rect::new(
+                    x_pos,
+                    y_pos + ((tile_height - thickness) / 2.0) as i32,
+                    tile_width as u32,
+                    thickness as u32,
+                ));
+                continue;
+            };
+            add_wall_piece_points(
+                &mut points,
+                &mut fill,
+                (x_pos, y_pos),
+                tile_width,
+                tile_height,
+                connections,
+                theme.thick_outlines,
+            );
+        }
+    }
+
+    canvas.set_draw_color(theme.door);
+    canvas.fill_rects(&doors)?;
+    if theme.wall_fill != theme.background {
+        canvas.set_draw_color(theme.wall_fill);
+        canvas.draw_points(&fill[..])?;
+    }
+    canvas.set_draw_color(theme.wall_outline);
+    canvas.draw_points(&points[..])
+}
+
+// Walls are drawn as the outline of a thick stroke running through the tile
+// centre towards every connected neighbour, which gives the arcade's double
+// lines. Turns get a rounded outer corner from the stroke itself and a
+// rounded inner corner from a fillet between the two arms. The inside of
+// the stroke goes in `fill`.
+fn add_wall_piece_points(
+    points: &mut Vec<Point>,
+    fill: &mut Vec<Point>,
+    origin: (i32, i32),
+    tile_width: f32,
+    tile_height: f32,
+    connections: u8,
+    thick: bool,
+) {
+    let tile_size = tile_width.min(tile_height);
+    let half_width = tile_size / 4.0; // Distance from the centre to each line
+    let fillet_radius = tile_size / 4.0;
+    let line_thickness = (tile_size / if thick { 6.0 } else { 12.0 }).max(1.0);
+
+    let connected = |side: u8| connections & side != 0;
+
+    for h in 0..tile_height as i32 {
+        for w in 0..tile_width as i32 {
+            // Offset of the pixel centre from the tile centre
+            let dx = w as f32 + 0.5 - tile_width / 2.0;
+            let dy = h as f32 + 0.5 - tile_height / 2.0;
+
+            // Distance to the stroke's skeleton: the centre point plus one arm per connection
+            let mut skeleton_distance = dx.hypot(dy);
+            if (connected(WALL_N) && dy <= 0.0) || (connected(WALL_S) && dy >= 0.0) {
+                skeleton_distance = skeleton_distance.min(dx.abs());
+            }
+            if (connected(WALL_E) && dx >= 0.0) || (connected(WALL_W) && dx <= 0.0) {
+                skeleton_distance = skeleton_distance.min(dy.abs());
+            }
+            let mut inside = half_width - skeleton_distance;
+
+            // Round off the inner corner when both arms around this quadrant are present
+            let vertical_arm = if dy <= 0.0 { WALL_N } else { WALL_S };
+            let horizontal_arm = if dx >= 0.0 { WALL_E } else { WALL_W };
+            if connected(vertical_arm) && connected(horizontal_arm) {
+                let ex = (half_width + fillet_radius - dx.abs()).max(0.0);
+                let ey = (half_width + fillet_radius - dy.abs()).max(0.0);
+                inside = inside.max(ex.hypot(ey) - fillet_radius);
+            }
+
+            if (0.0..line_thickness).contains(&inside) {
+                points.push(Point::new(origin.0 + w, origin.1 + h));
+            } else if inside >= line_thickness {
+                fill.push(Point::new(origin.0 + w, origin.1 + h));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // '#' is a wall, anything else open space
+    fn mask(rows: &[&str]) -> Vec<Vec<bool>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    #[test]
+    fn every_set_of_connections_has_a_piece() {
+        for connections in 0..16 {
+            let tile = MazeTile::from_connections(connections);
+            assert_eq!(tile.connections(), Some(connections), "{:?}", tile);
+        }
+    }
+
+    #[test]
+    fn corners_and_end_caps() {
+        let tiles = autotile(&mask(&["##", "#."]));
+        assert_eq!(tiles[0][0], MazeTile::WallCornerNW);
+        assert_eq!(tiles[0][1], MazeTile::WallEndW);
+        assert_eq!(tiles[1][0], MazeTile::WallEndN);
+        assert_eq!(tiles[1][1], MazeTile::Empty);
+    }
+
+    #[test]
+    fn t_junctions() {
+        let tiles = autotile(&mask(&["###", ".#."]));
+        assert_eq!(tiles[0][1], MazeTile::WallTeeS);
+        assert_eq!(tiles[0][0], MazeTile::WallEndE);
+        assert_eq!(tiles[1][1], MazeTile::WallEndN);
+
+        let tiles = autotile(&mask(&["#.", "##", "#."]));
+        assert_eq!(tiles[1][0], MazeTile::WallTeeE);
+    }
+
+    #[test]
+    fn crosses() {
+        let tiles = autotile(&mask(&[".#.", "###", ".#."]));
+        assert_eq!(tiles[1][1], MazeTile::WallCross);
+    }
+
+    #[test]
+    fn a_lone_wall_is_a_pillar() {
+        let tiles = autotile(&mask(&["...", ".#.", "..."]));
+        assert_eq!(tiles[1][1], MazeTile::WallPillar);
+        assert_eq!(autotile(&mask(&["#"]))[0][0], MazeTile::WallPillar);
+    }
+
+    #[test]
+    fn nothing_joins_past_the_edge_of_the_map() {
+        let tiles = autotile(&mask(&["###", "#.#", "###"]));
+        assert_eq!(tiles[0][0], MazeTile::WallCornerNW);
+        assert_eq!(tiles[0][1], MazeTile::WallHorizontal);
+        assert_eq!(tiles[0][2], MazeTile::WallCornerNE);
+        assert_eq!(tiles[1][0], MazeTile::WallVertical);
+        assert_eq!(tiles[1][2], MazeTile::WallVertical);
+        assert_eq!(tiles[2][0], MazeTile::WallCornerSW);
+        assert_eq!(tiles[2][2], MazeTile::WallCornerSE);
+
+        let tiles = autotile(&mask(&["##"]));
+        assert_eq!(tiles[0], vec![MazeTile::WallEndE, MazeTile::WallEndW]);
+    }
+
+    #[test]
+    fn thick_walls_are_outlined() {
+        let tiles = autotile(&mask(&["###", "###", "###"]));
+        assert_eq!(tiles[0][0], MazeTile::WallCornerNW);
+        assert_eq!(tiles[0][1], MazeTile::WallHorizontal);
+        assert_eq!(tiles[1][0], MazeTile::WallVertical);
+        assert_eq!(tiles[1][1], MazeTile::WallSolid);
+        assert_eq!(tiles[2][2], MazeTile::WallCornerSE);
+
+        // Two rows thick: the pieces along each side don't join across
+        let tiles = autotile(&mask(&["....", ".##.", ".##.", "...."]));
+        assert_eq!(tiles[1][1], MazeTile::WallCornerNW);
+        assert_eq!(tiles[1][2], MazeTile::WallCornerNE);
+        assert_eq!(tiles[2][1], MazeTile::WallCornerSW);
+        assert_eq!(tiles[2][2], MazeTile::WallCornerSE);
+    }
+}
@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::level::{load_level, parse_level, Level, LevelRules};
+use crate::theme::Theme;
+use crate::tiled::is_tiled_map;
+
+pub const PACKS_DIR: &str = "./packs"; // Where the level select looks for packs
+pub const PROGRESS_PATH: &str = "./progress.toml";
+const PACK_FILE: &str = "pack.toml";
+
+// A level pack is a directory, or a zip archive of one, holding pack.toml
+// and the maze files it names:
+//
+//   name = "Ms. Pac-Man"
+//   requires = "classic"  # Optional, the pack to finish before this one opens
+//   repeat_from = 6       # Optional, where to go round again after the last level
+//
+//   [[levels]]
+//   maze = "maze1.txt"    # A level file, or a Tiled map in a directory pack
+//   intermission = "They Meet"  # Optional, a cutscene after this level
+//   fruit = "cherry"      # Optional rule overrides, see LevelRules
+//   theme = { name = "ms-pacman", wall_outline = [255, 0, 0] }
+//
+// Levels are played in order, one per level cleared. Every maze in a pack
+// has to be the same size, as the window is made to fit the first.
+#[derive(Deserialize)]
+struct PackFile {
+    name: String,
+    requires: Option<String>,
+    #[serde(default = "first_level")]
+    repeat_from: usize,
+    levels: Vec<PackFileLevel>,
+}
+
+fn first_level() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+struct PackFileLevel {
+    maze: String,
+    intermission: Option<String>,
+    #[serde(flatten)]
+    rules: LevelRules,
+}
+
+#[derive(Clone)]
+pub struct LevelPack {
+    pub id: String, // The directory or archive name, which progress is kept under
+    pub name: String,
+    pub requires: Option<String>, // The id of the pack to finish first
+    pub levels: Vec<PackLevel>,
+    repeat_from: usize, // Index of the level played after the last one
+}
+
+#[derive(Clone)]
+pub struct PackLevel {
+    pub level: Level, // With the pack's rules for it
+    pub intermission: Option<String>,
+}
+
+impl LevelPack {
+    pub fn load(path: &str) -> Result<LevelPack, String> {
+        let mut source = if path.ends_with(".zip") {
+            PackSource::open_archive(path)?
+        } else {
+            PackSource::Directory(PathBuf::from(path))
+        };
+        let text = source.read(PACK_FILE)?;
+        let file: PackFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        if file.levels.is_empty() {
+            return Err(format!("{}: Pack has no levels", path));
+        }
+        if file.repeat_from == 0 || file.repeat_from > file.levels.len() {
+            return Err(format!(
+                "{}: repeat_from has to be a level from 1 to {}",
+                path,
+                file.levels.len()
+            ));
+        }
+
+        let mut levels: Vec<PackLevel> = Vec::new();
+        for entry in file.levels {
+            let mut level = source.load_maze(&entry.maze)?;
+            if let Some(first) = levels.first() {
+                let size = (level.width(), level.height());
+                if size != (first.level.width(), first.level.height()) {
+                    return Err(format!(
+                        "{}: {} isn't the same size as the first maze",
+                        path, entry.maze
+                    ));
+                }
+            }
+            if let Some(theme) = &entry.rules.theme {
+                theme
+                    .apply(&Theme::ARCADE)
+                    .map_err(|e| format!("{}: {}: {}", path, entry.maze, e))?;
+            }
+            level.rules = entry.rules;
+            levels.push(PackLevel {
+                level,
+                intermission: entry.intermission,
+            });
+        }
+
+        let id = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(LevelPack {
+            id,
+            name: file.name,
+            requires: file.requires,
+            levels,
+            repeat_from: file.repeat_from - 1,
+        })
+    }
+
+    // The pack's level for a level number counted from 1. After the last
+    // one the levels from repeat_from on come round again.
+    pub fn entry(&self, level_number: u32) -> &PackLevel {
+        let index = level_number.max(1) as usize - 1;
+        let count = self.levels.len();
+        if index < count {
+            &self.levels[index]
+        } else {
+            let looped = count - self.repeat_from;
+            &self.levels[self.repeat_from + (index - count) % looped]
+        }
+    }
+
+    // The size of every maze in the pack
+    pub fn size(&self) -> (usize, usize) {
+        let level = &self.levels[0].level;
+        (level.width(), level.height())
+    }
+}
+
+// Every pack in `dir`, sorted by name, with an error for each one that
+// couldn't be loaded
+pub fn load_packs(dir: &str) -> (Vec<LevelPack>, Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.join(PACK_FILE).is_file() || path.extension().is_some_and(|e| e == "zip")
+        })
+        .collect();
+    paths.sort();
+
+    let (mut packs, mut errors) = (Vec::new(), Vec::new());
+    for path in paths {
+        match LevelPack::load(&path.to_string_lossy()) {
+            Ok(pack) => packs.push(pack),
+            Err(e) => errors.push(e),
+        }
+    }
+    (packs, errors)
+}
+
+enum PackSource {
+    Directory(PathBuf),
+    Archive {
+        path: String,
+        archive: zip::ZipArchive<File>,
+        prefix: String, // The directory inside the archive holding pack.toml
+    },
+}
+
+impl PackSource {
+    fn open_archive(path: &str) -> Result<PackSource, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let archive = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", path, e))?;
+        // The pack can be at the top of the archive or in a directory in it
+        let prefix = archive
+            .file_names()
+            .filter_map(|name| name.strip_suffix(PACK_FILE))
+            .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+            .min_by_key(|prefix| prefix.len())
+            .ok_or(format!("{}: No {} in the archive", path, PACK_FILE))?
+            .to_string();
+        Ok(PackSource::Archive {
+            path: path.to_string(),
+            archive,
+            prefix,
+        })
+    }
+
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        match self {
+            PackSource::Directory(dir) => {
+                let path = dir.join(name);
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            PackSource::Archive {
+                path,
+                archive,
+                prefix,
+            } => {
+                let mut file = archive
+                    .by_name(&format!("{}{}", prefix, name))
+                    .map_err(|e| format!("{}: {}: {}", path, name, e))?;
+                let mut text = String::new();
+                file.read_to_string(&mut text)
+                    .map_err(|e| format!("{}: {}: {}", path, name, e))?;
+                Ok(text)
+            }
+        }
+    }
+
+    fn load_maze(&mut self, name: &str) -> Result<Level, String> {
+        match self {
+            PackSource::Directory(dir) => load_level(&dir.join(name).to_string_lossy()),
+            // Tiled maps can name tilesets in other files, which are only
+            // looked for on disk
+            PackSource::Archive { path, .. } if is_tiled_map(name) => Err(format!(
+                "{}: {} is a Tiled map, which only a pack directory can hold",
+                path, name
+            )),
+            PackSource::Archive { .. } => {
+                let text = self.read(name)?;
+                parse_level(&text).map_err(|e| format!("{}: {}", name, e))
+            }
+        }
+    }
+}
+
+// How far each pack has been played, kept between runs so packs and levels
+// stay unlocked
+#[derive(Default, Serialize, Deserialize)]
+pub struct PackProgress {
+    #[serde(default)]
+    reached: BTreeMap<String, u32>, // Furthest level reached in each pack
+}
+
+impl PackProgress {
+    // Nothing reached yet if the file isn't there or can't be read
+    pub fn load(path: &str) -> PackProgress {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return PackProgress::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Could not read {}, starting afresh: {}", path, e);
+            PackProgress::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // The furthest level reached in a pack, 1 for one never played
+    pub fn reached(&self, pack: &LevelPack) -> u32 {
+        self.reached.get(&pack.id).copied().unwrap_or(1)
+    }
+
+    // Notes a level being reached, returning whether it is further than
+    // before
+    pub fn reach(&mut self, pack: &LevelPack, level_number: u32) -> bool {
+        if level_number <= self.reached(pack) {
+            return false;
+        }
+        self.reached.insert(pack.id.clone(), level_number);
+        true
+    }
+
+    // A pack opens once the pack it requires has had every level cleared
+    pub fn is_unlocked(&self, pack: &LevelPack, packs: &[LevelPack]) -> bool {
+        let Some(required) = &pack.requires else {
+            return true;
+        };
+        packs
+            .iter()
+            .find(|other| &other.id == required)
+            .is_some_and(|other| self.reached(other) as usize > other.levels.len())
+    }
+}
@@ -0,0 +1,422 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+use crate::maze::MazeTile;
+
+// Up, left, down, right, the order ties are broken in
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (-1, 0), (0, 1), (1, 0)];
+
+// Who is moving decides which ways are open. The door lets ghosts leaving
+// the house out going up and eyes in going down, and nobody else through.
+// Ghosts only go sideways through the tunnels off the edge of the maze.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Mover {
+    Pacman,
+    Ghost,
+    LeavingGhost,
+    Eyes,
+}
+
+impl Mover {
+    const ALL: [Mover; 4] = [
+        Mover::Pacman,
+        Mover::Ghost,
+        Mover::LeavingGhost,
+        Mover::Eyes,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn can_enter(self, tile: MazeTile, direction: (i32, i32)) -> bool {
+        match tile {
+            MazeTile::Door => match self {
+                Mover::LeavingGhost => direction == (0, -1),
+                Mover::Eyes => direction == (0, 1),
+                _ => false,
+            },
+            tile => !tile.is_wall(),
+        }
+    }
+
+    fn wraps(self, direction: (i32, i32)) -> bool {
+        self == Mover::Pacman || direction.1 == 0
+    }
+}
+
+// How far every tile is from one target tile, for one kind of mover
+pub struct DistanceField {
+    width: usize,
+    distances: Vec<u32>, // u32::MAX where the target can't be reached from
+}
+
+impl DistanceField {
+    pub fn get(&self, tile: (i32, i32)) -> Option<u32> {
+        if tile.0 < 0 || tile.1 < 0 || tile.0 as usize >= self.width {
+            return None;
+        }
+        let distance = *self
+            .distances
+            .get(tile.1 as usize * self.width + tile.0 as usize)?;
+        (distance != u32::MAX).then_some(distance)
+    }
+}
+
+// Shortest paths over the maze. The navigation graph has a node for every
+// tile and an edge for every step a mover can take, tunnels included.
+// Distance fields are worked out the first time they are asked for and
+// kept until the maze changes. Copies share the graph and the fields, so
+// copying a game stays cheap.
+#[derive(Clone)]
+pub struct Pathfinder {
+    graph: Rc<Graph>,
+    fields: RefCell<HashMap<(Mover, usize), Rc<DistanceField>>>,
+}
+
+struct Graph {
+    tiles: Vec<Vec<MazeTile>>, // The maze the graph was built from
+    width: usize,
+    height: usize,
+    edges: [Vec<[Option<usize>; 4]>; 4], // Per mover, per tile, per direction
+    reverse_edges: [Vec<Vec<usize>>; 4], // Per mover, the tiles that step onto each tile
+}
+
+impl Pathfinder {
+    pub fn new(tiles: &[Vec<MazeTile>]) -> Pathfinder {
+        let width = tiles.iter().map(|row| row.len()).max().unwrap_or(0);
+        let height = tiles.len();
+        let tile_at = |col: i32, row: i32| {
+            tiles[row as usize]
+                .get(col as usize)
+                .copied()
+                .unwrap_or(MazeTile::Empty)
+        };
+
+        let mut edges: [Vec<[Option<usize>; 4]>; 4] = Default::default();
+        let mut reverse_edges: [Vec<Vec<usize>>; 4] = Default::default();
+        for mover in Mover::ALL {
+            let mut mover_edges = vec![[None; 4]; width * height];
+            let mut mover_reverse = vec![Vec::new(); width * height];
+            for row in 0..height as i32 {
+                for col in 0..width as i32 {
+                    let from = row as usize * width + col as usize;
+                    for (d, direction) in DIRECTIONS.into_iter().enumerate() {
+                        let next = (col + direction.0, row + direction.1);
+                        let inside = next.0 >= 0
+                            && next.1 >= 0
+                            && next.0 < width as i32
+                            && next.1 < height as i32;
+                        if !inside && !mover.wraps(direction) {
+                            continue;
+                        }
+                        // Off the edge comes back on the other side
+                        let (to_col, to_row) = (
+                            next.0.rem_euclid(width as i32),
+                            next.1.rem_euclid(height as i32),
+                        );
+                        if mover.can_enter(tile_at(to_col, to_row), direction) {
+                            let to = to_row as usize * width + to_col as usize;
+                            mover_edges[from][d] = Some(to);
+                            mover_reverse[to].push(from);
+                        }
+                    }
+                }
+            }
+            edges[mover.index()] = mover_edges;
+            reverse_edges[mover.index()] = mover_reverse;
+        }
+
+        Pathfinder {
+            graph: Rc::new(Graph {
+                tiles: tiles.to_vec(),
+                width,
+                height,
+                edges,
+                reverse_edges,
+            }),
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Rebuilds the graph, dropping every cached distance field, if the maze
+    // isn't the one the graph was built from
+    pub fn update(&mut self, tiles: &[Vec<MazeTile>]) {
+        if self.graph.tiles != tiles {
+            *self = Pathfinder::new(tiles);
+        }
+    }
+
+    fn index(&self, tile: (i32, i32)) -> Option<usize> {
+        let inside = tile.0 >= 0
+            && tile.1 >= 0
+            && (tile.0 as usize) < self.graph.width
+            && (tile.1 as usize) < self.graph.height;
+        inside.then(|| tile.1 as usize * self.graph.width + tile.0 as usize)
+    }
+
+    fn tile(&self, index: usize) -> (i32, i32) {
+        (
+            (index % self.graph.width) as i32,
+            (index / self.graph.width) as i32,
+        )
+    }
+
+    // The steps `mover` can take from `tile`, as (direction, tile reached)
+    pub fn neighbours(
+        &self,
+        mover: Mover,
+        tile: (i32, i32),
+    ) -> impl Iterator<Item = ((i32, i32), (i32, i32))> + '_ {
+        let edges = self
+            .index(tile)
+            .map(|index| self.graph.edges[mover.index()][index])
+            .unwrap_or([None; 4]);
+        DIRECTIONS
+            .into_iter()
+            .zip(edges)
+            .filter_map(move |(direction, to)| Some((direction, self.tile(to?))))
+    }
+
+    // Breadth first search for the nearest tile where `is_goal` holds. The
+    // path leaves out `from` and ends on the goal.
+    pub fn bfs(
+        &self,
+        mover: Mover,
+        from: (i32, i32),
+        is_goal: impl Fn((i32, i32)) -> bool,
+    ) -> Option<Vec<(i32, i32)>> {
+        let start = self.index(from)?;
+        let edges = &self.graph.edges[mover.index()];
+        let mut came_from = vec![usize::MAX; edges.len()];
+        came_from[start] = start;
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            if is_goal(self.tile(index)) {
+                return Some(self.path(&came_from, start, index));
+            }
+            for to in edges[index].into_iter().flatten() {
+                if came_from[to] == usize::MAX {
+                    came_from[to] = index;
+                    queue.push_back(to);
+                }
+            }
+        }
+        None
+    }
+
+    // A* from one tile to another, guided by the distance as the crow flies
+    // allowing for the way round through the tunnels. The path leaves out
+    // `from` and ends on `to`.
+    pub fn astar(&self, mover: Mover, from: (i32, i32), to: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        let (start, goal) = (self.index(from)?, self.index(to)?);
+        let edges = &self.graph.edges[mover.index()];
+        let estimate = |index: usize| {
+            let (col, row) = self.tile(index);
+            let dx = (col - to.0).unsigned_abs() as usize;
+            let dy = (row - to.1).unsigned_abs() as usize;
+            (dx.min(self.graph.width - dx) + dy.min(self.graph.height - dy)) as u32
+        };
+
+        let mut came_from = vec![usize::MAX; edges.len()];
+        let mut cost = vec![u32::MAX; edges.len()];
+        came_from[start] = start;
+        cost[start] = 0;
+        let mut open = BinaryHeap::from([Reverse((estimate(start), start))]);
+        while let Some(Reverse((_, index))) = open.pop() {
+            if index == goal {
+                return Some(self.path(&came_from, start, goal));
+            }
+            for next in edges[index].into_iter().flatten() {
+                let next_cost = cost[index] + 1;
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    came_from[next] = index;
+                    open.push(Reverse((next_cost + estimate(next), next)));
+                }
+            }
+        }
+        None
+    }
+
+    // Every tile `mover` can get to from `from`, `from` included
+    pub fn region(&self, mover: Mover, from: (i32, i32)) -> HashSet<(i32, i32)> {
+        let Some(start) = self.index(from) else {
+            return HashSet::new();
+        };
+        let edges = &self.graph.edges[mover.index()];
+        let mut seen = vec![false; edges.len()];
+        seen[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            for to in edges[index].into_iter().flatten() {
+                if !seen[to] {
+                    seen[to] = true;
+                    queue.push_back(to);
+                }
+            }
+        }
+        (0..edges.len())
+            .filter(|&index| seen[index])
+            .map(|index| self.tile(index))
+            .collect()
+    }
+
+    fn path(&self, came_from: &[usize], start: usize, end: usize) -> Vec<(i32, i32)> {
+        let mut path = Vec::new();
+        let mut index = end;
+        while index != start {
+            path.push(self.tile(index));
+            index = came_from[index];
+        }
+        path.reverse();
+        path
+    }
+
+    // How far every tile is from `to`. Asking for one field per target
+    // answers all-pairs queries as they come up.
+    pub fn distances_to(&self, mover: Mover, to: (i32, i32)) -> Option<Rc<DistanceField>> {
+        let goal = self.index(to)?;
+        if let Some(field) = self.fields.borrow().get(&(mover, goal)) {
+            return Some(field.clone());
+        }
+
+        // Searching backwards from the target, as the door is one-way
+        let reverse_edges = &self.graph.reverse_edges[mover.index()];
+        let mut distances = vec![u32::MAX; reverse_edges.len()];
+        distances[goal] = 0;
+        let mut queue = VecDeque::from([goal]);
+        while let Some(index) = queue.pop_front() {
+            for &from in &reverse_edges[index] {
+                if distances[from] == u32::MAX {
+                    distances[from] = distances[index] + 1;
+                    queue.push_back(from);
+                }
+            }
+        }
+
+        let field = Rc::new(DistanceField {
+            width: self.graph.width,
+            distances,
+        });
+        self.fields
+            .borrow_mut()
+            .insert((mover, goal), field.clone());
+        Some(field)
+    }
+
+    pub fn distance(&self, mover: Mover, from: (i32, i32), to: (i32, i32)) -> Option<u32> {
+        self.distances_to(mover, to)?.get(from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{parse_level, Level, CLASSIC_LEVEL};
+
+    fn classic() -> Level {
+        parse_level(CLASSIC_LEVEL).unwrap()
+    }
+
+    fn to_tile(tile: (usize, usize)) -> (i32, i32) {
+        (tile.0 as i32, tile.1 as i32)
+    }
+
+    // Every step of a path is to a next-door tile, or across the maze
+    // through a tunnel
+    fn assert_connected(from: (i32, i32), path: &[(i32, i32)], width: i32) {
+        let mut previous = from;
+        for &tile in path {
+            let dx = (tile.0 - previous.0).abs();
+            let dy = (tile.1 - previous.1).abs();
+            assert!(
+                (dx + dy == 1) || (dy == 0 && dx == width - 1),
+                "{:?} to {:?} isn't a step",
+                previous,
+                tile
+            );
+            previous = tile;
+        }
+    }
+
+    #[test]
+    fn bfs_finds_the_nearest_goal() {
+        let level = classic();
+        let paths = Pathfinder::new(&level.tiles);
+        let start = to_tile(level.pacman_start);
+        let is_pellet = |tile: (i32, i32)| {
+            level
+                .pellets
+                .contains_key(&(tile.0 as usize, tile.1 as usize))
+        };
+        let path = paths.bfs(Mover::Pacman, start, is_pellet).unwrap();
+
+        assert_ne!(path.first(), Some(&start));
+        assert!(is_pellet(*path.last().unwrap()));
+        assert_connected(start, &path, level.width() as i32);
+        let nearest = level
+            .pellets
+            .keys()
+            .filter_map(|&pellet| paths.distance(Mover::Pacman, start, to_tile(pellet)))
+            .min()
+            .unwrap();
+        assert_eq!(path.len() as u32, nearest);
+    }
+
+    #[test]
+    fn astar_is_as_short_as_the_distance_field() {
+        let level = classic();
+        let paths = Pathfinder::new(&level.tiles);
+        let start = to_tile(level.pacman_start);
+        for goal in [(1, 1), (26, 1), (1, 29), (26, 29), (0, 14), (6, 14)] {
+            let path = paths.astar(Mover::Pacman, start, goal).unwrap();
+            assert_eq!(path.last(), Some(&goal));
+            assert_connected(start, &path, level.width() as i32);
+            assert_eq!(
+                Some(path.len() as u32),
+                paths.distance(Mover::Pacman, start, goal)
+            );
+        }
+    }
+
+    #[test]
+    fn tunnels_join_the_sides() {
+        let paths = Pathfinder::new(&classic().tiles);
+        for mover in [Mover::Pacman, Mover::Ghost] {
+            assert_eq!(paths.distance(mover, (0, 14), (27, 14)), Some(1));
+            assert_eq!(paths.distance(mover, (27, 14), (0, 14)), Some(1));
+        }
+    }
+
+    #[test]
+    fn the_door_is_one_way() {
+        let level = classic();
+        let paths = Pathfinder::new(&level.tiles);
+        let inside = to_tile(level.ghost_starts[1]);
+        let exit = to_tile(level.house_exit);
+
+        assert_eq!(paths.distance(Mover::Pacman, exit, inside), None);
+        assert_eq!(paths.distance(Mover::Ghost, exit, inside), None);
+        assert!(paths.distance(Mover::Eyes, exit, inside).is_some());
+        assert!(paths.distance(Mover::LeavingGhost, inside, exit).is_some());
+        assert_eq!(paths.distance(Mover::Eyes, inside, exit), None);
+    }
+
+    #[test]
+    fn update_drops_fields_for_the_old_maze() {
+        let mut level = classic();
+        let mut paths = Pathfinder::new(&level.tiles);
+        let (from, to) = ((1, 1), (6, 1));
+        assert_eq!(paths.distance(Mover::Pacman, from, to), Some(5));
+
+        level.tiles[1][3] = MazeTile::WallPillar;
+        paths.update(&level.tiles);
+        let around = paths.distance(Mover::Pacman, from, to).unwrap();
+        assert!(around > 5);
+        let path = paths.astar(Mover::Pacman, from, to).unwrap();
+        assert_eq!(path.len() as u32, around);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+use crate::game::{Game, TICKS_PER_SECOND};
+
+// Simulation speeds to pick from, as game ticks per frame
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 2;
+const HISTORY_TICKS: usize = 10 * TICKS_PER_SECOND as usize; // How far back rewinding goes
+
+// Developer controls over how the game runs: freezing it while it is still
+// drawn, stepping one tick at a time, slow motion and fast forward, and
+// rewinding through the last few seconds of play. Only the game is rewound,
+// the scent map, achievements, score popups and sounds carry on from where
+// they were. Off unless [debug] playback_controls is set.
+pub struct Playback {
+    pub frozen: bool,
+    speed: usize,            // Index into SPEEDS
+    ticks_owed: f32,         // Fractions of a tick carried over at slow speeds
+    steps: u32,              // Single ticks asked for while frozen
+    history: VecDeque<Game>, // The game before each of the latest ticks, oldest first
+}
+
+impl Playback {
+    pub fn new() -> Playback {
+        Playback {
+            frozen: false,
+            speed: NORMAL_SPEED,
+            ticks_owed: 0.0,
+            steps: 0,
+            history: VecDeque::new(),
+        }
+    }
+
+    // Forgets the history, for a game that starts afresh
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.ticks_owed = 0.0;
+        self.steps = 0;
+    }
+
+    pub fn toggle_frozen(&mut self) {
+        self.frozen = !self.frozen;
+        self.steps = 0;
+    }
+
+    // One tick on, freezing the game first if it is running
+    pub fn step(&mut self) {
+        self.frozen = true;
+        self.steps += 1;
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    // Whether the game is running as it would without these controls
+    pub fn is_normal(&self) -> bool {
+        !self.frozen && self.speed == NORMAL_SPEED
+    }
+
+    // How many ticks to run this frame, `game_speed` being the player's
+    // chosen speed that the playback speed is on top of
+    pub fn ticks_this_frame(&mut self, game_speed: f32) -> u32 {
+        if self.frozen {
+            return std::mem::take(&mut self.steps);
+        }
+        self.ticks_owed += SPEEDS[self.speed] * game_speed;
+        let ticks = self.ticks_owed as u32;
+        self.ticks_owed -= ticks as f32;
+        ticks
+    }
+
+    // Keeps the game as it is before it is ticked, dropping the oldest
+    // snapshot once there are enough
+    pub fn record(&mut self, game: &Game) {
+        if self.history.len() == HISTORY_TICKS {
+            self.history.pop_front();
+        }
+        self.history.push_back(game.clone());
+    }
+
+    // Freezes the game and takes it back a tick, None once the history has
+    // run out
+    pub fn rewind(&mut self) -> Option<Game> {
+        self.frozen = true;
+        self.steps = 0;
+        self.history.pop_back()
+    }
+
+    // What to show on screen while the game isn't running normally
+    pub fn status(&self) -> Option<String> {
+        let speed = SPEEDS[self.speed];
+        match (self.frozen, self.speed == NORMAL_SPEED) {
+            (true, _) => Some(format!("FROZEN  {} TICKS TO REWIND", self.history.len())),
+            (false, true) => None,
+            (false, false) => Some(format!("SPEED x{}", speed)),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use sdl2::pixels::Color;
+use std::time::{Duration, Instant};
+
+use crate::config::{EffectConfig, PostProcessingConfig};
+
+// An effect that keeps going over its budget for this many frames in a row
+// is switched off, so a slow machine degrades to a plain picture instead of
+// dropping frames
+const OVER_BUDGET_FRAME_LIMIT: u32 = 60;
+
+const BYTES_PER_PIXEL: usize = 4; // RGBA32: red, green, blue, alpha
+
+const GLOW_SCALE: usize = 4; // The glow is worked out at 1/4 of the resolution
+const GLOW_GAIN: u32 = 2;
+const GLOW_TOLERANCE: u32 = 96; // Summed over the channels, how far a pixel can be from a glowing colour
+
+pub struct Effect {
+    pub name: &'static str,
+    pub enabled: bool,
+    budget: Duration,
+    over_budget_frames: u32,
+}
+
+impl Effect {
+    fn new(name: &'static str, config: EffectConfig, default_budget_ms: f32) -> Effect {
+        let budget_ms = config.budget_ms.unwrap_or(default_budget_ms);
+        Effect {
+            name,
+            enabled: config.enabled,
+            budget: Duration::from_secs_f32(budget_ms.max(0.0) / 1000.0),
+            over_budget_frames: 0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.over_budget_frames = 0;
+    }
+
+    fn run(&mut self, effect: impl FnOnce()) {
+        if !self.enabled {
+            return;
+        }
+        let start = Instant::now();
+        effect();
+        if start.elapsed() > self.budget {
+            self.over_budget_frames += 1;
+            if self.over_budget_frames >= OVER_BUDGET_FRAME_LIMIT {
+                eprintln!(
+                    "Turning off {}: over its {:.1} ms budget for {} frames",
+                    self.name,
+                    self.budget.as_secs_f32() * 1000.0,
+                    OVER_BUDGET_FRAME_LIMIT
+                );
+                self.enabled = false;
+                self.over_budget_frames = 0;
+            }
+        } else {
+            self.over_budget_frames = 0;
+        }
+    }
+}
+
+// Retro CRT effects applied on the CPU to the finished playfield pixels
+pub struct PostProcessor {
+    pub scanlines: Effect,
+    pub curvature: Effect,
+    pub glow: Effect,
+    size: (usize, usize),
+    curvature_map: Vec<Option<usize>>, // Source pixel for every destination pixel
+    glow_colors: Vec<Color>,           // Only pixels drawn in these glow
+    glow_layer: Vec<u8>,
+    glow_scratch: Vec<u8>,
+    curvature_scratch: Vec<u8>,
+}
+
+impl PostProcessor {
+    pub fn new(config: &PostProcessingConfig, size: (u32, u32)) -> PostProcessor {
+        let size = (size.0 as usize, size.1 as usize);
+        let glow_size = glow_layer_size(size);
+        PostProcessor {
+            scanlines: Effect::new("scanlines", config.scanlines, 2.0),
+            curvature: Effect::new("curvature", config.curvature, 4.0),
+            glow: Effect::new("glow", config.glow, 8.0),
+            size,
+            curvature_map: build_curvature_map(size, 0.08),
+            glow_colors: Vec::new(),
+            glow_layer: vec![0; glow_size.0 * glow_size.1 * 3],
+            glow_scratch: vec![0; glow_size.0 * glow_size.1 * 3],
+            curvature_scratch: vec![0; size.0 * size.1 * BYTES_PER_PIXEL],
+        }
+    }
+
+    // The colours the glow picks out, which should be those of the walls
+    // and Pac-Man as they are drawn now
+    pub fn set_glow_colors(&mut self, colors: &[Color]) {
+        self.glow_colors.clear();
+        self.glow_colors.extend_from_slice(colors);
+    }
+
+    pub fn any_enabled(&self) -> bool {
+        self.scanlines.enabled || self.curvature.enabled || self.glow.enabled
+    }
+
+    // Pixels are RGBA32 rows without padding, at the size given to new()
+    pub fn apply(&mut self, pixels: &mut [u8]) {
+        let size = self.size;
+
+        // Glow first so the scanlines also cut through the halo, then bend the result
+        let (glow_layer, glow_scratch) = (&mut self.glow_layer, &mut self.glow_scratch);
+        let glow_colors = &self.glow_colors;
+        self.glow
+            .run(|| apply_glow(pixels, size, glow_colors, glow_layer, glow_scratch));
+        self.scanlines.run(|| apply_scanlines(pixels, size));
+        let (map, scratch) = (&self.curvature_map, &mut self.curvature_scratch);
+        self.curvature.run(|| apply_curvature(pixels, map, scratch));
+    }
+}
+
+fn apply_scanlines(pixels: &mut [u8], size: (usize, usize)) {
+    let row_bytes = size.0 * BYTES_PER_PIXEL;
+    for row in pixels.chunks_exact_mut(row_bytes).skip(1).step_by(2) {
+        for pixel in row.chunks_exact_mut(BYTES_PER_PIXEL) {
+            // Darken the colour channels to 60%, leave alpha alone
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as u16 * 3 / 5) as u8;
+            }
+        }
+    }
+}
+
+// Slight barrel distortion: every destination pixel samples the source a
+// little further out the further it is from the centre. The mapping only
+// depends on the size, so it is worked out once.
+fn build_curvature_map(size: (usize, usize), strength: f32) -> Vec<Option<usize>> {
+    let (width, height) = size;
+    let mut map = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            // -1.0..1.0 across the picture
+            let nx = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let ny = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+            let factor = 1.0 + strength * (nx * nx + ny * ny);
+            let sx = ((nx * factor + 1.0) / 2.0 * width as f32).floor();
+            let sy = ((ny * factor + 1.0) / 2.0 * height as f32).floor();
+
+            let inside = sx >= 0.0 && sy >= 0.0 && sx < width as f32 && sy < height as f32;
+            map.push(inside.then(|| sy as usize * width + sx as usize));
+        }
+    }
+    map
+}
+
+fn apply_curvature(pixels: &mut [u8], map: &[Option<usize>], scratch: &mut [u8]) {
+    scratch.copy_from_slice(pixels);
+    for (dst, src) in pixels.chunks_exact_mut(BYTES_PER_PIXEL).zip(map) {
+        match src {
+            Some(src) => {
+                let offset = src * BYTES_PER_PIXEL;
+                dst.copy_from_slice(&scratch[offset..offset + BYTES_PER_PIXEL]);
+            }
+            None => dst[..3].fill(0), // Outside the curved screen
+        }
+    }
+}
+
+// Phosphor glow: pixels in the glowing colours, the walls and Pac-Man,
+// bleed into their surroundings. Other colours, like the grid and HUD text,
+// don't. The halo is soft anyway, so it is worked out at a fraction of the
+// resolution and smoothly stretched back up.
+fn apply_glow(
+    pixels: &mut [u8],
+    size: (usize, usize),
+    colors: &[Color],
+    layer: &mut [u8],
+    scratch: &mut [u8],
+) {
+    let (width, height) = size;
+    let (glow_width, glow_height) = glow_layer_size(size);
+
+    // Average the glowing colours of each block into the small layer
+    layer.fill(0);
+    let mut sums = vec![0u32; glow_width * 3];
+    for glow_y in 0..glow_height {
+        sums.fill(0);
+        for y in glow_y * GLOW_SCALE..((glow_y + 1) * GLOW_SCALE).min(height) {
+            let row = &pixels[y * width * BYTES_PER_PIXEL..(y + 1) * width * BYTES_PER_PIXEL];
+            for (x, pixel) in row.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+                let (r, g, b) = (pixel[0], pixel[1], pixel[2]);
+                let glows = colors.iter().any(|color| {
+                    r.abs_diff(color.r) as u32
+                        + g.abs_diff(color.g) as u32
+                        + b.abs_diff(color.b) as u32
+                        <= GLOW_TOLERANCE
+                });
+                if glows {
+                    let sum = &mut sums[x / GLOW_SCALE * 3..x / GLOW_SCALE * 3 + 3];
+                    sum[0] += r as u32;
+                    sum[1] += g as u32;
+                    sum[2] += b as u32;
+                }
+            }
+        }
+        let block_area = (GLOW_SCALE * GLOW_SCALE) as u32;
+        for (glow, sum) in layer[glow_y * glow_width * 3..(glow_y + 1) * glow_width * 3]
+            .iter_mut()
+            .zip(&sums)
+        {
+            *glow = (sum / block_area).min(255) as u8;
+        }
+    }
+
+    // Two box blurs are close enough to a gaussian
+    for _ in 0..2 {
+        box_blur(layer, scratch, (glow_width, glow_height), 2);
+    }
+
+    // Add the halo back on top, bilinearly sampled so the blocks don't show.
+    // Weights are in 1/256ths.
+    let sample = |i: usize, scaled_len: usize| {
+        let position = ((i * 256 + 128) / GLOW_SCALE).saturating_sub(128);
+        let first = (position / 256).min(scaled_len - 1);
+        (
+            first,
+            (first + 1).min(scaled_len - 1),
+            (position % 256) as u32,
+        )
+    };
+    let columns: Vec<(usize, usize, u32)> = (0..width).map(|x| sample(x, glow_width)).collect();
+    let glow_row_len = glow_width * 3;
+    let mut blended_row = vec![0u32; glow_row_len];
+    for (y, row) in pixels.chunks_exact_mut(width * BYTES_PER_PIXEL).enumerate() {
+        let (y0, y1, fy) = sample(y, glow_height);
+        let top = &layer[y0 * glow_row_len..(y0 + 1) * glow_row_len];
+        let bottom = &layer[y1 * glow_row_len..(y1 + 1) * glow_row_len];
+        if top.iter().chain(bottom).all(|&glow| glow == 0) {
+            continue;
+        }
+        for ((blended, &top), &bottom) in blended_row.iter_mut().zip(top).zip(bottom) {
+            *blended = top as u32 * (256 - fy) + bottom as u32 * fy;
+        }
+        for (pixel, &(x0, x1, fx)) in row.chunks_exact_mut(BYTES_PER_PIXEL).zip(&columns) {
+            for (channel, value) in pixel[..3].iter_mut().enumerate() {
+                let glow =
+                    blended_row[x0 * 3 + channel] * (256 - fx) + blended_row[x1 * 3 + channel] * fx;
+                let glow = ((glow >> 16) * GLOW_GAIN).min(255) as u8;
+                *value = value.saturating_add(glow);
+            }
+        }
+    }
+}
+
+fn glow_layer_size(size: (usize, usize)) -> (usize, usize) {
+    (size.0.div_ceil(GLOW_SCALE), size.1.div_ceil(GLOW_SCALE))
+}
+
+// Separable box blur over an RGB layer, horizontal into scratch and then
+// vertical back into the layer
+fn box_blur(layer: &mut [u8], scratch: &mut [u8], size: (usize, usize), radius: usize) {
+    let (width, height) = size;
+    let window = (radius * 2 + 1) as u32;
+    let clamp = |i: isize, len: usize| i.clamp(0, len as isize - 1) as usize;
+
+    for y in 0..height {
+        for channel in 0..3 {
+            let at = |x: usize| (y * width + x) * 3 + channel;
+            let mut sum: u32 = (-(radius as isize)..=radius as isize)
+                .map(|dx| layer[at(clamp(dx, width))] as u32)
+                .sum();
+            for x in 0..width {
+                scratch[at(x)] = (sum / window) as u8;
+                let leaving = clamp(x as isize - radius as isize, width);
+                let entering = clamp(x as isize + radius as isize + 1, width);
+                sum = sum + layer[at(entering)] as u32 - layer[at(leaving)] as u32;
+            }
+        }
+    }
+
+    // Walk the rows in order and keep a running sum per column, which is
+    // much kinder to the cache than walking down each column
+    let row_len = width * 3;
+    let row = |y: isize| clamp(y, height) * row_len;
+    let mut sums = vec![0u32; row_len];
+    for dy in -(radius as isize)..=radius as isize {
+        let start = row(dy);
+        for (sum, value) in sums.iter_mut().zip(&scratch[start..start + row_len]) {
+            *sum += *value as u32;
+        }
+    }
+    for y in 0..height {
+        let leaving = row(y as isize - radius as isize);
+        let entering = row(y as isize + radius as isize + 1);
+        for (i, sum) in sums.iter_mut().enumerate() {
+            layer[y * row_len + i] = (*sum / window) as u8;
+            *sum = *sum + scratch[entering + i] as u32 - scratch[leaving + i] as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (usize, usize) = (32, 8);
+
+    // A black picture with a 4x4 block of `color` at x
+    fn picture(blocks: &[(usize, Color)]) -> Vec<u8> {
+        let mut pixels = vec![0; SIZE.0 * SIZE.1 * BYTES_PER_PIXEL];
+        for &(left, color) in blocks {
+            for y in 2..6 {
+                for x in left..left + 4 {
+                    let i = (y * SIZE.0 + x) * BYTES_PER_PIXEL;
+                    pixels[i..i + 3].copy_from_slice(&[color.r, color.g, color.b]);
+                }
+            }
+        }
+        pixels
+    }
+
+    fn glow(pixels: &mut [u8], colors: &[Color]) {
+        let glow_size = glow_layer_size(SIZE);
+        let mut layer = vec![0; glow_size.0 * glow_size.1 * 3];
+        let mut scratch = layer.clone();
+        apply_glow(pixels, SIZE, colors, &mut layer, &mut scratch);
+    }
+
+    fn brightness(pixels: &[u8], x: usize, y: usize) -> u32 {
+        let i = (y * SIZE.0 + x) * BYTES_PER_PIXEL;
+        pixels[i..i + 3].iter().map(|&channel| channel as u32).sum()
+    }
+
+    #[test]
+    fn only_the_glowing_colours_glow() {
+        let pink = Color::RGB(255, 0, 220);
+        let white = Color::RGB(255, 255, 255);
+        let mut pixels = picture(&[(4, pink), (24, white)]);
+        glow(&mut pixels, &[pink]);
+        // Just below each block
+        assert!(brightness(&pixels, 6, 7) > 0);
+        assert_eq!(brightness(&pixels, 26, 7), 0);
+    }
+
+    #[test]
+    fn nothing_glows_without_colours() {
+        let mut pixels = picture(&[(4, Color::RGB(0, 0, 255))]);
+        let before = pixels.clone();
+        glow(&mut pixels, &[]);
+        assert_eq!(pixels, before);
+    }
+}
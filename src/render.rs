@@ -0,0 +1,503 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::WindowCanvas;
+use std::time::Duration;
+
+use crate::game::{Fruit, Game, Ghost, GhostName, GhostState, Phase, TICKS_PER_SECOND};
+use crate::level::Pellet;
+use crate::sprites::SpriteAtlas;
+use crate::theme::{blend, GhostMarkers, Theme};
+
+// Drawing for everything that moves or gets eaten. Each object uses its
+// sprite when the atlas has one and is drawn procedurally otherwise.
+
+// Suffix used by per-direction sprite animations
+pub fn direction_name(direction: (f32, f32)) -> &'static str {
+    if direction.0 == -1.0 {
+        "left"
+    } else if direction.1 == -1.0 {
+        "up"
+    } else if direction.1 == 1.0 {
+        "down"
+    } else {
+        "right" // Same default as draw_pacman
+    }
+}
+
+// Square of `size` centred on `center`
+fn centred_rect(center: (f32, f32), size: f32) -> Rect {
+    Rect::new(
+        (center.0 - size / 2.0) as i32,
+        (center.1 - size / 2.0) as i32,
+        size as u32,
+        size as u32,
+    )
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_secs_f32(ticks as f32 / TICKS_PER_SECOND as f32)
+}
+
+pub fn draw_pacman(
+    canvas: &mut WindowCanvas,
+    center: (f32, f32),
+    radius: f32,
+    mouth_angle: f32,
+    player_direction: (f32, f32),
+    color: Color,
+) -> Result<(), String> {
+    let (cx, cy) = center;
+    let r = radius as i32;
+
+    // Helper function to compute minimal angle difference
+    fn angle_diff(a: f32, b: f32) -> f32 {
+        let mut diff = a - b;
+        while diff < -180.0 {
+            diff += 360.0;
+        }
+        while diff > 180.0 {
+            diff -= 360.0;
+        }
+        diff.abs()
+    }
+
+    // Determine the desired angle based on player direction
+    let desired_angle = if player_direction.0 == 1.0 {
+        0.0
+    } else if player_direction.0 == -1.0 {
+        180.0
+    } else if player_direction.1 == -1.0 {
+        -90.0
+    } else if player_direction.1 == 1.0 {
+        90.0
+    } else {
+        0.0 // Default to facing right if no direction
+    };
+
+    for w in 0..r * 2 {
+        for h in 0..r * 2 {
+            let dx = w - r; // Horizontal offset from center
+            let dy = h - r; // Vertical offset from center
+
+            // Convert dx and dy to få2
+            let dx_f32 = dx as f32;
+            let dy_f32 = dy as f32;
+
+            // Compute angle from positive x-axis to point (dx, dy)
+            let angle = dy_f32.atan2(dx_f32).to_degrees();
+            let delta_angle = angle_diff(angle, desired_angle);
+
+            // Check if the point is outside the mouth's opening
+            if dx * dx + dy * dy <= r * r && delta_angle > mouth_angle / 2.0 {
+                canvas.set_draw_color(color);
+                canvas.draw_point((cx as i32 + dx, cy as i32 + dy))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Pac-Man as he is in the current phase: chomping, or shrinking away when he dies
+pub fn draw_player(
+    canvas: &mut WindowCanvas,
+    game: &Game,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let pacman = &game.pacman;
+    let dst = centred_rect(pacman.pos, tile_size);
+    let radius = tile_size / 2.0;
+
+    if let Phase::Dying(ticks_left) = game.phase {
+        let ticks = (crate::game::DEATH_TICKS - ticks_left) as u64;
+        if let Some(atlas) = sprite_atlas {
+            if atlas.draw_animation(canvas, "pacman_death", ticks_to_duration(ticks), dst)? {
+                return Ok(());
+            }
+        }
+        // The mouth opens upwards until nothing is left
+        let progress = ticks as f32 / crate::game::DEATH_TICKS as f32;
+        return draw_outlined_pacman(
+            canvas,
+            pacman.pos,
+            radius,
+            progress * 360.0,
+            (0.0, -1.0),
+            theme,
+        );
+    }
+
+    if let Some(atlas) = sprite_atlas {
+        let name = format!("pacman_chomp_{}", direction_name(pacman.direction));
+        if atlas.draw_animation(canvas, &name, elapsed, dst)? {
+            return Ok(());
+        }
+    }
+    draw_outlined_pacman(
+        canvas,
+        pacman.pos,
+        radius,
+        pacman.mouth_angle,
+        pacman.direction,
+        theme,
+    )
+}
+
+// With thick outlines on, Pac-Man is drawn over a bigger one in a colour
+// that stands out from the background
+fn draw_outlined_pacman(
+    canvas: &mut WindowCanvas,
+    center: (f32, f32),
+    radius: f32,
+    mouth_angle: f32,
+    direction: (f32, f32),
+    theme: &Theme,
+) -> Result<(), String> {
+    if theme.thick_outlines {
+        let outline = Theme::contrasting(theme.background);
+        let bigger = radius + outline_width(radius);
+        draw_pacman(canvas, center, bigger, mouth_angle, direction, outline)?;
+    }
+    draw_pacman(canvas, center, radius, mouth_angle, direction, theme.pacman)
+}
+
+fn outline_width(radius: f32) -> f32 {
+    (radius / 4.0).max(2.0)
+}
+
+pub fn draw_pellets(
+    canvas: &mut WindowCanvas,
+    game: &Game,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    color: Color,
+) -> Result<(), String> {
+    // Energizers blink while the game is on
+    let energizers_visible = game.phase != Phase::Playing || (game.tick / 10).is_multiple_of(2);
+    let dot_size = (tile_size / 8.0).max(2.0);
+
+    let mut dots = Vec::new();
+    let mut energizer_points = Vec::new();
+    for (&(col, row), pellet) in &game.pellets {
+        let center = (
+            (col as f32 + 0.5) * tile_size,
+            (row as f32 + 0.5) * tile_size,
+        );
+        let (frame, size) = match pellet {
+            Pellet::Dot => ("dot", dot_size),
+            Pellet::Energizer if energizers_visible => ("energizer", tile_size * 0.75),
+            Pellet::Energizer => continue,
+        };
+        if let Some(atlas) = sprite_atlas {
+            if atlas.draw_frame(canvas, frame, centred_rect(center, tile_size))? {
+                continue;
+            }
+        }
+        match pellet {
+            Pellet::Dot => dots.push(centred_rect(center, size)),
+            Pellet::Energizer => add_disc_points(&mut energizer_points, center, size / 2.0),
+        }
+    }
+
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&dots)?;
+    canvas.draw_points(&energizer_points[..])
+}
+
+fn add_disc_points(points: &mut Vec<Point>, center: (f32, f32), radius: f32) {
+    let r = radius.ceil() as i32;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let (x, y) = (dx as f32 + 0.5, dy as f32 + 0.5);
+            if x * x + y * y <= radius * radius {
+                points.push(Point::new(center.0 as i32 + dx, center.1 as i32 + dy));
+            }
+        }
+    }
+}
+
+pub fn fruit_color(fruit: Fruit) -> Color {
+    match fruit {
+        Fruit::Cherry => Color::RGB(255, 0, 0),
+        Fruit::Strawberry => Color::RGB(255, 0, 80),
+        Fruit::Orange => Color::RGB(255, 160, 0),
+        Fruit::Apple => Color::RGB(220, 0, 0),
+        Fruit::Melon => Color::RGB(0, 200, 60),
+        Fruit::Galaxian => Color::RGB(255, 255, 0),
+        Fruit::Bell => Color::RGB(255, 220, 0),
+        Fruit::Key => Color::RGB(100, 200, 255),
+    }
+}
+
+pub fn draw_fruit(
+    canvas: &mut WindowCanvas,
+    fruit: Fruit,
+    center: (f32, f32),
+    size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+) -> Result<(), String> {
+    if let Some(atlas) = sprite_atlas {
+        let name = format!("fruit_{}", fruit.name());
+        if atlas.draw_frame(canvas, &name, centred_rect(center, size))? {
+            return Ok(());
+        }
+    }
+    // A round fruit with a green stalk
+    let mut points = Vec::new();
+    add_disc_points(&mut points, (center.0, center.1 + size * 0.1), size * 0.35);
+    canvas.set_draw_color(fruit_color(fruit));
+    canvas.draw_points(&points[..])?;
+    canvas.set_draw_color(Color::RGB(0, 160, 0));
+    canvas.fill_rect(Rect::new(
+        center.0 as i32,
+        (center.1 - size * 0.4) as i32,
+        (size / 12.0).max(1.0) as u32,
+        (size * 0.25) as u32,
+    ))
+}
+
+pub fn draw_ghosts(
+    canvas: &mut WindowCanvas,
+    game: &Game,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let flashing = game.frightened_flashing() && !theme.reduced_flashing;
+    for ghost in &game.ghosts {
+        // Without the flashing, frightened ghosts fade back to their own
+        // colour as they recover
+        let mut look = *theme;
+        if let Some(amount) = game.frightened_ending().filter(|_| theme.reduced_flashing) {
+            look.frightened = blend(theme.frightened, theme.ghost(ghost.name), amount);
+        }
+        draw_ghost(
+            canvas,
+            ghost,
+            flashing,
+            game.tick,
+            tile_size,
+            sprite_atlas,
+            elapsed,
+            &look,
+        )?;
+    }
+    Ok(())
+}
+
+// The walls as they are drawn: flashing once the level is cleared, or with
+// reduced flashing turning over to the flash colour bit by bit
+pub fn maze_look(game: &Game, theme: &Theme) -> Theme {
+    let mut look = *theme;
+    if let Some((flashed, progress)) = game.maze_flash() {
+        let flash = Theme::contrasting(theme.background);
+        if theme.reduced_flashing {
+            look.wall_outline = blend(theme.wall_outline, flash, progress);
+        } else if flashed {
+            look.wall_outline = flash;
+        }
+    }
+    look
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_ghost(
+    canvas: &mut WindowCanvas,
+    ghost: &Ghost,
+    flashing: bool,
+    tick: u64,
+    tile_size: f32,
+    sprite_atlas: Option<&SpriteAtlas>,
+    elapsed: Duration,
+    theme: &Theme,
+) -> Result<(), String> {
+    let eyes_only = matches!(ghost.state, GhostState::Eaten | GhostState::EnteringHouse);
+    let direction = direction_name(ghost.direction);
+    let dst = centred_rect(ghost.pos, tile_size);
+
+    if let Some(atlas) = sprite_atlas {
+        let drawn = if eyes_only {
+            atlas.draw_frame(canvas, &format!("ghost_eyes_{}", direction), dst)?
+        } else if ghost.frightened {
+            let name = if flashing {
+                "ghost_flashing"
+            } else {
+                "ghost_frightened"
+            };
+            atlas.draw_animation(canvas, name, elapsed, dst)?
+        } else {
+            let name = format!("ghost_{}_{}", ghost.name.name(), direction);
+            atlas.draw_animation(canvas, &name, elapsed, dst)?
+        };
+        if drawn {
+            if !eyes_only && !ghost.frightened {
+                draw_marker(canvas, ghost, tile_size / 2.0, theme)?;
+            }
+            return Ok(());
+        }
+    }
+
+    let r = tile_size / 2.0;
+    let (cx, cy) = ghost.pos;
+
+    if !eyes_only {
+        let wobble = if (tick / 8).is_multiple_of(2) {
+            0.0
+        } else {
+            0.5
+        };
+        if theme.thick_outlines {
+            let outline = ghost_body_points(ghost.pos, r + outline_width(r), wobble);
+            canvas.set_draw_color(Theme::contrasting(theme.background));
+            canvas.draw_points(&outline[..])?;
+        }
+        let body_color = match (ghost.frightened, flashing) {
+            (true, true) => theme.flashing,
+            (true, false) => theme.frightened,
+            _ => theme.ghost(ghost.name),
+        };
+        canvas.set_draw_color(body_color);
+        canvas.draw_points(&ghost_body_points(ghost.pos, r, wobble)[..])?;
+        if !ghost.frightened {
+            draw_marker(canvas, ghost, r, theme)?;
+        }
+
+        if ghost.frightened {
+            // A worried little face instead of eyes
+            let face_color = if flashing {
+                theme.flashing_face
+            } else {
+                theme.frightened_face
+            };
+            let dot = (tile_size / 8.0).max(1.0) as u32;
+            canvas.set_draw_color(face_color);
+            for side in [-1.0, 1.0] {
+                canvas.fill_rect(centred_rect(
+                    (cx + side * r * 0.35, cy - r * 0.2),
+                    dot as f32,
+                ))?;
+            }
+            let zigzag: Vec<Point> = (0..=4)
+                .map(|i| {
+                    Point::new(
+                        (cx - r * 0.6 + i as f32 * r * 0.3) as i32,
+                        (cy + r * 0.35 + if i % 2 == 0 { 0.0 } else { -r * 0.15 }) as i32,
+                    )
+                })
+                .collect();
+            return canvas.draw_lines(&zigzag[..]);
+        }
+    }
+
+    // White eyes with the pupils looking where the ghost is going
+    let mut whites = Vec::new();
+    let mut pupils = Vec::new();
+    for side in [-1.0, 1.0] {
+        let eye = (cx + side * r * 0.38, cy - r * 0.2);
+        add_disc_points(&mut whites, eye, r * 0.3);
+        let pupil = (
+            eye.0 + ghost.direction.0 * r * 0.14,
+            eye.1 + ghost.direction.1 * r * 0.14,
+        );
+        add_disc_points(&mut pupils, pupil, r * 0.15);
+    }
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    canvas.draw_points(&whites[..])?;
+    canvas.set_draw_color(theme.frightened);
+    canvas.draw_points(&pupils[..])
+}
+
+// Round head, straight sides and a wavy skirt that wobbles as it moves
+fn ghost_body_points(center: (f32, f32), r: f32, wobble: f32) -> Vec<Point> {
+    let mut body = Vec::new();
+    let size = (r * 2.0) as i32;
+    for h in 0..size {
+        for w in 0..size {
+            let x = w as f32 + 0.5 - r;
+            let y = h as f32 + 0.5 - r;
+            let inside = if y < 0.0 {
+                x * x + y * y <= r * r
+            } else {
+                // Three bumps along the bottom edge
+                let bumps = ((x + r) / (r * 2.0) * 3.0 + wobble).fract();
+                let depth = (1.0 - (bumps * 2.0 - 1.0).abs()) * r / 3.0;
+                y < r - depth
+            };
+            if inside {
+                body.push(Point::new(
+                    (center.0 - r) as i32 + w,
+                    (center.1 - r) as i32 + h,
+                ));
+            }
+        }
+    }
+    body
+}
+
+// Letters on a three by five grid
+const MARKER_LETTERS: [[&str; 5]; 4] = [
+    ["##.", "#.#", "##.", "#.#", "##."], // B
+    ["##.", "#.#", "##.", "#..", "#.."], // P
+    ["###", ".#.", ".#.", ".#.", "###"], // I
+    [".##", "#..", "#..", "#..", ".##"], // C
+];
+
+// The ghost's letter or shape, on its body below the eyes
+fn draw_marker(
+    canvas: &mut WindowCanvas,
+    ghost: &Ghost,
+    r: f32,
+    theme: &Theme,
+) -> Result<(), String> {
+    let index = GhostName::ALL
+        .iter()
+        .position(|&name| name == ghost.name)
+        .unwrap_or(0);
+    let centre = (ghost.pos.0, ghost.pos.1 + r * 0.38);
+    let size = r * 0.5;
+    canvas.set_draw_color(Theme::contrasting(theme.ghost(ghost.name)));
+    match theme.markers {
+        GhostMarkers::Off => Ok(()),
+        GhostMarkers::Letters => {
+            let cell = (size / 5.0).max(1.0);
+            let mut cells = Vec::new();
+            for (row, line) in MARKER_LETTERS[index].iter().enumerate() {
+                for (col, mark) in line.chars().enumerate() {
+                    if mark == '#' {
+                        cells.push(Rect::new(
+                            (centre.0 + (col as f32 - 1.5) * cell) as i32,
+                            (centre.1 + (row as f32 - 2.5) * cell) as i32,
+                            cell.ceil() as u32,
+                            cell.ceil() as u32,
+                        ));
+                    }
+                }
+            }
+            canvas.fill_rects(&cells)
+        }
+        GhostMarkers::Shapes => {
+            let mut points = Vec::new();
+            let half = size / 2.0;
+            let s = half.ceil() as i32;
+            for dy in -s..=s {
+                for dx in -s..=s {
+                    let (x, y) = (dx as f32 / half, dy as f32 / half);
+                    let inside = match ghost.name {
+                        // Point up, widening to the bottom
+                        GhostName::Blinky => y.abs() <= 1.0 && x.abs() <= (y + 1.0) / 2.0,
+                        GhostName::Pinky => x * x + y * y <= 1.0,
+                        GhostName::Inky => x.abs() <= 0.85 && y.abs() <= 0.85,
+                        GhostName::Clyde => x.abs() + y.abs() <= 1.0,
+                    };
+                    if inside {
+                        points.push(Point::new(centre.0 as i32 + dx, centre.1 as i32 + dy));
+                    }
+                }
+            }
+            canvas.draw_points(&points[..])
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use std::collections::HashMap;
+
+use crate::events::{EventListener, GameEvent};
+use crate::game::TICKS_PER_SECOND;
+
+const SCENT_TICKS: u64 = 2 * TICKS_PER_SECOND as u64; // How long a tile smells of Pac-Man
+
+// The trail Pac-Man leaves behind: every tile he enters is lit up and fades
+// over a couple of seconds
+#[derive(Default)]
+pub struct ScentMap {
+    entered: HashMap<(usize, usize), u64>, // Tile to the tick Pac-Man entered it
+}
+
+impl ScentMap {
+    // From 1.0 just after Pac-Man was here down to 0.0 once the scent is gone
+    pub fn strength(&self, tile: (usize, usize), tick: u64) -> f32 {
+        match self.entered.get(&tile) {
+            Some(&entered) => {
+                let age = tick.saturating_sub(entered);
+                1.0 - (age as f32 / SCENT_TICKS as f32).min(1.0)
+            }
+            None => 0.0,
+        }
+    }
+
+    // Forgets the scents that have faded by `tick`
+    pub fn update(&mut self, tick: u64) {
+        self.entered
+            .retain(|_, entered| tick.saturating_sub(*entered) < SCENT_TICKS);
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        tile_width: f32,
+        tile_height: f32,
+        tick: u64,
+    ) -> Result<(), String> {
+        for &(col, row) in self.entered.keys() {
+            let brightness = self.strength((col, row), tick) * 200.0;
+            canvas.set_draw_color(Color::RGB(
+                brightness as u8,
+                brightness as u8,
+                brightness as u8,
+            ));
+            let x = (col as f32 * tile_width) as i32;
+            let y = (row as f32 * tile_height) as i32;
+            canvas.fill_rect(Rect::new(x, y, tile_width as u32, tile_height as u32))?;
+        }
+        Ok(())
+    }
+}
+
+impl EventListener for ScentMap {
+    fn on_event(&mut self, tick: u64, event: &GameEvent) {
+        if let GameEvent::TileEntered { tile } = event {
+            self.entered.insert(*tile, tick);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use sdl2::image::LoadTexture;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+// Layout of the TOML file that describes a sprite sheet. See
+// assets/sprites.example.toml for the frame and animation names the game
+// looks up.
+#[derive(Deserialize)]
+struct AtlasDescription {
+    image: String, // Relative to the description file
+    frames: HashMap<String, FrameRect>,
+    #[serde(default)]
+    animations: HashMap<String, Animation>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct FrameRect {
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct Animation {
+    frames: Vec<String>,
+    #[serde(default = "default_frame_ms")]
+    frame_ms: u64,
+    #[serde(default = "default_looping", rename = "loop")]
+    looping: bool,
+}
+
+fn default_frame_ms() -> u64 {
+    100
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl Animation {
+    fn frame_at(&self, elapsed: Duration) -> Option<&str> {
+        let count = self.frames.len() as u128;
+        if count == 0 {
+            return None;
+        }
+        let step = elapsed.as_millis() / self.frame_ms.max(1) as u128;
+        let index = if self.looping {
+            step % count
+        } else {
+            step.min(count - 1) // Hold the last frame
+        };
+        Some(&self.frames[index as usize])
+    }
+}
+
+pub struct SpriteAtlas<'a> {
+    texture: Texture<'a>,
+    frames: HashMap<String, Rect>,
+    animations: HashMap<String, Animation>,
+}
+
+impl<'a> SpriteAtlas<'a> {
+    pub fn load(
+        description_path: &str,
+        texture_creator: &'a TextureCreator<WindowContext>,
+    ) -> Result<SpriteAtlas<'a>, String> {
+        let text = std::fs::read_to_string(description_path)
+            .map_err(|e| format!("{}: {}", description_path, e))?;
+        let description: AtlasDescription =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", description_path, e))?;
+
+        let image_path = Path::new(description_path)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(&description.image);
+        let texture = texture_creator.load_texture(&image_path)?;
+
+        // Catch typos in animation definitions up front rather than mid-game
+        for (name, animation) in &description.animations {
+            for frame in &animation.frames {
+                if !description.frames.contains_key(frame) {
+                    return Err(format!(
+                        "{}: animation '{}' uses unknown frame '{}'",
+                        description_path, name, frame
+                    ));
+                }
+            }
+        }
+
+        let frames = description
+            .frames
+            .into_iter()
+            .map(|(name, f)| (name, Rect::new(f.x, f.y, f.w, f.h)))
+            .collect();
+
+        Ok(SpriteAtlas {
+            texture,
+            frames,
+            animations: description.animations,
+        })
+    }
+
+    // Returns false when the atlas has no such frame, so the caller can fall
+    // back to procedural drawing
+    pub fn draw_frame(
+        &self,
+        canvas: &mut WindowCanvas,
+        name: &str,
+        dst: Rect,
+    ) -> Result<bool, String> {
+        match self.frames.get(name) {
+            Some(src) => {
+                canvas.copy(&self.texture, *src, dst)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn draw_animation(
+        &self,
+        canvas: &mut WindowCanvas,
+        name: &str,
+        elapsed: Duration,
+        dst: Rect,
+    ) -> Result<bool, String> {
+        match self.animations.get(name).and_then(|a| a.frame_at(elapsed)) {
+            Some(frame) => self.draw_frame(canvas, frame, dst),
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use sdl2::pixels::Color;
+use serde::Deserialize;
+
+use crate::config::AccessibilityConfig;
+use crate::game::GhostName;
+
+// Every colour the maze, what is in it and the HUD are drawn in, and the
+// accessibility settings that change how they are drawn. Sprites from the
+// atlas are drawn as they are.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Theme {
+    pub background: Color,
+    pub wall_fill: Color, // Between the two lines of a wall
+    pub wall_outline: Color,
+    pub door: Color,
+    pub pellet: Color,
+    pub pacman: Color,
+    pub hud: Color,
+    pub grid: Color,        // The debug grid
+    pub ghosts: [Color; 4], // In GhostName::ALL order
+    pub frightened: Color,  // Frightened ghosts, and every ghost's pupils
+    pub frightened_face: Color,
+    pub flashing: Color, // Frightened ghosts about to recover
+    pub flashing_face: Color,
+    pub markers: GhostMarkers,
+    pub thick_outlines: bool, // Heavier walls, and Pac-Man and the ghosts outlined
+    pub reduced_flashing: bool, // Fades instead of flashes
+}
+
+// Drawn on each ghost so they can be told apart without their colours.
+// Frightened ghosts go without, their face showing what they are.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GhostMarkers {
+    #[default]
+    Off,
+    Letters, // B, P, I and C
+    Shapes,  // Triangle, circle, square and diamond
+}
+
+// Ghost colours picked to stay apart for each kind of colour blindness
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Theme {
+    pub const ARCADE: Theme = Theme {
+        background: Color::RGB(0, 0, 0),
+        wall_fill: Color::RGB(0, 0, 0),
+        wall_outline: Color::RGB(0, 0, 255),
+        door: Color::RGB(255, 184, 255),
+        pellet: Color::RGB(255, 184, 151),
+        pacman: Color::RGB(255, 255, 0),
+        hud: Color::RGB(255, 255, 255),
+        grid: Color::RGB(255, 255, 255),
+        ghosts: [
+            Color::RGB(255, 0, 0),
+            Color::RGB(255, 184, 255),
+            Color::RGB(0, 255, 255),
+            Color::RGB(255, 184, 82),
+        ],
+        frightened: Color::RGB(33, 33, 255),
+        frightened_face: Color::RGB(255, 184, 151),
+        flashing: Color::RGB(255, 255, 255),
+        flashing_face: Color::RGB(255, 0, 0),
+        markers: GhostMarkers::Off,
+        thick_outlines: false,
+        reduced_flashing: false,
+    };
+
+    // The first Ms. Pac-Man maze: pink walls with a red edge
+    pub const MS_PACMAN: Theme = Theme {
+        wall_fill: Color::RGB(255, 183, 174),
+        wall_outline: Color::RGB(255, 0, 0),
+        pellet: Color::RGB(222, 222, 255),
+        ..Theme::ARCADE
+    };
+
+    pub const NEON: Theme = Theme {
+        background: Color::RGB(20, 0, 40),
+        wall_fill: Color::RGB(60, 0, 90),
+        wall_outline: Color::RGB(255, 0, 220),
+        door: Color::RGB(0, 255, 160),
+        pellet: Color::RGB(0, 255, 255),
+        pacman: Color::RGB(255, 255, 80),
+        hud: Color::RGB(0, 255, 255),
+        grid: Color::RGB(120, 60, 160),
+        ghosts: [
+            Color::RGB(255, 40, 90),
+            Color::RGB(255, 120, 255),
+            Color::RGB(60, 255, 200),
+            Color::RGB(255, 160, 40),
+        ],
+        frightened: Color::RGB(80, 80, 255),
+        frightened_face: Color::RGB(255, 255, 255),
+        flashing: Color::RGB(255, 255, 255),
+        flashing_face: Color::RGB(255, 0, 220),
+        ..Theme::ARCADE
+    };
+
+    // Dark on light, for bright rooms and screenshots
+    pub const PAPER: Theme = Theme {
+        background: Color::RGB(245, 240, 225),
+        wall_fill: Color::RGB(200, 210, 235),
+        wall_outline: Color::RGB(30, 40, 110),
+        door: Color::RGB(200, 60, 140),
+        pellet: Color::RGB(90, 70, 50),
+        pacman: Color::RGB(230, 170, 0),
+        hud: Color::RGB(30, 30, 30),
+        grid: Color::RGB(170, 170, 170),
+        ghosts: [
+            Color::RGB(210, 0, 0),
+            Color::RGB(220, 90, 180),
+            Color::RGB(0, 150, 170),
+            Color::RGB(220, 120, 0),
+        ],
+        frightened: Color::RGB(40, 40, 200),
+        frightened_face: Color::RGB(245, 240, 225),
+        flashing: Color::RGB(150, 150, 150),
+        flashing_face: Color::RGB(210, 0, 0),
+        ..Theme::ARCADE
+    };
+
+    // The four greens of the original Game Boy screen
+    pub const GAME_BOY: Theme = Theme {
+        background: Color::RGB(155, 188, 15),
+        wall_fill: Color::RGB(139, 172, 15),
+        wall_outline: Color::RGB(15, 56, 15),
+        door: Color::RGB(48, 98, 48),
+        pellet: Color::RGB(48, 98, 48),
+        pacman: Color::RGB(15, 56, 15),
+        hud: Color::RGB(15, 56, 15),
+        grid: Color::RGB(48, 98, 48),
+        ghosts: [Color::RGB(15, 56, 15); 4],
+        frightened: Color::RGB(48, 98, 48),
+        frightened_face: Color::RGB(155, 188, 15),
+        flashing: Color::RGB(139, 172, 15),
+        flashing_face: Color::RGB(15, 56, 15),
+        ..Theme::ARCADE
+    };
+
+    // Pure colours on black, with nothing dim
+    pub const HIGH_CONTRAST: Theme = Theme {
+        wall_outline: Color::RGB(255, 255, 255),
+        door: Color::RGB(255, 255, 255),
+        pellet: Color::RGB(255, 255, 255),
+        grid: Color::RGB(128, 128, 128),
+        ghosts: [
+            Color::RGB(255, 0, 0),
+            Color::RGB(255, 100, 255),
+            Color::RGB(0, 255, 255),
+            Color::RGB(255, 160, 0),
+        ],
+        frightened: Color::RGB(60, 60, 255),
+        frightened_face: Color::RGB(255, 255, 255),
+        ..Theme::ARCADE
+    };
+
+    const BUILT_IN: [(&'static str, Theme); 6] = [
+        ("arcade", Theme::ARCADE),
+        ("ms-pacman", Theme::MS_PACMAN),
+        ("neon", Theme::NEON),
+        ("paper", Theme::PAPER),
+        ("game-boy", Theme::GAME_BOY),
+        ("high-contrast", Theme::HIGH_CONTRAST),
+    ];
+
+    pub fn named(name: &str) -> Option<Theme> {
+        Theme::BUILT_IN
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|&(_, theme)| theme)
+    }
+
+    pub fn ghost(&self, name: GhostName) -> Color {
+        let index = GhostName::ALL.iter().position(|&n| n == name).unwrap_or(0);
+        self.ghosts[index]
+    }
+
+    // The theme with the accessibility settings on top, which go over
+    // whatever a level pack picks
+    pub fn accessible(mut self, settings: &AccessibilityConfig) -> Theme {
+        match settings.palette {
+            // The Okabe-Ito colours, which both kinds of red-green
+            // colour blindness keep apart
+            Some(Palette::Deuteranopia | Palette::Protanopia) => {
+                self.ghosts = [
+                    Color::RGB(213, 94, 0),
+                    Color::RGB(204, 121, 167),
+                    Color::RGB(86, 180, 233),
+                    Color::RGB(230, 159, 0),
+                ];
+                self.frightened = Color::RGB(0, 70, 170);
+            }
+            // Blue and yellow are the ones that run together
+            Some(Palette::Tritanopia) => {
+                self.ghosts = [
+                    Color::RGB(230, 30, 30),
+                    Color::RGB(255, 140, 200),
+                    Color::RGB(0, 190, 190),
+                    Color::RGB(170, 170, 170),
+                ];
+                self.frightened = Color::RGB(90, 50, 170);
+            }
+            None => {}
+        }
+        self.markers = settings.ghost_markers;
+        self.thick_outlines = settings.high_contrast;
+        self.reduced_flashing = settings.reduced_flashing;
+        self
+    }
+
+    // Black or white, whichever stands out more against `color`
+    pub fn contrasting(color: Color) -> Color {
+        let luma = 0.299 * color.r as f32 + 0.587 * color.g as f32 + 0.114 * color.b as f32;
+        if luma > 140.0 {
+            Color::RGB(0, 0, 0)
+        } else {
+            Color::RGB(255, 255, 255)
+        }
+    }
+}
+
+// Part way from `from` to `to`, `amount` going from 0 to 1
+pub fn blend(from: Color, to: Color, amount: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount.clamp(0.0, 1.0)) as u8;
+    Color::RGB(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+type Rgb = (u8, u8, u8);
+
+// A theme as written in config.toml's [theme] table or a level pack's
+// `theme` rule: a built-in theme to start from, and any colours to change
+// in it. Colours are [red, green, blue]. A pack level's theme is laid over
+// the player's, then the colours the player set go back on top, so a pack
+// can pick the look but the player's own colours always show.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeSettings {
+    pub name: Option<String>, // Starts from what is underneath if not given
+    pub background: Option<Rgb>,
+    pub wall_fill: Option<Rgb>,
+    pub wall_outline: Option<Rgb>,
+    pub door: Option<Rgb>,
+    pub pellet: Option<Rgb>,
+    pub pacman: Option<Rgb>,
+    pub hud: Option<Rgb>,
+    pub grid: Option<Rgb>,
+    pub ghosts: Option<[Rgb; 4]>,
+    pub frightened: Option<Rgb>,
+    pub frightened_face: Option<Rgb>,
+    pub flashing: Option<Rgb>,
+    pub flashing_face: Option<Rgb>,
+}
+
+impl ThemeSettings {
+    // The theme these settings make when laid over `under`
+    pub fn apply(&self, under: &Theme) -> Result<Theme, String> {
+        let theme = match &self.name {
+            Some(name) => Theme::named(name).ok_or_else(|| {
+                let names: Vec<&str> = Theme::BUILT_IN.iter().map(|(name, _)| *name).collect();
+                format!("Unknown theme '{}', pick from {}", name, names.join(", "))
+            })?,
+            None => *under,
+        };
+        Ok(self.apply_colors(theme))
+    }
+
+    // `theme` with the colours these settings give, leaving out the name
+    pub fn apply_colors(&self, mut theme: Theme) -> Theme {
+        let set = |color: &mut Color, rgb: Option<Rgb>| {
+            if let Some((r, g, b)) = rgb {
+                *color = Color::RGB(r, g, b);
+            }
+        };
+        set(&mut theme.background, self.background);
+        set(&mut theme.wall_fill, self.wall_fill);
+        set(&mut theme.wall_outline, self.wall_outline);
+        set(&mut theme.door, self.door);
+        set(&mut theme.pellet, self.pellet);
+        set(&mut theme.pacman, self.pacman);
+        set(&mut theme.hud, self.hud);
+        set(&mut theme.grid, self.grid);
+        if let Some(ghosts) = self.ghosts {
+            for (color, rgb) in theme.ghosts.iter_mut().zip(ghosts) {
+                set(color, Some(rgb));
+            }
+        }
+        set(&mut theme.frightened, self.frightened);
+        set(&mut theme.frightened_face, self.frightened_face);
+        set(&mut theme.flashing, self.flashing);
+        set(&mut theme.flashing_face, self.flashing_face);
+        theme
+    }
+}
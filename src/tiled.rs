@@ -0,0 +1,730 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::level::{Level, LevelRules, Pellet};
+use crate::maze::{autotile, MazeTile};
+
+// Tiled keeps whether a tile is flipped or rotated in the top bits of its ID
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+// The custom property that says what a tile is in the maze
+const MAZE_PROPERTY: &str = "maze";
+
+// A map from the Tiled editor, saved as TMX or JSON, made into a level.
+//
+// Each tile says what it is with a custom string property "maze" in its
+// tileset, or the map has a property "tile <ID>" for it instead, where ID is
+// the tile's global ID in the map. Either way the value is one of:
+//   wall       a wall, with the piece worked out from its neighbours
+//   empty      open space
+//   dot, energizer, door
+//   a wall piece's name, such as wall_corner_nw, to pick the piece by hand
+// Empty cells are open space, and where tile layers overlap the top one
+// wins.
+//
+// Objects in object layers are told apart by their type (class in newer
+// versions of Tiled) or failing that their name: pacman, blinky, pinky,
+// inky, clyde and fruit go on the tile under the object's centre, and a
+// tunnel clears every tile it covers of walls and pellets.
+pub fn load_tiled_map(path: &str) -> Result<Level, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let map = if path.ends_with(".tmx") {
+        read_tmx(&text, directory)
+    } else {
+        read_json(&text, directory)
+    };
+    map.and_then(build_level)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+// Whether a level file is a Tiled map rather than the text format
+pub fn is_tiled_map(path: &str) -> bool {
+    [".tmx", ".tmj", ".json"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+// What the importer needs from a map, read the same from either format
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_size: (f32, f32),
+    maze_values: HashMap<u32, String>, // "maze" property for each global tile ID
+    layers: Vec<Vec<u32>>,             // Global tile IDs, bottom layer first
+    objects: Vec<MapObject>,
+}
+
+struct MapObject {
+    kind: String,
+    rect: (f32, f32, f32, f32), // Left, top, width and height in pixels
+}
+
+impl MapObject {
+    fn new(
+        name: &str,
+        kind: &str,
+        position: (f32, f32),
+        size: (f32, f32),
+        tile: bool,
+    ) -> MapObject {
+        let kind = if kind.is_empty() { name } else { kind };
+        // Tile objects hang up from their position rather than down
+        let top = if tile {
+            position.1 - size.1
+        } else {
+            position.1
+        };
+        MapObject {
+            kind: kind.trim().to_lowercase(),
+            rect: (position.0, top, size.0, size.1),
+        }
+    }
+}
+
+fn build_level(map: TiledMap) -> Result<Level, String> {
+    let (width, height) = (map.width, map.height);
+    if width == 0 || height == 0 {
+        return Err("Map is empty".to_string());
+    }
+
+    // Unmapped tiles are listed in one go, so they can all be fixed at once
+    let mut values: Vec<Vec<&str>> = vec![vec!["empty"; width]; height];
+    let mut unmapped: BTreeMap<u32, (usize, usize)> = BTreeMap::new();
+    for layer in &map.layers {
+        for (i, &gid) in layer.iter().enumerate().take(width * height) {
+            let (x, y) = (i % width, i / width);
+            let gid = gid & !FLIP_FLAGS;
+            if gid == 0 {
+                continue;
+            }
+            match map.maze_values.get(&gid) {
+                Some(value) => values[y][x] = value,
+                None => {
+                    unmapped.entry(gid).or_insert((x, y));
+                }
+            }
+        }
+    }
+    if !unmapped.is_empty() {
+        let list: Vec<String> = unmapped
+            .iter()
+            .map(|(gid, (x, y))| format!("{} (first at ({}, {}))", gid, x, y))
+            .collect();
+        return Err(format!(
+            "No maze tile for tile IDs {}. Give each a '{}' property in its tileset \
+             or a 'tile <ID>' property on the map.",
+            list.join(", "),
+            MAZE_PROPERTY
+        ));
+    }
+
+    let mut walls = vec![vec![false; width]; height];
+    let mut pieces = Vec::new();
+    let mut doors = Vec::new();
+    let mut pellets = HashMap::new();
+    for (y, row) in values.iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            match value {
+                "empty" => {}
+                "wall" => walls[y][x] = true,
+                "dot" => {
+                    pellets.insert((x, y), Pellet::Dot);
+                }
+                "energizer" => {
+                    pellets.insert((x, y), Pellet::Energizer);
+                }
+                "door" => doors.push((x, y)),
+                name => {
+                    let piece = wall_piece(name)
+                        .ok_or(format!("Unknown maze tile '{}' at ({}, {})", name, x, y))?;
+                    walls[y][x] = true;
+                    pieces.push(((x, y), piece));
+                }
+            }
+        }
+    }
+
+    let mut pacman_start = None;
+    let mut ghost_starts = [None; 4];
+    let mut fruit_tile = None;
+    for object in &map.objects {
+        let (left, top, w, h) = object.rect;
+        let tile_at = |px: f32, py: f32| {
+            let x = (px / map.tile_size.0)
+                .floor()
+                .clamp(0.0, (width - 1) as f32);
+            let y = (py / map.tile_size.1)
+                .floor()
+                .clamp(0.0, (height - 1) as f32);
+            (x as usize, y as usize)
+        };
+        let centre = tile_at(left + w / 2.0, top + h / 2.0);
+        let spot = match object.kind.as_str() {
+            "pacman" => &mut pacman_start,
+            "blinky" => &mut ghost_starts[0],
+            "pinky" => &mut ghost_starts[1],
+            "inky" => &mut ghost_starts[2],
+            "clyde" => &mut ghost_starts[3],
+            "fruit" => &mut fruit_tile,
+            "tunnel" => {
+                // The far edges of the rectangle are just outside it
+                let from = tile_at(left, top);
+                let to = tile_at((left + w - 0.5).max(left), (top + h - 0.5).max(top));
+                let covered = (from.1..=to.1).flat_map(|y| (from.0..=to.0).map(move |x| (x, y)));
+                for (x, y) in covered {
+                    walls[y][x] = false;
+                    pellets.remove(&(x, y));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if spot.replace(centre).is_some() {
+            return Err(format!("More than one '{}' object", object.kind));
+        }
+    }
+
+    let pacman_start = pacman_start.ok_or("Map has no 'pacman' object")?;
+    let fruit_tile = fruit_tile.ok_or("Map has no 'fruit' object")?;
+    let names = ["blinky", "pinky", "inky", "clyde"];
+    let mut starts = [(0, 0); 4];
+    for (ghost, start) in ghost_starts.iter().enumerate() {
+        starts[ghost] = start.ok_or(format!("Map has no '{}' object", names[ghost]))?;
+    }
+    let &(door_col, door_row) = doors.first().ok_or("Map has no 'door' tile")?;
+    if door_row == 0 {
+        return Err("The ghost house door can't be on the top row".to_string());
+    }
+
+    let mut tiles = autotile(&walls);
+    for ((x, y), piece) in pieces {
+        if walls[y][x] {
+            tiles[y][x] = piece;
+        }
+    }
+    for &(x, y) in &doors {
+        tiles[y][x] = MazeTile::Door;
+    }
+
+    Ok(Level {
+        tiles,
+        pellets,
+        pacman_start,
+        ghost_starts: starts,
+        fruit_tile,
+        house_exit: (door_col, door_row - 1),
+        rules: LevelRules::default(),
+    })
+}
+
+fn wall_piece(name: &str) -> Option<MazeTile> {
+    (0..16)
+        .map(MazeTile::from_connections)
+        .chain([MazeTile::WallSolid])
+        .find(|piece| piece.name() == name)
+}
+
+// Map properties of the form "tile <ID>" give the maze value for a tile ID
+fn add_map_tile_properties<'a>(
+    maze_values: &mut HashMap<u32, String>,
+    properties: impl Iterator<Item = (&'a str, String)>,
+) -> Result<(), String> {
+    for (name, value) in properties {
+        if let Some(gid) = name.strip_prefix("tile ") {
+            let gid = gid
+                .trim()
+                .parse()
+                .map_err(|_| format!("Map property '{}' needs a tile ID", name))?;
+            maze_values.insert(gid, value.trim().to_lowercase());
+        }
+    }
+    Ok(())
+}
+
+fn attribute<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Result<T, String> {
+    let value = node.attribute(name).ok_or(format!(
+        "<{}> has no {} attribute",
+        node.tag_name().name(),
+        name
+    ))?;
+    value.parse().map_err(|_| {
+        format!(
+            "<{}> has a bad {}: '{}'",
+            node.tag_name().name(),
+            name,
+            value
+        )
+    })
+}
+
+fn optional_attribute(node: roxmltree::Node, name: &str) -> f32 {
+    node.attribute(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0.0)
+}
+
+// The name and value of each <property> in the node's <properties>
+fn tmx_properties<'a>(node: roxmltree::Node<'a, 'a>) -> impl Iterator<Item = (&'a str, String)> {
+    node.children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children())
+        .filter(|property| property.has_tag_name("property"))
+        .filter_map(|property| {
+            // Multi-line values are kept as the text of the element
+            let value = property.attribute("value").or(property.text())?;
+            Some((property.attribute("name")?, value.to_string()))
+        })
+}
+
+fn read_tmx(text: &str, directory: &Path) -> Result<TiledMap, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let root = document.root_element();
+    if !root.has_tag_name("map") {
+        return Err("Not a Tiled map".to_string());
+    }
+    if root.attribute("infinite") == Some("1") {
+        return Err("Infinite maps can't be imported, give the map a fixed size".to_string());
+    }
+    let mut map = TiledMap {
+        width: attribute(root, "width")?,
+        height: attribute(root, "height")?,
+        tile_size: (
+            attribute(root, "tilewidth")?,
+            attribute(root, "tileheight")?,
+        ),
+        maze_values: HashMap::new(),
+        layers: Vec::new(),
+        objects: Vec::new(),
+    };
+    add_map_tile_properties(&mut map.maze_values, tmx_properties(root))?;
+
+    for tileset in root.children().filter(|node| node.has_tag_name("tileset")) {
+        let first_gid: u32 = attribute(tileset, "firstgid")?;
+        match tileset.attribute("source") {
+            Some(source) => {
+                let path = directory.join(source);
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                if source.ends_with(".tsx") {
+                    let document = roxmltree::Document::parse(&text)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    read_tmx_tileset(document.root_element(), first_gid, &mut map);
+                } else {
+                    let tileset: JsonTileset = serde_json::from_str(&text)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    read_json_tileset(&tileset, first_gid, &mut map);
+                }
+            }
+            None => read_tmx_tileset(tileset, first_gid, &mut map),
+        }
+    }
+
+    // Layers can be inside groups, so every one in the document is read
+    for node in root.descendants() {
+        if node.has_tag_name("layer") {
+            let data = node
+                .children()
+                .find(|child| child.has_tag_name("data"))
+                .ok_or("A tile layer has no <data>")?;
+            map.layers.push(read_tmx_data(data)?);
+        } else if node.has_tag_name("objectgroup") {
+            for object in node.children().filter(|child| child.has_tag_name("object")) {
+                let kind = object
+                    .attribute("type")
+                    .or(object.attribute("class"))
+                    .unwrap_or_default();
+                map.objects.push(MapObject::new(
+                    object.attribute("name").unwrap_or_default(),
+                    kind,
+                    (attribute(object, "x")?, attribute(object, "y")?),
+                    (
+                        optional_attribute(object, "width"),
+                        optional_attribute(object, "height"),
+                    ),
+                    object.attribute("gid").is_some(),
+                ));
+            }
+        }
+    }
+    Ok(map)
+}
+
+fn read_tmx_tileset(tileset: roxmltree::Node, first_gid: u32, map: &mut TiledMap) {
+    for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+        let Ok(id) = attribute::<u32>(tile, "id") else {
+            continue;
+        };
+        for (name, value) in tmx_properties(tile) {
+            if name == MAZE_PROPERTY {
+                map.maze_values
+                    .insert(first_gid + id, value.trim().to_lowercase());
+            }
+        }
+    }
+}
+
+fn read_tmx_data(data: roxmltree::Node) -> Result<Vec<u32>, String> {
+    match data.attribute("encoding") {
+        Some("csv") => data
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(|gid| {
+                gid.trim()
+                    .parse()
+                    .map_err(|_| format!("Bad tile ID '{}' in a tile layer", gid.trim()))
+            })
+            .collect(),
+        // The old XML format, a <tile> for every cell
+        None => Ok(data
+            .children()
+            .filter(|node| node.has_tag_name("tile"))
+            .map(|tile| attribute(tile, "gid").unwrap_or(0))
+            .collect()),
+        Some(encoding) => Err(format!(
+            "Tile layers saved as {} can't be imported, save them as CSV",
+            encoding
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+    #[serde(default)]
+    tilesets: Vec<JsonTileset>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    #[serde(default)]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<JsonTile>,
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: serde_json::Value, // Tile IDs, or a string when encoded
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>, // In a group
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    gid: Option<u32>,
+}
+
+fn json_properties(properties: &[JsonProperty]) -> impl Iterator<Item = (&str, String)> {
+    properties.iter().map(|property| {
+        let value = match &property.value {
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        (property.name.as_str(), value)
+    })
+}
+
+fn read_json(text: &str, directory: &Path) -> Result<TiledMap, String> {
+    let json: JsonMap = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if json.infinite {
+        return Err("Infinite maps can't be imported, give the map a fixed size".to_string());
+    }
+    let mut map = TiledMap {
+        width: json.width,
+        height: json.height,
+        tile_size: (json.tilewidth, json.tileheight),
+        maze_values: HashMap::new(),
+        layers: Vec::new(),
+        objects: Vec::new(),
+    };
+    add_map_tile_properties(&mut map.maze_values, json_properties(&json.properties))?;
+
+    for tileset in &json.tilesets {
+        match &tileset.source {
+            Some(source) => {
+                let path = directory.join(source);
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                let external: JsonTileset = serde_json::from_str(&text)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                read_json_tileset(&external, tileset.firstgid, &mut map);
+            }
+            None => read_json_tileset(tileset, tileset.firstgid, &mut map),
+        }
+    }
+    read_json_layers(&json.layers, &mut map)?;
+    Ok(map)
+}
+
+fn read_json_tileset(tileset: &JsonTileset, first_gid: u32, map: &mut TiledMap) {
+    for tile in &tileset.tiles {
+        for (name, value) in json_properties(&tile.properties) {
+            if name == MAZE_PROPERTY {
+                map.maze_values
+                    .insert(first_gid + tile.id, value.trim().to_lowercase());
+            }
+        }
+    }
+}
+
+fn read_json_layers(layers: &[JsonLayer], map: &mut TiledMap) -> Result<(), String> {
+    for layer in layers {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let data = layer
+                    .data
+                    .as_array()
+                    .ok_or("Tile layers saved as base64 can't be imported, save them as CSV")?;
+                let gids = data
+                    .iter()
+                    .map(|gid| gid.as_u64().map(|gid| gid as u32))
+                    .collect::<Option<Vec<u32>>>()
+                    .ok_or("Bad tile ID in a tile layer")?;
+                map.layers.push(gids);
+            }
+            "objectgroup" => {
+                for object in &layer.objects {
+                    let kind = if object.kind.is_empty() {
+                        &object.class
+                    } else {
+                        &object.kind
+                    };
+                    map.objects.push(MapObject::new(
+                        &object.name,
+                        kind,
+                        (object.x, object.y),
+                        (object.width, object.height),
+                        object.gid.is_some(),
+                    ));
+                }
+            }
+            "group" => read_json_layers(&layer.layers, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    const TILE: f32 = 8.0;
+    const TILESET: [(u32, &str); 4] = [(1, "wall"), (2, "dot"), (3, "energizer"), (4, "door")];
+
+    // The classic level drawn as a Tiled map: the tile IDs of one layer,
+    // and a point object for each start and the fruit
+    struct ClassicMap {
+        width: usize,
+        height: usize,
+        gids: Vec<u32>,
+        objects: Vec<(&'static str, f32, f32)>,
+    }
+
+    fn classic_map() -> ClassicMap {
+        let lines: Vec<&str> = CLASSIC_LEVEL.lines().collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap();
+        let mut gids = vec![0; width * lines.len()];
+        let mut objects = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let centre = ((x as f32 + 0.5) * TILE, (y as f32 + 0.5) * TILE);
+                let object = match c {
+                    '#' => Err(1),
+                    '.' => Err(2),
+                    'o' => Err(3),
+                    '-' => Err(4),
+                    'P' => Ok("pacman"),
+                    'F' => Ok("fruit"),
+                    '1' => Ok("blinky"),
+                    '2' => Ok("pinky"),
+                    '3' => Ok("inky"),
+                    '4' => Ok("clyde"),
+                    _ => continue,
+                };
+                match object {
+                    Ok(name) => objects.push((name, centre.0, centre.1)),
+                    Err(gid) => gids[y * width + x] = gid,
+                }
+            }
+        }
+        ClassicMap {
+            width,
+            height: lines.len(),
+            gids,
+            objects,
+        }
+    }
+
+    fn classic_tmx(flip: bool) -> String {
+        let ClassicMap {
+            width,
+            height,
+            gids,
+            objects,
+        } = classic_map();
+        let tiles: String = TILESET
+            .iter()
+            .map(|(gid, value)| {
+                format!(
+                    r#"<tile id="{}"><properties><property name="maze" value="{}"/></properties></tile>"#,
+                    gid - 1,
+                    value
+                )
+            })
+            .collect();
+        let data: Vec<String> = gids
+            .iter()
+            .map(|&gid| match gid {
+                1 if flip => (gid | 0x8000_0000).to_string(),
+                gid => gid.to_string(),
+            })
+            .collect();
+        let objects: String = objects
+            .iter()
+            .map(|(name, x, y)| format!(r#"<object name="{}" x="{}" y="{}"/>"#, name, x, y))
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="{width}" height="{height}" tilewidth="{TILE}" tileheight="{TILE}" infinite="0">
+ <tileset firstgid="1" name="maze" tilewidth="{TILE}" tileheight="{TILE}" tilecount="4">{tiles}</tileset>
+ <layer id="1" name="maze" width="{width}" height="{height}"><data encoding="csv">{data}</data></layer>
+ <objectgroup id="2" name="actors">{objects}</objectgroup>
+</map>"#,
+            data = data.join(",")
+        )
+    }
+
+    // The JSON map gives the tiles their values with map properties rather
+    // than a tileset
+    fn classic_json() -> String {
+        let ClassicMap {
+            width,
+            height,
+            gids,
+            objects,
+        } = classic_map();
+        let properties: Vec<serde_json::Value> = TILESET
+            .iter()
+            .map(|(gid, value)| {
+                serde_json::json!({"name": format!("tile {}", gid), "type": "string", "value": value})
+            })
+            .collect();
+        let objects: Vec<serde_json::Value> = objects
+            .iter()
+            .map(|(name, x, y)| serde_json::json!({"name": "", "type": name, "x": x, "y": y}))
+            .collect();
+        serde_json::json!({
+            "width": width,
+            "height": height,
+            "tilewidth": TILE,
+            "tileheight": TILE,
+            "properties": properties,
+            "layers": [
+                {"type": "group", "layers": [{"type": "tilelayer", "data": gids}]},
+                {"type": "objectgroup", "objects": objects},
+            ],
+        })
+        .to_string()
+    }
+
+    fn assert_same_level(imported: &Level, expected: &Level) {
+        assert!(imported.tiles == expected.tiles);
+        assert_eq!(imported.pellets, expected.pellets);
+        assert_eq!(imported.pacman_start, expected.pacman_start);
+        assert_eq!(imported.ghost_starts, expected.ghost_starts);
+        assert_eq!(imported.fruit_tile, expected.fruit_tile);
+        assert_eq!(imported.house_exit, expected.house_exit);
+    }
+
+    #[test]
+    fn tmx_matches_the_text_format() {
+        let classic = parse_level(CLASSIC_LEVEL).unwrap();
+        for flip in [false, true] {
+            let map = read_tmx(&classic_tmx(flip), Path::new("")).unwrap();
+            assert_same_level(&build_level(map).unwrap(), &classic);
+        }
+    }
+
+    #[test]
+    fn json_matches_the_text_format() {
+        let classic = parse_level(CLASSIC_LEVEL).unwrap();
+        let map = read_json(&classic_json(), Path::new("")).unwrap();
+        assert_same_level(&build_level(map).unwrap(), &classic);
+    }
+
+    #[test]
+    fn unmapped_tiles_are_listed() {
+        let tmx = classic_tmx(false).replace(r#"<property name="maze" value="dot"/>"#, "");
+        let map = read_tmx(&tmx, Path::new("")).unwrap();
+        let error = build_level(map).err().unwrap();
+        assert!(error.starts_with("No maze tile for tile IDs 2 (first at (1, 1))"));
+    }
+
+    #[test]
+    fn tunnel_objects_clear_walls_and_pellets() {
+        let tmx = classic_tmx(false).replace(
+            "</objectgroup>",
+            r#"<object type="tunnel" x="0" y="8" width="16" height="8"/></objectgroup>"#,
+        );
+        let level = build_level(read_tmx(&tmx, Path::new("")).unwrap()).unwrap();
+        for tile in [(0, 1), (1, 1)] {
+            assert_eq!(level.tiles[tile.1][tile.0], MazeTile::Empty);
+            assert!(!level.pellets.contains_key(&tile));
+        }
+    }
+
+    #[test]
+    fn a_missing_actor_is_an_error() {
+        let tmx = classic_tmx(false).replace(r#"name="clyde""#, r#"name="sue""#);
+        let map = read_tmx(&tmx, Path::new("")).unwrap();
+        assert_eq!(
+            build_level(map).err(),
+            Some("Map has no 'clyde' object".to_string())
+        );
+    }
+}
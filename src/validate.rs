@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::level::Level;
+use crate::maze::{MazeTile, WALL_E, WALL_N, WALL_S, WALL_W};
+use crate::pathfinding::{Mover, Pathfinder};
+
+const REPORT_TILES: usize = 12; // Tiles listed per problem before the rest are counted
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,   // The level can't be played properly
+    Warning, // Playable, but not the way the arcade does it
+}
+
+// Something wrong with a level, and the tiles where it is
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    pub tiles: Vec<(usize, usize)>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        let listed: Vec<String> = self
+            .tiles
+            .iter()
+            .take(REPORT_TILES)
+            .map(|(x, y)| format!("({}, {})", x, y))
+            .collect();
+        if !listed.is_empty() {
+            write!(f, " at {}", listed.join(", "))?;
+        }
+        if self.tiles.len() > REPORT_TILES {
+            write!(f, " and {} more", self.tiles.len() - REPORT_TILES)?;
+        }
+        Ok(())
+    }
+}
+
+fn problem(severity: Severity, message: String, mut tiles: Vec<(usize, usize)>) -> Problem {
+    tiles.sort_by_key(|&(x, y)| (y, x));
+    Problem {
+        severity,
+        message,
+        tiles,
+    }
+}
+
+fn to_tile(tile: (usize, usize)) -> (i32, i32) {
+    (tile.0 as i32, tile.1 as i32)
+}
+
+// Looks for the mistakes that spoil a maze: places Pac-Man can't get to,
+// dead ends, a ghost house the ghosts can't get in and out of, tunnels
+// that come out into a wall and wall pieces that don't join up. Errors
+// come first.
+pub fn validate_level(level: &Level) -> Vec<Problem> {
+    let paths = Pathfinder::new(&level.tiles);
+    let mut problems = Vec::new();
+    check_reachability(level, &paths, &mut problems);
+    check_dead_ends(level, &paths, &mut problems);
+    check_ghost_house(level, &paths, &mut problems);
+    check_tunnels(level, &mut problems);
+    check_wall_pieces(level, &mut problems);
+    problems.sort_by_key(|problem| problem.severity == Severity::Warning);
+    problems
+}
+
+// Every pellet and the fruit need to be somewhere Pac-Man can get to. Open
+// space he can't reach is fine when there is nothing in it, as around the
+// outside of the classic maze.
+fn check_reachability(level: &Level, paths: &Pathfinder, problems: &mut Vec<Problem>) {
+    let Some(reachable) = paths.distances_to(Mover::Pacman, to_tile(level.pacman_start)) else {
+        return;
+    };
+    let stranded = |tile: (usize, usize)| reachable.get(to_tile(tile)).is_none();
+
+    // Sealed off pellets, one problem per enclosed region
+    let mut seen = HashSet::new();
+    let mut pellets: Vec<(usize, usize)> = level.pellets.keys().copied().collect();
+    pellets.sort_by_key(|&(x, y)| (y, x));
+    for &pellet in &pellets {
+        if !stranded(pellet) || seen.contains(&pellet) {
+            continue;
+        }
+        let region = paths.region(Mover::Pacman, to_tile(pellet));
+        let in_region: Vec<(usize, usize)> = pellets
+            .iter()
+            .copied()
+            .filter(|&tile| region.contains(&to_tile(tile)))
+            .collect();
+        seen.extend(in_region.iter().copied());
+        let count = match in_region.len() {
+            1 => "1 pellet".to_string(),
+            n => format!("{} pellets", n),
+        };
+        problems.push(problem(
+            Severity::Error,
+            format!("{} sealed off from Pac-Man's start", count),
+            in_region,
+        ));
+    }
+
+    if stranded(level.fruit_tile) {
+        problems.push(problem(
+            Severity::Error,
+            "The fruit appears where Pac-Man can't get to it".to_string(),
+            vec![level.fruit_tile],
+        ));
+    }
+}
+
+// Open tiles Pac-Man can reach with only one way out. The arcade maze has
+// none, so ghosts can always be dodged.
+fn check_dead_ends(level: &Level, paths: &Pathfinder, problems: &mut Vec<Problem>) {
+    let region = paths.region(Mover::Pacman, to_tile(level.pacman_start));
+    let dead_ends: Vec<(usize, usize)> = region
+        .iter()
+        .filter(|&&tile| paths.neighbours(Mover::Pacman, tile).count() == 1)
+        .map(|&(x, y)| (x as usize, y as usize))
+        .collect();
+    if !dead_ends.is_empty() {
+        problems.push(problem(
+            Severity::Warning,
+            "Dead end".to_string(),
+            dead_ends,
+        ));
+    }
+}
+
+// Ghosts leave the house through the door, come back through it as eyes
+// and need the way from the door to the rest of the maze
+fn check_ghost_house(level: &Level, paths: &Pathfinder, problems: &mut Vec<Problem>) {
+    let exit = to_tile(level.house_exit);
+    let centre = to_tile(level.house_centre());
+    let mut error = |message: &str, tile: (usize, usize)| {
+        problems.push(problem(Severity::Error, message.to_string(), vec![tile]));
+    };
+
+    if level.tiles[level.house_exit.1].get(level.house_exit.0) != Some(&MazeTile::Empty) {
+        error("The ghost house door opens into a wall", level.house_exit);
+        return;
+    }
+    for (ghost, &start) in level.ghost_starts.iter().enumerate().skip(1) {
+        if paths
+            .distance(Mover::LeavingGhost, to_tile(start), exit)
+            .is_none()
+        {
+            error(
+                &format!("Ghost {} can't get out of the ghost house", ghost + 1),
+                start,
+            );
+        }
+    }
+    if paths.distance(Mover::Eyes, exit, centre).is_none() {
+        error(
+            "Eaten ghosts can't get back into the middle of the ghost house",
+            level.house_exit,
+        );
+    }
+    let pacman = to_tile(level.pacman_start);
+    if paths.distance(Mover::Ghost, exit, pacman).is_none() {
+        error(
+            "The ghost house door doesn't lead to where Pac-Man is",
+            level.house_exit,
+        );
+    }
+    let blinky = level.ghost_starts[0];
+    if paths
+        .distance(Mover::Ghost, to_tile(blinky), pacman)
+        .is_none()
+    {
+        error("Blinky starts where he can't get to Pac-Man", blinky);
+    }
+}
+
+// Open space on an edge is a tunnel, which needs open space where it comes
+// out on the other side
+fn check_tunnels(level: &Level, problems: &mut Vec<Problem>) {
+    let (width, height) = (level.width(), level.height());
+    let open = |x: usize, y: usize| level.tiles[y].get(x).is_none_or(|&t| t == MazeTile::Empty);
+    let mut unpaired = Vec::new();
+    for y in 0..height {
+        if width > 1 && open(0, y) != open(width - 1, y) {
+            unpaired.push(if open(0, y) { (0, y) } else { (width - 1, y) });
+        }
+    }
+    for x in 0..width {
+        if height > 1 && open(x, 0) != open(x, height - 1) {
+            unpaired.push(if open(x, 0) { (x, 0) } else { (x, height - 1) });
+        }
+    }
+    if !unpaired.is_empty() {
+        problems.push(problem(
+            Severity::Warning,
+            "Tunnel comes out into a wall on the other side".to_string(),
+            unpaired,
+        ));
+    }
+}
+
+// Neighbouring wall pieces have to agree on whether they join, or the
+// lines drawn for them break off. Autotiled walls always do, but a level
+// that picks its own pieces may not.
+fn check_wall_pieces(level: &Level, problems: &mut Vec<Problem>) {
+    let connections = |x: usize, y: usize| {
+        level
+            .tiles
+            .get(y)
+            .and_then(|row| row.get(x))
+            .and_then(|tile| tile.connections())
+    };
+    let mut mismatched = Vec::new();
+    for (y, row) in level.tiles.iter().enumerate() {
+        for x in 0..row.len() {
+            let Some(here) = connections(x, y) else {
+                continue;
+            };
+            for (side, opposite, dx, dy) in [
+                (WALL_N, WALL_S, 0, -1),
+                (WALL_E, WALL_W, 1, 0),
+                (WALL_S, WALL_N, 0, 1),
+                (WALL_W, WALL_E, -1, 0),
+            ] {
+                let neighbour = (x as isize + dx, y as isize + dy);
+                let there = (neighbour.0 >= 0 && neighbour.1 >= 0)
+                    .then(|| connections(neighbour.0 as usize, neighbour.1 as usize))
+                    .flatten();
+                let joins_there = there.is_some_and(|there| there & opposite != 0);
+                if (here & side != 0) != joins_there {
+                    mismatched.push((x, y));
+                    break;
+                }
+            }
+        }
+    }
+    if !mismatched.is_empty() {
+        problems.push(problem(
+            Severity::Warning,
+            "Wall piece doesn't join up with its neighbours".to_string(),
+            mismatched,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{parse_level, CLASSIC_LEVEL};
+
+    fn classic() -> Level {
+        parse_level(CLASSIC_LEVEL).unwrap()
+    }
+
+    fn wall_off(level: &mut Level, tile: (usize, usize)) {
+        level.tiles[tile.1][tile.0] = MazeTile::WallPillar;
+        level.pellets.remove(&tile);
+    }
+
+    fn find<'a>(problems: &'a [Problem], message: &str) -> &'a Problem {
+        problems
+            .iter()
+            .find(|problem| problem.message.contains(message))
+            .unwrap_or_else(|| panic!("no problem saying '{}'", message))
+    }
+
+    #[test]
+    fn shipped_mazes_are_clean() {
+        let mazes = [
+            CLASSIC_LEVEL,
+            include_str!("../packs/ms-pacman/maze1.txt"),
+            include_str!("../packs/ms-pacman/maze2.txt"),
+            include_str!("../packs/ms-pacman/maze3.txt"),
+            include_str!("../packs/ms-pacman/maze4.txt"),
+        ];
+        for text in mazes {
+            let problems = validate_level(&parse_level(text).unwrap());
+            let listed: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            assert!(problems.is_empty(), "{:?}", listed);
+        }
+    }
+
+    #[test]
+    fn sealed_off_pellets_are_an_error() {
+        let mut level = classic();
+        wall_off(&mut level, (2, 1));
+        wall_off(&mut level, (1, 2));
+        let problems = validate_level(&level);
+        let sealed = find(&problems, "1 pellet sealed off");
+        assert_eq!(sealed.severity, Severity::Error);
+        assert_eq!(sealed.tiles, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn dead_ends_are_a_warning() {
+        let mut level = classic();
+        wall_off(&mut level, (2, 1));
+        let problems = validate_level(&level);
+        let dead_end = find(&problems, "Dead end");
+        assert_eq!(dead_end.severity, Severity::Warning);
+        assert!(dead_end.tiles.contains(&(1, 1)));
+    }
+
+    #[test]
+    fn a_blocked_door_is_an_error() {
+        let mut level = classic();
+        let exit = level.house_exit;
+        wall_off(&mut level, exit);
+        let problems = validate_level(&level);
+        assert_eq!(
+            find(&problems, "door opens into a wall").severity,
+            Severity::Error
+        );
+    }
+
+    #[test]
+    fn a_one_sided_tunnel_is_a_warning() {
+        let mut level = classic();
+        wall_off(&mut level, (0, 14));
+        let problems = validate_level(&level);
+        let tunnel = find(&problems, "Tunnel comes out into a wall");
+        assert_eq!(tunnel.tiles, vec![(27, 14)]);
+    }
+
+    #[test]
+    fn errors_come_before_warnings() {
+        let mut level = classic();
+        wall_off(&mut level, (2, 1));
+        wall_off(&mut level, (1, 2));
+        wall_off(&mut level, (0, 14));
+        let problems = validate_level(&level);
+        let first_warning = problems
+            .iter()
+            .position(|problem| problem.severity == Severity::Warning)
+            .unwrap();
+        assert!(first_warning > 0);
+        assert!(problems[first_warning..]
+            .iter()
+            .all(|problem| problem.severity == Severity::Warning));
+    }
+}